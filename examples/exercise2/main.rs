@@ -1,6 +1,5 @@
-use std::{ffi::CString, mem};
+use std::{ffi::CString, mem, rc::Rc};
 
-use gl::types::GLsizei;
 use glam::Vec2;
 use glfw::Action;
 use itugl::{
@@ -8,17 +7,19 @@ use itugl::{
     core::{
         buffer_object::{BufferObject, Usage},
         color::Color,
-        data::Type,
         object::Object,
+        render_state::{BlendState, RenderState},
     },
     geometry::{
-        vertex_array_object::VertexArrayObject, vertex_attribute::VertexAttribute,
-        vertex_buffer_object::VertexBufferObject,
+        vertex_array_object::VertexArrayObject, vertex_buffer_object::VertexBufferObject,
+        vertex_layout::Vertex,
     },
-    shader::{Location, Program, Shader},
+    material::Material,
+    random::{self, RandomExt},
+    shader::{Program, Shader},
 };
 use rand::Rng;
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Vertex)]
 #[repr(C)]
 struct Particle {
     position: Vec2,
@@ -29,23 +30,12 @@ struct Particle {
     velocity: Vec2,
 }
 
-const VERTEX_ATTRIBUTES: [VertexAttribute; 6] = [
-    VertexAttribute::new(Type::Float, 2, false), // position
-    VertexAttribute::new(Type::Float, 1, false), // size
-    VertexAttribute::new(Type::Float, 1, false), // birth
-    VertexAttribute::new(Type::Float, 1, false), // duration
-    VertexAttribute::new(Type::Float, 4, false), // color
-    VertexAttribute::new(Type::Float, 2, false), // velocity
-];
-
 #[derive(Debug)]
 pub struct ParticlesApplication {
     window: Window,
-    program: Program,
+    material: Material,
     delta_time: f32,
     current_time: f32,
-    current_time_uniform: Location,
-    current_gravity_uniform: Location,
     mouse_position: Vec2,
     particle_count: usize,
     particle_capacity: usize,
@@ -80,10 +70,11 @@ impl Application for ParticlesApplication {
     fn new(width: u32, height: u32, title: &str) -> Self {
         let mut window = Window::new(width, height, title, glfw::WindowMode::Windowed);
         window.enable_feature(gl::PROGRAM_POINT_SIZE);
-        window.enable_feature(gl::BLEND);
-        unsafe { gl::BlendFunc(gl::SRC_ALPHA, gl::ONE) };
+        window.set_render_state(&RenderState::default().with_blend(BlendState::ADDITIVE));
         window.set_vsync(true);
-        let program = build_shaders();
+        let material = Material::new(Rc::new(build_shaders()))
+            .with("Gravity", -9.8)
+            .unwrap();
         let particle_capacity = 2048;
         // initialize geometry
         let vbo = VertexBufferObject::new();
@@ -91,23 +82,18 @@ impl Application for ParticlesApplication {
 
         let vao = VertexArrayObject::new();
         vao.bind();
-        let stride = mem::size_of::<Particle>() as GLsizei;
-        let mut offset = 0;
-        for (location, attribute) in VERTEX_ATTRIBUTES.into_iter().enumerate() {
-            vao.set_attribute(location as u32, &attribute, offset, stride);
-            offset += attribute.get_size();
-        }
+        vbo.bind();
+        Particle::layout().apply(&vao);
         vao.unbind();
+        vbo.unbind();
         Self {
             delta_time: 0.0,
             current_time: 0.0,
-            current_time_uniform: program.get_uniform_location(c"CurrentTime"),
-            current_gravity_uniform: program.get_uniform_location(c"Gravity"),
             mouse_position: window.get_mouse_position(false),
             particle_count: 0,
             particle_capacity,
             window,
-            program,
+            material,
             vao,
             vbo,
         }
@@ -140,10 +126,13 @@ impl Application for ParticlesApplication {
         let mouse_pos = self.window.get_mouse_position(true);
 
         if self.window.is_mouse_button_pressed(glfw::MouseButtonLeft) == Action::Press {
-            let mut rng = rand::rng();
-            let size = rng.random_range(10.0..=30.0);
-            let duration = rng.random_range(1.0..=2.0);
-            let color = Color::random();
+            let (size, duration, color) = random::with(|rng| {
+                (
+                    rng.random_range(10.0..=30.0),
+                    rng.random_range(1.0..=2.0),
+                    rng.random_color(),
+                )
+            });
             let velocity = 0.5 * (mouse_pos - self.mouse_position) * self.delta_time;
             self.emit_particle(mouse_pos, size, duration, color, velocity);
         }
@@ -152,11 +141,8 @@ impl Application for ParticlesApplication {
 
     fn render(&mut self) {
         self.window.clear_color(0.0, 0.0, 0.0, 0.0);
-        self.program.set_used();
-        self.program
-            .set_uniform1f(self.current_time_uniform, self.current_time);
-        self.program
-            .set_uniform1f(self.current_gravity_uniform, -9.8);
+        self.material.set("CurrentTime", self.current_time).unwrap();
+        self.material.apply();
         let particle_count = self.particle_count as i32;
         let particle_capacity = self.particle_capacity as i32;
         self.vao.bind();
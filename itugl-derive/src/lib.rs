@@ -0,0 +1,184 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, Meta};
+
+// Generates `itugl::geometry::vertex_layout::Vertex` for a #[repr(C)] struct.
+// Every field becomes an attribute at the next free location, its type mapped
+// through `VertexAttributeType`. Fields accept:
+//   #[normalized]     integer data is normalized to [0, 1] / [-1, 1], an error on
+//                     float fields
+//   #[location = N]   explicit location, following fields continue from N + 1. Two
+//                     attributes at the same location are an error
+//   #[divisor = N]    per instance attribute, advancing every N instances
+//   #[skip]           field is not exposed as an attribute
+#[proc_macro_derive(Vertex, attributes(normalized, location, divisor, skip))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !has_repr_c(input) {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Vertex can only be derived for #[repr(C)] structs",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Vertex can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Vertex can only be derived for structs with named fields",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut location: u32 = 0;
+    // Field at each location used so far
+    let mut used: Vec<(u32, &syn::Ident)> = vec![];
+    let mut attributes = vec![];
+    let mut checks = vec![];
+    for field in &fields.named {
+        let options = FieldOptions::parse(field)?;
+        if options.skip {
+            continue;
+        }
+        if let Some(explicit) = options.location {
+            location = explicit;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        if let Some((_, other)) = used.iter().find(|(used, _)| *used == location) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("location {location} is already used by `{other}`"),
+            ));
+        }
+        used.push((location, ident));
+        let ty = &field.ty;
+        let normalized = options.normalized;
+        let divisor = options.divisor;
+        if normalized {
+            // The field type is only known after expansion, so check it in a constant
+            let message = format!("#[normalized] has no effect on the float field `{ident}`");
+            checks.push(quote! {
+                ::core::assert!(
+                    !<#ty as ::itugl::geometry::vertex_layout::VertexAttributeType>::ATTRIBUTE
+                        .data_type()
+                        .is_float(),
+                    #message
+                );
+            });
+        }
+        attributes.push(quote! {
+            (
+                #location,
+                ::itugl::geometry::vertex_attribute::Layout::new(
+                    ::itugl::geometry::vertex_attribute::VertexAttribute::new(
+                        <#ty as ::itugl::geometry::vertex_layout::VertexAttributeType>::ATTRIBUTE.data_type(),
+                        <#ty as ::itugl::geometry::vertex_layout::VertexAttributeType>::ATTRIBUTE.components(),
+                        #normalized,
+                    )
+                    .with_divisor(#divisor),
+                    ::core::mem::offset_of!(Self, #ident) as i32,
+                    stride,
+                ),
+            )
+        });
+        location += 1;
+    }
+
+    // Constants outside the impl are checked by `cargo check`, those in generic functions only
+    // once the function is instantiated for a type
+    let (outer_checks, inner_checks) = if input.generics.params.is_empty() {
+        (quote! { const _: () = { #(#checks)* }; }, quote! {})
+    } else {
+        (quote! {}, quote! { const { #(#checks)* } })
+    };
+
+    Ok(quote! {
+        #outer_checks
+
+        // Sound because every attribute is a field of this #[repr(C)] struct, described by the
+        // `VertexAttributeType` of the field type
+        unsafe impl #impl_generics ::itugl::geometry::vertex_layout::Vertex for #name #ty_generics #where_clause {
+            fn layout() -> ::itugl::geometry::vertex_layout::VertexLayout {
+                #inner_checks
+                let stride = ::core::mem::size_of::<Self>() as i32;
+                ::itugl::geometry::vertex_layout::VertexLayout::new(
+                    stride,
+                    ::std::vec![#(#attributes),*],
+                )
+            }
+        }
+    })
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        let mut found = false;
+        if attr.path().is_ident("repr") {
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("C") {
+                    found = true;
+                }
+                Ok(())
+            });
+        }
+        found
+    })
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    normalized: bool,
+    location: Option<u32>,
+    divisor: u32,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+        for attr in &field.attrs {
+            if attr.path().is_ident("normalized") {
+                attr.meta.require_path_only()?;
+                options.normalized = true;
+            } else if attr.path().is_ident("skip") {
+                attr.meta.require_path_only()?;
+                options.skip = true;
+            } else if attr.path().is_ident("location") {
+                options.location = Some(parse_int(attr)?);
+            } else if attr.path().is_ident("divisor") {
+                options.divisor = parse_int(attr)?;
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Parse the integer in #[name = N]
+fn parse_int(attr: &syn::Attribute) -> syn::Result<u32> {
+    let Meta::NameValue(name_value) = &attr.meta else {
+        return Err(syn::Error::new_spanned(attr, "expected #[name = N]"));
+    };
+    let Expr::Lit(expr) = &name_value.value else {
+        return Err(syn::Error::new_spanned(
+            &name_value.value,
+            "expected an integer",
+        ));
+    };
+    let Lit::Int(value) = &expr.lit else {
+        return Err(syn::Error::new_spanned(&expr.lit, "expected an integer"));
+    };
+    value.base10_parse()
+}
@@ -1,5 +1,7 @@
 use glfw::Context;
 
+use crate::random;
+
 use super::window::Window;
 
 pub trait Application: Sized {
@@ -14,6 +16,8 @@ pub trait Application: Sized {
     fn run(mut self) {
         // If the application is not in error state, run
 
+        // Same random values on every run unless the application picks its own seed
+        random::seed(self.seed());
         self.initialize();
 
         // current time when the application started
@@ -34,6 +38,10 @@ pub trait Application: Sized {
             self.window_mut().glfw_mut().poll_events();
         }
     }
+    // Seed of the random source when the application starts
+    fn seed(&self) -> u64 {
+        random::DEFAULT_SEED
+    }
     fn initialize(&mut self) {}
     fn update(&mut self) {}
     fn render(&mut self) {}
@@ -1,13 +1,23 @@
+use std::rc::Rc;
+
 use gl::types::GLvoid;
 use glam::Vec2;
 use glfw::{fail_on_errors, Action, Context, GlfwReceiver, PWindow, WindowEvent, WindowMode};
 
-use crate::error::check_gl_error;
+use crate::{
+    core::{
+        capabilities::Capabilities,
+        render_state::{PolygonMode, RenderState},
+        state,
+    },
+    error::check_gl_error,
+};
 
 #[derive(Debug)]
 pub struct Window {
     pub inner_window: PWindow,
     pub events: GlfwReceiver<(f64, WindowEvent)>,
+    capabilities: Rc<Capabilities>,
 }
 
 impl Window {
@@ -33,18 +43,22 @@ impl Window {
     }
 
     pub fn enable_feature(&mut self, feature: gl::types::GLenum) {
-        unsafe { gl::Enable(feature) };
+        state::with(|state| state.enable(feature));
     }
     pub fn disable_feature(&mut self, feature: gl::types::GLenum) {
-        unsafe { gl::Disable(feature) };
+        state::with(|state| state.disable(feature));
     }
     pub fn set_wireframe(&mut self, enabled: bool) {
-        unsafe {
-            gl::PolygonMode(
-                gl::FRONT_AND_BACK,
-                if enabled { gl::LINE } else { gl::FILL },
-            );
+        let mode = if enabled {
+            PolygonMode::Line
+        } else {
+            PolygonMode::Fill
         };
+        state::with(|state| state.set_polygon_mode(mode as gl::types::GLenum));
+    }
+    // Blend, depth, stencil, rasterizer and color mask state all at once
+    pub fn set_render_state(&mut self, render_state: &RenderState) {
+        state::with(|state| render_state.apply(state));
     }
     pub fn set_vsync(&mut self, value: bool) {
         self.glfw_mut().set_swap_interval(if value {
@@ -69,20 +83,42 @@ impl Window {
         inner_window.set_key_polling(true);
 
         gl::load_with(|s| glfw.get_proc_address_raw(s));
-        unsafe { gl::Enable(gl::DEBUG_OUTPUT) }
-        unsafe {
-            gl::DebugMessageCallback(
-                Some(crate::error::debug_callback),
-                std::ptr::null_mut::<GLvoid>(),
-            );
-        };
-        check_gl_error();
+        // Nothing cached applies to the new context, which may support Direct State Access
+        let capabilities = state::with(|state| {
+            state.detect();
+            state.capabilities().cloned().unwrap_or_default()
+        });
+        log::info!(
+            "OpenGL {} (GLSL {}) on {} by {}",
+            capabilities.version_string,
+            capabilities.glsl_version_number(),
+            capabilities.renderer,
+            capabilities.vendor
+        );
+        // Debug output is core since 4.3, macOS stops at 4.1
+        if capabilities.supports_version(4, 3) || capabilities.has_extension("GL_KHR_debug") {
+            state::with(|state| state.enable(gl::DEBUG_OUTPUT));
+            unsafe {
+                gl::DebugMessageCallback(
+                    Some(crate::error::debug_callback),
+                    std::ptr::null_mut::<GLvoid>(),
+                );
+            };
+            check_gl_error();
+        }
 
         Self {
             inner_window,
             events,
+            capabilities,
         }
     }
+    // What the context of this window supports
+    #[must_use]
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
     pub fn clear(&self, r: f32, g: f32, b: f32, a: f32, depth: f64) {
         let mut mask = 0;
 
@@ -117,7 +153,6 @@ impl Window {
     }
 
     pub fn set_viewport(&self, width: i32, height: i32) {
-        unsafe { gl::Viewport(0, 0, width, height) };
-        check_gl_error();
+        state::with(|state| state.set_viewport(0, 0, width, height));
     }
 }
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod camera;
+pub mod controller;
+pub mod frustum;
@@ -0,0 +1,266 @@
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+
+use super::frustum::{DepthRange, Frustum};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // Vertical field of view in radians
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    // `height` is the vertical size of the view volume in world units
+    Orthographic { height: f32, near: f32, far: f32 },
+    // Depth goes from 1 at the near plane to 0 at infinity. Needs glClipControl with
+    // GL_ZERO_TO_ONE (GL 4.5 or ARB_clip_control), a GREATER depth test and clearing depth to 0
+    InfiniteReverseZ { fov_y: f32, near: f32 },
+}
+
+impl Projection {
+    #[must_use]
+    pub fn matrix(&self, aspect_ratio: f32) -> Mat4 {
+        match *self {
+            Self::Perspective { fov_y, near, far } => {
+                Mat4::perspective_rh_gl(fov_y, aspect_ratio, near, far)
+            }
+            Self::Orthographic { height, near, far } => {
+                let half = Vec2::new(height * aspect_ratio, height) * 0.5;
+                Mat4::orthographic_rh_gl(-half.x, half.x, -half.y, half.y, near, far)
+            }
+            Self::InfiniteReverseZ { fov_y, near } => {
+                Mat4::perspective_infinite_reverse_rh(fov_y, aspect_ratio, near)
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn depth_range(&self) -> DepthRange {
+        match self {
+            Self::Perspective { .. } | Self::Orthographic { .. } => DepthRange::NegativeOneToOne,
+            Self::InfiniteReverseZ { .. } => DepthRange::ReversedZeroToOne,
+        }
+    }
+}
+
+// Camera looking down its local -Z axis with +Y up, as OpenGL expects
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    position: Vec3,
+    orientation: Quat,
+    projection: Projection,
+    aspect_ratio: f32,
+}
+
+impl Camera {
+    #[must_use]
+    pub const fn new(projection: Projection, aspect_ratio: f32) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            projection,
+            aspect_ratio,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_position(mut self, position: Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    #[must_use]
+    pub fn looking_at(mut self, target: Vec3, up: Vec3) -> Self {
+        self.look_at(target, up);
+        self
+    }
+
+    #[must_use]
+    pub const fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub const fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    #[must_use]
+    pub const fn orientation(&self) -> Quat {
+        self.orientation
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        self.orientation = orientation.normalize();
+    }
+
+    #[must_use]
+    pub const fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub const fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    #[must_use]
+    pub const fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    // Call when the framebuffer is resized
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        if aspect_ratio.is_finite() && aspect_ratio > 0.0 {
+            self.aspect_ratio = aspect_ratio;
+        }
+    }
+
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        self.look_to(target - self.position, up);
+    }
+
+    // Orient the camera along `direction`, keeping `up` as close to the screen up as possible
+    pub fn look_to(&mut self, direction: Vec3, up: Vec3) {
+        let Some(forward) = direction.try_normalize() else {
+            return;
+        };
+        let right = forward
+            .cross(up)
+            .try_normalize()
+            .unwrap_or_else(|| forward.any_orthonormal_vector());
+        let camera_up = right.cross(forward);
+        self.orientation = Quat::from_mat3(&Mat3::from_cols(right, camera_up, -forward));
+    }
+
+    #[must_use]
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::NEG_Z
+    }
+
+    #[must_use]
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    #[must_use]
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    // Camera to world transform
+    #[must_use]
+    pub fn world_matrix(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.orientation, self.position)
+    }
+
+    #[must_use]
+    pub fn view_matrix(&self) -> Mat4 {
+        self.world_matrix().inverse()
+    }
+
+    #[must_use]
+    pub fn projection_matrix(&self) -> Mat4 {
+        self.projection.matrix(self.aspect_ratio)
+    }
+
+    #[must_use]
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    #[must_use]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(
+            &self.view_projection_matrix(),
+            self.projection.depth_range(),
+        )
+    }
+
+    // World space ray through a point in normalized device coordinates, as returned by
+    // `Window::get_mouse_position(true)`. Returns the origin and the normalized direction
+    #[must_use]
+    pub fn screen_ray(&self, ndc: Vec2) -> (Vec3, Vec3) {
+        let inverse = self.view_projection_matrix().inverse();
+        // Points on the near plane and halfway in depth, valid for every depth range
+        let near_depth = match self.projection.depth_range() {
+            DepthRange::NegativeOneToOne => -1.0,
+            DepthRange::ZeroToOne => 0.0,
+            DepthRange::ReversedZeroToOne => 1.0,
+        };
+        let near = inverse.project_point3(ndc.extend(near_depth));
+        let middle = inverse.project_point3(ndc.extend(0.5));
+        (near, (middle - near).normalize_or(self.forward()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{a} != {b}");
+    }
+
+    #[test]
+    fn look_to_builds_an_orthonormal_basis() {
+        let mut camera = Camera::new(
+            Projection::Orthographic {
+                height: 2.0,
+                near: 0.1,
+                far: 10.0,
+            },
+            1.0,
+        );
+        camera.look_to(Vec3::new(1.0, 1.0, 0.0), Vec3::Y);
+        assert_close(camera.forward(), Vec3::new(1.0, 1.0, 0.0).normalize());
+        assert_close(camera.right(), Vec3::Z);
+        assert_close(camera.up(), Vec3::new(-1.0, 1.0, 0.0).normalize());
+
+        // Looking straight along up still gives a valid orientation
+        camera.look_to(Vec3::Y * 3.0, Vec3::Y);
+        assert_close(camera.forward(), Vec3::Y);
+        assert!(camera.right().dot(Vec3::Y).abs() < 1e-4);
+        assert!(camera.orientation().is_normalized());
+
+        // A zero direction keeps the previous orientation
+        let orientation = camera.orientation();
+        camera.look_to(Vec3::ZERO, Vec3::Y);
+        assert_eq!(camera.orientation(), orientation);
+    }
+
+    #[test]
+    fn screen_rays_start_on_the_near_plane() {
+        let projections = [
+            Projection::Perspective {
+                fov_y: 1.0,
+                near: 0.5,
+                far: 100.0,
+            },
+            Projection::Orthographic {
+                height: 4.0,
+                near: 0.5,
+                far: 100.0,
+            },
+            Projection::InfiniteReverseZ {
+                fov_y: 1.0,
+                near: 0.5,
+            },
+        ];
+        for projection in projections {
+            let camera = Camera::new(projection, 1.5)
+                .with_position(Vec3::new(1.0, 2.0, 3.0))
+                .looking_at(Vec3::new(1.0, 2.0, -10.0), Vec3::Y);
+            let (origin, direction) = camera.screen_ray(Vec2::ZERO);
+            assert_close(origin, Vec3::new(1.0, 2.0, 2.5));
+            assert_close(direction, Vec3::NEG_Z);
+
+            // Points along an off center ray project back to the same screen position
+            let ndc = Vec2::new(0.5, -0.25);
+            let (origin, direction) = camera.screen_ray(ndc);
+            assert!(direction.is_normalized());
+            let view_projection = camera.view_projection_matrix();
+            for distance in [0.0, 1.0, 10.0] {
+                let projected = view_projection.project_point3(origin + direction * distance);
+                assert!(
+                    projected.truncate().abs_diff_eq(ndc, 1e-4),
+                    "{projection:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,443 @@
+use glam::{Quat, Vec2, Vec3};
+use glfw::{Action, Key, MouseButton, WindowEvent};
+
+use crate::application::window::Window;
+
+use super::camera::Camera;
+
+// Input for one frame, independent of where it comes from
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraInput {
+    // Requested movement in camera axes: x right, y up, z forward, each in [-1, 1]
+    pub movement: Vec3,
+    // Cursor movement in pixels since the last frame, +Y down like window coordinates
+    pub cursor_delta: Vec2,
+    // Cursor in normalized device coordinates
+    pub cursor: Vec2,
+    // Scroll wheel steps since the last frame, positive away from the user
+    pub zoom: f32,
+    pub primary_button: bool,
+    pub secondary_button: bool,
+    pub fast: bool,
+}
+
+// Collects CameraInput from a window: WASD to move, E/Space and Q/Ctrl for up and down, Shift
+// to go faster, left and right mouse buttons and the scroll wheel
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputTracker {
+    last_cursor: Option<Vec2>,
+    zoom: f32,
+}
+
+impl InputTracker {
+    // Scroll events are only delivered once scroll polling is enabled
+    #[must_use]
+    pub fn new(window: &mut Window) -> Self {
+        window.inner_window.set_scroll_polling(true);
+        Self::default()
+    }
+
+    // Forward the window events here, only scrolling is used
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Scroll(_, y) = event {
+            self.zoom += *y as f32;
+        }
+    }
+
+    pub fn poll(&mut self, window: &Window) -> CameraInput {
+        let inner = &window.inner_window;
+        let pressed = |keys: &[Key]| keys.iter().any(|&key| inner.get_key(key) == Action::Press);
+        let axis = |positive: &[Key], negative: &[Key]| {
+            f32::from(u8::from(pressed(positive))) - f32::from(u8::from(pressed(negative)))
+        };
+        let movement = Vec3::new(
+            axis(&[Key::D], &[Key::A]),
+            axis(&[Key::E, Key::Space], &[Key::Q, Key::LeftControl]),
+            axis(&[Key::W], &[Key::S]),
+        );
+
+        let (x, y) = inner.get_cursor_pos();
+        let cursor = Vec2::new(x as f32, y as f32);
+        let cursor_delta = self.last_cursor.map_or(Vec2::ZERO, |last| cursor - last);
+        self.last_cursor = Some(cursor);
+
+        CameraInput {
+            movement,
+            cursor_delta,
+            cursor: window.get_mouse_position(true),
+            zoom: std::mem::take(&mut self.zoom),
+            primary_button: inner.get_mouse_button(MouseButton::Button1) == Action::Press,
+            secondary_button: inner.get_mouse_button(MouseButton::Button2) == Action::Press,
+            fast: pressed(&[Key::LeftShift, Key::RightShift]),
+        }
+    }
+}
+
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput, delta_time: f32);
+}
+
+// Forward direction at yaw and pitch 0, perpendicular to `up`
+fn reference_forward(up: Vec3) -> Vec3 {
+    Vec3::NEG_Z
+        .reject_from(up)
+        .try_normalize()
+        .unwrap_or_else(|| Vec3::Y.reject_from(up).normalize())
+}
+
+// Yaw around `up` and pitch above the horizon of a direction
+fn yaw_pitch(direction: Vec3, up: Vec3) -> (f32, f32) {
+    let direction = direction.normalize_or(reference_forward(up));
+    let pitch = direction.dot(up).clamp(-1.0, 1.0).asin();
+    let reference = reference_forward(up);
+    let horizontal = direction.reject_from(up).normalize_or(reference);
+    let yaw = reference
+        .cross(horizontal)
+        .dot(up)
+        .atan2(reference.dot(horizontal));
+    (yaw, pitch)
+}
+
+fn direction_from(yaw: f32, pitch: f32, up: Vec3) -> Vec3 {
+    let reference = reference_forward(up);
+    let right = reference.cross(up);
+    Quat::from_axis_angle(up, yaw) * Quat::from_axis_angle(right, pitch) * reference
+}
+
+// Keep away from the poles where the look direction becomes parallel to up
+const MAX_PITCH: f32 = 89.0_f32.to_radians();
+
+// Free flying camera, moves along the view direction and looks around while the secondary
+// mouse button is held
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlyController {
+    up: Vec3,
+    yaw: f32,
+    pitch: f32,
+    speed: f32,
+    fast_multiplier: f32,
+    // Radians per pixel of cursor movement
+    sensitivity: f32,
+}
+
+impl FlyController {
+    // Start from the current orientation of the camera
+    #[must_use]
+    pub fn new(camera: &Camera, up: Vec3) -> Self {
+        let (yaw, pitch) = yaw_pitch(camera.forward(), up);
+        Self {
+            up,
+            yaw,
+            pitch,
+            speed: 1.0,
+            fast_multiplier: 4.0,
+            sensitivity: 0.003,
+        }
+    }
+
+    // Units per second
+    #[must_use]
+    pub const fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    fn look(&mut self, camera: &mut Camera, cursor_delta: Vec2) {
+        self.yaw -= cursor_delta.x * self.sensitivity;
+        self.pitch = (self.pitch - cursor_delta.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        camera.look_to(direction_from(self.yaw, self.pitch, self.up), self.up);
+    }
+
+    fn step(&self, input: &CameraInput, delta_time: f32) -> f32 {
+        let multiplier = if input.fast {
+            self.fast_multiplier
+        } else {
+            1.0
+        };
+        self.speed * multiplier * delta_time
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput, delta_time: f32) {
+        if input.secondary_button {
+            self.look(camera, input.cursor_delta);
+        }
+        let movement = camera.right() * input.movement.x
+            + camera.up() * input.movement.y
+            + camera.forward() * input.movement.z;
+        let step = self.step(input, delta_time);
+        camera.set_position(camera.position() + movement * step);
+    }
+}
+
+// Walking camera: always looks with the cursor, which is expected to be captured with
+// CursorMode::Disabled. Forward and sideways movement stay on the plane perpendicular to `up`
+// whatever the pitch, vertical movement goes straight along `up`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FirstPersonController {
+    fly: FlyController,
+}
+
+impl FirstPersonController {
+    #[must_use]
+    pub fn new(camera: &Camera, up: Vec3) -> Self {
+        Self {
+            fly: FlyController::new(camera, up),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_speed(mut self, speed: f32) -> Self {
+        self.fly = self.fly.with_speed(speed);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.fly = self.fly.with_sensitivity(sensitivity);
+        self
+    }
+}
+
+impl CameraController for FirstPersonController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput, delta_time: f32) {
+        self.fly.look(camera, input.cursor_delta);
+        let up = self.fly.up;
+        let forward = direction_from(self.fly.yaw, 0.0, up);
+        let right = forward.cross(up);
+        let movement =
+            right * input.movement.x + up * input.movement.y + forward * input.movement.z;
+        let step = self.fly.step(input, delta_time);
+        camera.set_position(camera.position() + movement.normalize_or_zero() * step);
+    }
+}
+
+// Turntable around a target: the primary button rotates around `up`, the secondary button pans
+// and scrolling zooms
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitController {
+    target: Vec3,
+    up: Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+    sensitivity: f32,
+    // Fraction of the distance gained or lost per scroll step
+    zoom_speed: f32,
+    // Fraction of the distance panned per pixel
+    pan_speed: f32,
+}
+
+impl OrbitController {
+    #[must_use]
+    pub fn new(camera: &Camera, target: Vec3, up: Vec3) -> Self {
+        let offset = camera.position() - target;
+        let (yaw, pitch) = yaw_pitch(-offset, up);
+        Self {
+            target,
+            up,
+            distance: offset.length().max(f32::EPSILON),
+            yaw,
+            pitch,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            pan_speed: 0.001,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_zoom_speed(mut self, zoom_speed: f32) -> Self {
+        self.zoom_speed = zoom_speed;
+        self
+    }
+
+    #[must_use]
+    pub const fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    #[must_use]
+    pub const fn distance(&self) -> f32 {
+        self.distance
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput, _delta_time: f32) {
+        if input.primary_button {
+            self.yaw -= input.cursor_delta.x * self.sensitivity;
+            self.pitch =
+                (self.pitch + input.cursor_delta.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        if input.secondary_button {
+            // Panning scales with the distance so it feels the same when zoomed in or out
+            let pan = camera.right() * -input.cursor_delta.x + camera.up() * input.cursor_delta.y;
+            self.target += pan * self.pan_speed * self.distance;
+        }
+        self.distance *= (1.0 - self.zoom_speed).powf(input.zoom);
+        let direction = direction_from(self.yaw, self.pitch, self.up);
+        camera.set_position(self.target - direction * self.distance);
+        camera.look_to(direction, self.up);
+    }
+}
+
+// Free rotation around a target following a virtual trackball under the cursor (Shoemake),
+// the secondary button pans and scrolling zooms
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArcballController {
+    target: Vec3,
+    last_cursor: Option<Vec2>,
+    zoom_speed: f32,
+    // Fraction of the distance panned per pixel
+    pan_speed: f32,
+}
+
+impl ArcballController {
+    #[must_use]
+    pub const fn new(target: Vec3) -> Self {
+        Self {
+            target,
+            last_cursor: None,
+            zoom_speed: 0.1,
+            pan_speed: 0.001,
+        }
+    }
+
+    #[must_use]
+    pub const fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    // Point on the unit trackball under a cursor in normalized device coordinates
+    fn project(cursor: Vec2) -> Vec3 {
+        let length_squared = cursor.length_squared();
+        if length_squared <= 1.0 {
+            cursor.extend((1.0 - length_squared).sqrt())
+        } else {
+            cursor.normalize().extend(0.0)
+        }
+    }
+}
+
+impl CameraController for ArcballController {
+    fn update(&mut self, camera: &mut Camera, input: &CameraInput, _delta_time: f32) {
+        let offset = camera.position() - self.target;
+        let distance = offset.length();
+        if input.primary_button {
+            if let Some(last) = self.last_cursor {
+                // Dragging turns the ball, so the camera turns the opposite way around the target
+                let local =
+                    Quat::from_rotation_arc(Self::project(input.cursor), Self::project(last));
+                let rotation = camera.orientation() * local * camera.orientation().inverse();
+                camera.set_orientation(rotation * camera.orientation());
+                camera.set_position(self.target + rotation * offset);
+            }
+            self.last_cursor = Some(input.cursor);
+        } else {
+            self.last_cursor = None;
+        }
+        if input.secondary_button {
+            let pan = camera.right() * -input.cursor_delta.x + camera.up() * input.cursor_delta.y;
+            let pan = pan * self.pan_speed * distance;
+            self.target += pan;
+            camera.set_position(camera.position() + pan);
+        }
+        if input.zoom != 0.0 {
+            let scale = (1.0 - self.zoom_speed).powf(input.zoom);
+            camera.set_position(self.target + (camera.position() - self.target) * scale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::camera::camera::Projection;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Projection::Perspective {
+                fov_y: 1.0,
+                near: 0.1,
+                far: 100.0,
+            },
+            1.0,
+        )
+    }
+
+    #[test]
+    fn yaw_and_pitch_round_trip() {
+        for up in [Vec3::Y, Vec3::Z] {
+            for direction in [
+                Vec3::new(1.0, 0.5, -2.0),
+                Vec3::new(-3.0, -1.0, 0.5),
+                Vec3::new(0.0, 0.2, 1.0),
+            ] {
+                let (yaw, pitch) = yaw_pitch(direction, up);
+                let result = direction_from(yaw, pitch, up);
+                assert!(
+                    result.abs_diff_eq(direction.normalize(), 1e-4),
+                    "{result} {direction}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn orbit_keeps_looking_at_the_target() {
+        let target = Vec3::new(1.0, 0.0, 0.0);
+        let mut camera = camera().with_position(Vec3::new(1.0, 0.0, 5.0));
+        let mut controller = OrbitController::new(&camera, target, Vec3::Y);
+        let input = CameraInput {
+            cursor_delta: Vec2::new(40.0, -20.0),
+            primary_button: true,
+            ..CameraInput::default()
+        };
+        controller.update(&mut camera, &input, 0.016);
+        assert!((camera.position().distance(target) - 5.0).abs() < 1e-4);
+        let to_target = (target - camera.position()).normalize();
+        assert!(camera.forward().abs_diff_eq(to_target, 1e-4));
+
+        // Scrolling forward zooms in
+        let input = CameraInput {
+            zoom: 2.0,
+            ..CameraInput::default()
+        };
+        controller.update(&mut camera, &input, 0.016);
+        assert!((controller.distance() - 5.0 * 0.81).abs() < 1e-4);
+        assert!((camera.position().distance(target) - controller.distance()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn first_person_moves_on_the_ground_plane() {
+        let mut camera = camera().looking_at(Vec3::new(0.0, -1.0, -1.0), Vec3::Y);
+        let mut controller = FirstPersonController::new(&camera, Vec3::Y).with_speed(2.0);
+        let input = CameraInput {
+            movement: Vec3::Z,
+            ..CameraInput::default()
+        };
+        controller.update(&mut camera, &input, 0.5);
+        assert!(camera.position().abs_diff_eq(Vec3::NEG_Z, 1e-4));
+
+        // The fly controller goes along the view direction instead
+        let mut camera = camera.with_position(Vec3::ZERO);
+        let mut controller = FlyController::new(&camera, Vec3::Y).with_speed(2.0);
+        controller.update(&mut camera, &input, 0.5);
+        let expected = Vec3::new(0.0, -1.0, -1.0).normalize();
+        assert!(camera.position().abs_diff_eq(expected, 1e-4));
+    }
+}
@@ -0,0 +1,109 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::geometry::processing::{BoundingBox, BoundingSphere};
+
+// Clip space depth convention of a projection matrix
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthRange {
+    // OpenGL default, -w <= z <= w
+    NegativeOneToOne,
+    // glClipControl with GL_ZERO_TO_ONE, 0 <= z <= w with the near plane at 0
+    ZeroToOne,
+    // Same clip volume with the near plane at z = w, as made by reverse-Z projections
+    ReversedZeroToOne,
+}
+
+// Six planes with normals pointing inside, as (normal, distance) so dot(normal, p) + d >= 0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // Planes of a view-projection matrix (Gribb & Hartmann). Planes at infinity never cull
+    #[must_use]
+    pub fn from_matrix(view_projection: &Mat4, depth_range: DepthRange) -> Self {
+        let rows = [0, 1, 2, 3].map(|row| view_projection.row(row));
+        let (near, far) = match depth_range {
+            DepthRange::NegativeOneToOne => (rows[3] + rows[2], rows[3] - rows[2]),
+            DepthRange::ZeroToOne => (rows[2], rows[3] - rows[2]),
+            DepthRange::ReversedZeroToOne => (rows[3] - rows[2], rows[2]),
+        };
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            near,
+            far,
+        ]
+        .map(|plane| {
+            let length = plane.truncate().length();
+            if length > f32::EPSILON {
+                plane / length
+            } else {
+                Vec4::W
+            }
+        });
+        Self { planes }
+    }
+
+    // Left, right, bottom, top, near and far
+    #[must_use]
+    pub const fn planes(&self) -> &[Vec4; 6] {
+        &self.planes
+    }
+
+    #[must_use]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(point.extend(1.0)) >= 0.0)
+    }
+
+    #[must_use]
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(sphere.center.extend(1.0)) >= -sphere.radius)
+    }
+
+    // Conservative test, boxes near the frustum corners may pass while outside
+    #[must_use]
+    pub fn intersects_box(&self, bounds: &BoundingBox) -> bool {
+        if bounds.is_empty() {
+            return false;
+        }
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), bounds.max, bounds.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_and_far_planes_keep_their_order() {
+        let perspective = Mat4::perspective_rh_gl(1.0, 1.5, 0.5, 100.0);
+        let zero_to_one = Mat4::perspective_rh(1.0, 1.5, 0.5, 100.0);
+        let reversed = Mat4::perspective_infinite_reverse_rh(1.0, 1.5, 0.5);
+        for (matrix, depth_range) in [
+            (perspective, DepthRange::NegativeOneToOne),
+            (zero_to_one, DepthRange::ZeroToOne),
+            (reversed, DepthRange::ReversedZeroToOne),
+        ] {
+            let planes = Frustum::from_matrix(&matrix, depth_range).planes;
+            let near = planes[4];
+            assert!(
+                near.abs_diff_eq(Vec4::new(0.0, 0.0, -1.0, -0.5), 1e-4),
+                "{near}"
+            );
+            assert!(planes[5].dot(Vec4::new(0.0, 0.0, -99.0, 1.0)) >= 0.0);
+        }
+    }
+}
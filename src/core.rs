@@ -1,4 +1,9 @@
 pub mod buffer_object;
+pub mod capabilities;
 pub mod color;
 pub mod data;
+pub mod framebuffer;
 pub mod object;
+pub mod render_state;
+pub mod state;
+pub mod texture;
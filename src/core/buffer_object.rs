@@ -1,10 +1,15 @@
-use std::{os::raw::c_void, ptr::null};
+use std::{ops::Range, os::raw::c_void, ptr::null};
 
-use gl::types::{GLenum, GLsizeiptr};
+use gl::types::{GLenum, GLint, GLintptr, GLsizeiptr};
 
-use crate::error::check_gl_error;
+use crate::error::{check_gl_error, gl_error};
 
-use super::object::{NullHandle, Object};
+use super::{
+    capabilities::Capabilities,
+    data::Pod,
+    object::{Handle, NullHandle, Object},
+    state,
+};
 #[repr(u32)]
 #[derive(Clone, Copy, Debug)]
 pub enum Target {
@@ -12,6 +17,12 @@ pub enum Target {
     ArrayBuffer = gl::ARRAY_BUFFER,
     // Element Buffer Object
     ElementArrayBuffer = gl::ELEMENT_ARRAY_BUFFER,
+    // Draw commands for indirect draw calls
+    DrawIndirectBuffer = gl::DRAW_INDIRECT_BUFFER,
+    // Source of buffer to buffer copies
+    CopyReadBuffer = gl::COPY_READ_BUFFER,
+    // Destination of buffer to buffer copies
+    CopyWriteBuffer = gl::COPY_WRITE_BUFFER,
     // TODO: There are more types, add them when they are supported
 }
 #[repr(u32)]
@@ -29,47 +40,219 @@ pub enum Usage {
 }
 pub trait BufferObject: Object {
     fn unbind(&self) {
-        unsafe { gl::BindBuffer(self.target() as u32, NullHandle) }
-        check_gl_error();
+        state::with(|state| state.bind_buffer(self.target() as GLenum, NullHandle));
     }
     fn target(&self) -> Target;
+    // Bind for reading or writing the contents and return the target used. The buffer stays
+    // bound, element buffers go through GL_COPY_WRITE_BUFFER so the bound VAO keeps its own
+    fn bind_for_data(&self) -> GLenum {
+        let target = match self.target() {
+            Target::ElementArrayBuffer => Target::CopyWriteBuffer,
+            target => target,
+        } as GLenum;
+        state::with(|state| state.bind_buffer(target, self.handle()));
+        target
+    }
     fn allocate_data<T>(&self, data: &[T], usage: Usage) {
-        self.bind();
-        unsafe {
-            gl::BufferData(
-                self.target() as GLenum,
-                std::mem::size_of_val(data) as gl::types::GLsizeiptr,
-                data.as_ptr().cast::<gl::types::GLvoid>(),
-                usage as GLenum,
-            );
+        let size = std::mem::size_of_val(data) as GLsizeiptr;
+        let data = data.as_ptr().cast::<gl::types::GLvoid>();
+        if state::direct_state_access() {
+            unsafe { gl::NamedBufferData(self.handle(), size, data, usage as GLenum) };
+        } else {
+            let target = self.bind_for_data();
+            unsafe { gl::BufferData(target, size, data, usage as GLenum) };
         }
-        self.unbind();
         check_gl_error();
     }
     fn reserve_data<T>(&self, size: usize, usage: Usage) {
-        self.bind();
-        unsafe {
-            gl::BufferData(
-                self.target() as GLenum,
-                (size * size_of::<T>()) as gl::types::GLsizeiptr,
-                null::<c_void>(),
-                usage as GLenum,
-            );
+        let size = (size * size_of::<T>()) as GLsizeiptr;
+        if state::direct_state_access() {
+            unsafe { gl::NamedBufferData(self.handle(), size, null::<c_void>(), usage as GLenum) };
+        } else {
+            let target = self.bind_for_data();
+            unsafe { gl::BufferData(target, size, null::<c_void>(), usage as GLenum) };
         }
-        self.unbind();
         check_gl_error();
     }
     fn update_data<T>(&self, data: &[T], offset: GLsizeiptr) {
-        self.bind();
-        unsafe {
-            gl::BufferSubData(
-                self.target() as GLenum,
-                offset,
-                std::mem::size_of_val(data) as gl::types::GLsizeiptr,
-                data.as_ptr().cast::<gl::types::GLvoid>(),
-            );
+        let size = std::mem::size_of_val(data) as GLsizeiptr;
+        let data = data.as_ptr().cast::<gl::types::GLvoid>();
+        if state::direct_state_access() {
+            unsafe { gl::NamedBufferSubData(self.handle(), offset, size, data) };
+        } else {
+            let target = self.bind_for_data();
+            unsafe { gl::BufferSubData(target, offset, size, data) };
+        }
+        check_gl_error();
+    }
+    fn size(&self) -> GLsizeiptr {
+        let mut size: GLint = 0;
+        if state::direct_state_access() {
+            unsafe { gl::GetNamedBufferParameteriv(self.handle(), gl::BUFFER_SIZE, &mut size) };
+        } else {
+            let target = self.bind_for_data();
+            unsafe { gl::GetBufferParameteriv(target, gl::BUFFER_SIZE, &mut size) };
+        }
+        check_gl_error();
+        size as GLsizeiptr
+    }
+    // Copy `size` bytes from this buffer into `other`, offsets are in bytes
+    fn copy_to<B: BufferObject>(
+        &self,
+        other: &B,
+        src_offset: GLintptr,
+        dst_offset: GLintptr,
+        size: GLsizeiptr,
+    ) {
+        if state::direct_state_access() {
+            unsafe {
+                gl::CopyNamedBufferSubData(
+                    self.handle(),
+                    other.handle(),
+                    src_offset,
+                    dst_offset,
+                    size,
+                );
+            }
+        } else {
+            state::with(|state| {
+                state.bind_buffer(Target::CopyReadBuffer as GLenum, self.handle());
+                state.bind_buffer(Target::CopyWriteBuffer as GLenum, other.handle());
+            });
+            unsafe {
+                gl::CopyBufferSubData(
+                    Target::CopyReadBuffer as GLenum,
+                    Target::CopyWriteBuffer as GLenum,
+                    src_offset,
+                    dst_offset,
+                    size,
+                );
+            }
+        }
+        check_gl_error();
+    }
+    // Fill the whole buffer repeating the 32 bit pattern in `value`, so its size must be a
+    // multiple of 4 bytes. Without GL 4.3 the pattern is uploaded from memory instead
+    fn clear(&self, value: u32) -> Result<(), String> {
+        let size = self.size();
+        if size % 4 != 0 {
+            return Err(format!(
+                "Cannot clear a buffer of {size} bytes with a 4 byte pattern"
+            ));
+        }
+        if !Capabilities::current_supports(4, 3, "GL_ARB_clear_buffer_object") {
+            self.update_data(&vec![value; size as usize / 4], 0);
+            return Ok(());
+        }
+        let value = (&raw const value).cast::<gl::types::GLvoid>();
+        if state::direct_state_access() {
+            unsafe {
+                gl::ClearNamedBufferSubData(
+                    self.handle(),
+                    gl::R32UI,
+                    0,
+                    size,
+                    gl::RED_INTEGER,
+                    gl::UNSIGNED_INT,
+                    value,
+                );
+            }
+        } else {
+            let target = self.bind_for_data();
+            unsafe {
+                gl::ClearBufferSubData(
+                    target,
+                    gl::R32UI,
+                    0,
+                    size,
+                    gl::RED_INTEGER,
+                    gl::UNSIGNED_INT,
+                    value,
+                );
+            }
+        }
+        check_gl_error();
+        Ok(())
+    }
+    // Tell the driver the current contents are no longer needed. Only a hint, so nothing
+    // happens without GL 4.3
+    fn invalidate(&self) {
+        if !Capabilities::current_supports(4, 3, "GL_ARB_invalidate_subdata") {
+            return;
         }
-        self.unbind();
+        unsafe { gl::InvalidateBufferData(self.handle()) };
+        check_gl_error();
+    }
+    // Read back the elements in `range`, indices are in elements of T. Fails when the range
+    // is not inside the buffer or GL rejects the read
+    fn read_back<T: Pod>(&self, range: Range<usize>) -> Result<Vec<T>, String> {
+        let count = range.end.saturating_sub(range.start);
+        let buffer_size = self.size() as usize;
+        let Some((offset, size)) = byte_range(&range, size_of::<T>(), buffer_size) else {
+            return Err(format!(
+                "Cannot read elements {range:?} of {} bytes from a buffer of {buffer_size} bytes",
+                size_of::<T>()
+            ));
+        };
+        let mut data: Vec<T> = Vec::with_capacity(count);
+        let pointer = data.as_mut_ptr().cast::<gl::types::GLvoid>();
+        // Zeroed first, so the elements are valid even if GL writes nothing
+        unsafe { std::ptr::write_bytes(data.as_mut_ptr(), 0, count) };
+        // Leave earlier errors out of the check below
         check_gl_error();
+        let (offset, size) = (offset as GLintptr, size as GLsizeiptr);
+        if state::direct_state_access() {
+            unsafe { gl::GetNamedBufferSubData(self.handle(), offset, size, pointer) };
+        } else {
+            let target = self.bind_for_data();
+            unsafe { gl::GetBufferSubData(target, offset, size, pointer) };
+        }
+        gl_error().map_err(|error| format!("Reading back the buffer failed: {error}"))?;
+        // Every element is initialized and, being Pod, valid
+        unsafe { data.set_len(count) };
+        Ok(data)
+    }
+}
+
+// Offset and size in bytes of the elements in `range`, None when they are not all inside the
+// buffer, including when the computation overflows
+fn byte_range(
+    range: &Range<usize>,
+    element_size: usize,
+    buffer_size: usize,
+) -> Option<(usize, usize)> {
+    let offset = range.start.checked_mul(element_size)?;
+    let size = range
+        .end
+        .checked_sub(range.start)?
+        .checked_mul(element_size)?;
+    (offset.checked_add(size)? <= buffer_size).then_some((offset, size))
+}
+
+// New buffer name, already created as a buffer object when Direct State Access is used
+#[must_use]
+pub fn create_buffer() -> Handle {
+    let mut handle = NullHandle;
+    if state::direct_state_access() {
+        unsafe { gl::CreateBuffers(1, &mut handle) };
+    } else {
+        unsafe { gl::GenBuffers(1, &mut handle) };
+    }
+    check_gl_error();
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_back_ranges() {
+        assert_eq!(byte_range(&(1..3), 4, 12), Some((4, 8)));
+        assert_eq!(byte_range(&(3..3), 4, 12), Some((12, 0)));
+        assert_eq!(byte_range(&(1..4), 4, 12), None);
+        assert_eq!(byte_range(&Range { start: 3, end: 1 }, 4, 12), None);
+        assert_eq!(byte_range(&(usize::MAX / 2..usize::MAX), 4, 12), None);
+        assert_eq!(byte_range(&(1..usize::MAX), 2, usize::MAX), None);
     }
 }
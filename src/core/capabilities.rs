@@ -0,0 +1,287 @@
+use std::{collections::BTreeSet, ffi::CStr, rc::Rc};
+
+use gl::types::{GLenum, GLint, GLuint};
+
+use crate::error::check_gl_error;
+
+use super::state;
+
+// Implementation limits of the context
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_texture_size: GLint,
+    pub max_3d_texture_size: GLint,
+    pub max_cube_map_texture_size: GLint,
+    pub max_array_texture_layers: GLint,
+    pub max_texture_image_units: GLint,
+    pub max_combined_texture_image_units: GLint,
+    pub max_vertex_attribs: GLint,
+    // 0 before GL 4.3
+    pub max_vertex_attrib_bindings: GLint,
+    // In bytes
+    pub max_uniform_block_size: GLint,
+    pub max_uniform_buffer_bindings: GLint,
+    pub max_samples: GLint,
+    pub max_color_attachments: GLint,
+    pub max_draw_buffers: GLint,
+    pub max_viewport_dims: [GLint; 2],
+    // All 0 when compute shaders are not supported
+    pub max_compute_work_group_count: [GLint; 3],
+    pub max_compute_work_group_size: [GLint; 3],
+    pub max_compute_work_group_invocations: GLint,
+}
+
+// What the driver behind the current context supports
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    // Major and minor, such as (4, 6)
+    pub version: (u32, u32),
+    // Major and minor, such as (4, 60) for #version 460
+    pub glsl_version: (u32, u32),
+    pub version_string: String,
+    pub vendor: String,
+    pub renderer: String,
+    pub extensions: BTreeSet<String>,
+    pub limits: Limits,
+}
+
+fn get_string(name: GLenum) -> String {
+    let pointer = unsafe { gl::GetString(name) };
+    check_gl_error();
+    if pointer.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(pointer.cast()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn get_integer(name: GLenum) -> GLint {
+    let mut value = 0;
+    unsafe { gl::GetIntegerv(name, &mut value) };
+    check_gl_error();
+    value
+}
+
+fn get_integers<const N: usize>(name: GLenum) -> [GLint; N] {
+    let mut values = [0; N];
+    for (index, value) in values.iter_mut().enumerate() {
+        unsafe { gl::GetIntegeri_v(name, index as GLuint, value) };
+    }
+    check_gl_error();
+    values
+}
+
+// "4.60 NVIDIA" or "OpenGL ES GLSL ES 3.20" into (4, 60) or (3, 20)
+fn parse_version(version: &str) -> (u32, u32) {
+    let Some(number) = version
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+    else {
+        return (0, 0);
+    };
+    let mut parts = number.split('.').map(|part| {
+        part.chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>()
+            .parse()
+            .unwrap_or(0)
+    });
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+impl Capabilities {
+    // Ask the current context, the GL functions must be loaded
+    #[must_use]
+    pub fn query() -> Self {
+        let version = (
+            get_integer(gl::MAJOR_VERSION) as u32,
+            get_integer(gl::MINOR_VERSION) as u32,
+        );
+        let extension_count = get_integer(gl::NUM_EXTENSIONS);
+        let extensions = (0..extension_count as GLuint)
+            .filter_map(|index| {
+                let pointer = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+                (!pointer.is_null()).then(|| {
+                    unsafe { CStr::from_ptr(pointer.cast()) }
+                        .to_string_lossy()
+                        .into_owned()
+                })
+            })
+            .collect::<BTreeSet<_>>();
+        check_gl_error();
+
+        let mut capabilities = Self {
+            version,
+            glsl_version: parse_version(&get_string(gl::SHADING_LANGUAGE_VERSION)),
+            version_string: get_string(gl::VERSION),
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            extensions,
+            limits: Limits::default(),
+        };
+        capabilities.limits = capabilities.query_limits();
+        capabilities
+    }
+
+    fn query_limits(&self) -> Limits {
+        // Querying limits the context does not know about is an error, leave those at 0
+        let compute = self.supports_feature(4, 3, "GL_ARB_compute_shader");
+        let mut limits = Limits {
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_3d_texture_size: get_integer(gl::MAX_3D_TEXTURE_SIZE),
+            max_cube_map_texture_size: get_integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE),
+            max_array_texture_layers: get_integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+            max_texture_image_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            max_combined_texture_image_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_uniform_buffer_bindings: get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_draw_buffers: get_integer(gl::MAX_DRAW_BUFFERS),
+            ..Limits::default()
+        };
+        unsafe { gl::GetIntegerv(gl::MAX_VIEWPORT_DIMS, limits.max_viewport_dims.as_mut_ptr()) };
+        check_gl_error();
+        if self.supports_separate_vertex_formats() {
+            limits.max_vertex_attrib_bindings = get_integer(gl::MAX_VERTEX_ATTRIB_BINDINGS);
+        }
+        if compute {
+            limits.max_compute_work_group_count = get_integers(gl::MAX_COMPUTE_WORK_GROUP_COUNT);
+            limits.max_compute_work_group_size = get_integers(gl::MAX_COMPUTE_WORK_GROUP_SIZE);
+            limits.max_compute_work_group_invocations =
+                get_integer(gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS);
+        }
+        limits
+    }
+
+    // Capabilities of the context current on this thread, once a window has been created
+    #[must_use]
+    pub fn current() -> Option<Rc<Self>> {
+        state::with(|state| state.capabilities().cloned())
+    }
+
+    // Log the error of `check` against the current context, for calls that would otherwise
+    // only fail with a bare GL error. Returns false when the check failed, so callers can skip
+    // entry points the driver did not load
+    pub fn report(check: impl FnOnce(&Self) -> Result<(), String>) -> bool {
+        if let Some(Err(error)) = Self::current().map(|capabilities| check(&capabilities)) {
+            log::error!("{error}");
+            return false;
+        }
+        true
+    }
+
+    #[must_use]
+    pub fn supports_version(&self, major: u32, minor: u32) -> bool {
+        self.version >= (major, minor)
+    }
+
+    // Names as reported by GL, such as "GL_ARB_direct_state_access"
+    #[must_use]
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    // Core since `major`.`minor`, or exposed through `extension` before that
+    #[must_use]
+    pub fn supports_feature(&self, major: u32, minor: u32, extension: &str) -> bool {
+        self.supports_version(major, minor) || self.has_extension(extension)
+    }
+
+    // Same as `supports_feature` for the current context, false before any window exists
+    #[must_use]
+    pub fn current_supports(major: u32, minor: u32, extension: &str) -> bool {
+        Self::current()
+            .is_some_and(|capabilities| capabilities.supports_feature(major, minor, extension))
+    }
+
+    #[must_use]
+    pub fn supports_direct_state_access(&self) -> bool {
+        self.supports_version(4, 5) || self.has_extension("GL_ARB_direct_state_access")
+    }
+
+    #[must_use]
+    pub fn supports_separate_vertex_formats(&self) -> bool {
+        self.supports_feature(4, 3, "GL_ARB_vertex_attrib_binding")
+    }
+
+    #[must_use]
+    pub const fn supports_compute(&self) -> bool {
+        self.limits.max_compute_work_group_invocations > 0
+    }
+
+    // GLSL version as used in #version directives, such as 460
+    #[must_use]
+    pub const fn glsl_version_number(&self) -> u32 {
+        self.glsl_version.0 * 100 + self.glsl_version.1
+    }
+
+    pub fn check_texture_size(&self, width: GLint, height: GLint) -> Result<(), String> {
+        let max = self.limits.max_texture_size;
+        if width > max || height > max {
+            return Err(format!(
+                "Texture of {width}x{height} exceeds the maximum size of {max} on {}",
+                self.renderer
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_vertex_attribute(&self, location: GLuint) -> Result<(), String> {
+        let max = self.limits.max_vertex_attribs;
+        if location as GLint >= max {
+            return Err(format!(
+                "Vertex attribute location {location} is not below the limit of {max} on {}",
+                self.renderer
+            ));
+        }
+        Ok(())
+    }
+
+    // glVertexAttribFormat and glBindVertexBuffer, core since 4.3
+    pub fn check_separate_vertex_formats(&self) -> Result<(), String> {
+        if self.supports_separate_vertex_formats() {
+            return Ok(());
+        }
+        Err(format!(
+            "Separate vertex formats need OpenGL 4.3 or GL_ARB_vertex_attrib_binding, {} has {}.{}",
+            self.renderer, self.version.0, self.version.1
+        ))
+    }
+
+    // Draw*BaseInstance, core since 4.2
+    pub fn check_base_instance(&self) -> Result<(), String> {
+        if self.supports_feature(4, 2, "GL_ARB_base_instance") {
+            return Ok(());
+        }
+        Err(format!(
+            "Drawing from a base instance needs OpenGL 4.2 or GL_ARB_base_instance, {} has {}.{}",
+            self.renderer, self.version.0, self.version.1
+        ))
+    }
+
+    pub fn check_vertex_binding(&self, binding: GLuint) -> Result<(), String> {
+        self.check_separate_vertex_formats()?;
+        let max = self.limits.max_vertex_attrib_bindings;
+        if binding as GLint >= max {
+            return Err(format!(
+                "Vertex buffer binding {binding} is not below the limit of {max} on {}",
+                self.renderer
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_draw_buffers(&self, count: usize) -> Result<(), String> {
+        let max = self.limits.max_draw_buffers;
+        if count as GLint > max {
+            return Err(format!(
+                "{count} draw buffers requested but at most {max} are supported on {}",
+                self.renderer
+            ));
+        }
+        Ok(())
+    }
+}
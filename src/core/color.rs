@@ -1,7 +1,23 @@
-use glam::{Vec3, Vec4};
-use rand::Rng;
+use std::{
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Sub},
+    str::FromStr,
+};
 
-#[derive(Clone, Copy, Debug, Default)]
+use glam::{Mat3, Vec3, Vec4};
+
+use crate::random::{self, RandomExt};
+
+use super::data::{
+    pack::{pack_unorm8, unpack_unorm8},
+    Pod,
+};
+
+pub mod gradient;
+
+// RGBA with components usually in [0, 1]. Nothing tracks whether a color is in sRGB or linear,
+// the conversions below say which one they expect
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -9,6 +25,9 @@ pub struct Color {
     pub a: f32,
 }
 
+// Four f32 in a #[repr(C)] struct
+unsafe impl Pod for Color {}
+
 impl From<Color> for Vec3 {
     fn from(val: Color) -> Self {
         Self {
@@ -23,18 +42,395 @@ impl From<Color> for Vec4 {
         Self::new(val.r, val.g, val.b, val.a)
     }
 }
+impl From<Vec3> for Color {
+    fn from(val: Vec3) -> Self {
+        Self::rgb(val.x, val.y, val.z)
+    }
+}
+impl From<Vec4> for Color {
+    fn from(val: Vec4) -> Self {
+        Self::new(val.x, val.y, val.z, val.w)
+    }
+}
+
+// Linear sRGB to LMS cone responses and LMS to OKLab, from Björn Ottosson's reference
+const LINEAR_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    0.412_221_46,
+    0.211_903_5,
+    0.088_302_46,
+    0.536_332_55,
+    0.680_699_5,
+    0.281_718_85,
+    0.051_445_995,
+    0.107_396_96,
+    0.629_978_7,
+]);
+const LMS_TO_OKLAB: Mat3 = Mat3::from_cols_array(&[
+    0.210_454_26,
+    1.977_998_5,
+    0.025_904_037,
+    0.793_617_8,
+    -2.428_592_2,
+    0.782_771_77,
+    -0.004_072_047,
+    0.450_593_7,
+    -0.808_675_77,
+]);
 
 impl Color {
+    pub const BLACK: Self = Self::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Self = Self::rgb(1.0, 1.0, 1.0);
+    pub const GRAY: Self = Self::rgb(0.5, 0.5, 0.5);
+    pub const RED: Self = Self::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Self = Self::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Self = Self::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Self = Self::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Self = Self::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Self = Self::rgb(1.0, 0.0, 1.0);
+    pub const TRANSPARENT: Self = Self::new(0.0, 0.0, 0.0, 0.0);
+
+    #[must_use]
     pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
         Self { r, g, b, a }
     }
+
+    // Opaque color
+    #[must_use]
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+
+    // Opaque color from the random source of this thread, see `random::seed`
+    #[must_use]
     pub fn random() -> Self {
-        let mut rng = rand::rng();
-        Self {
-            r: rng.random_range(0.0..=1.0),
-            g: rng.random_range(0.0..=1.0),
-            b: rng.random_range(0.0..=1.0),
-            a: 1.0,
+        random::with(|rng| rng.random_color())
+    }
+
+    #[must_use]
+    pub const fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    // Apply `f` to r, g and b, keeping alpha
+    #[must_use]
+    pub fn map_rgb(self, f: impl Fn(f32) -> f32) -> Self {
+        Self::new(f(self.r), f(self.g), f(self.b), self.a)
+    }
+
+    #[must_use]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Vec4::from(self).lerp(Vec4::from(other), t).into()
+    }
+
+    // Every component clamped to [0, 1]
+    #[must_use]
+    pub fn saturate(self) -> Self {
+        Vec4::from(self).clamp(Vec4::ZERO, Vec4::ONE).into()
+    }
+
+    // Color multiplied by its alpha, as blended by `BlendState::PREMULTIPLIED`
+    #[must_use]
+    pub fn premultiplied(self) -> Self {
+        self.map_rgb(|c| c * self.a)
+    }
+
+    // Inverse of `premultiplied`, fully transparent colors become transparent black
+    #[must_use]
+    pub fn unpremultiplied(self) -> Self {
+        if self.a == 0.0 {
+            return Self::TRANSPARENT;
+        }
+        self.map_rgb(|c| c / self.a)
+    }
+
+    // Relative luminance of a linear color
+    #[must_use]
+    pub fn luminance(self) -> f32 {
+        Vec3::from(self).dot(Vec3::new(0.2126, 0.7152, 0.0722))
+    }
+
+    // sRGB encoded color, as written in files and color pickers, to linear for lighting
+    #[must_use]
+    pub fn to_linear(self) -> Self {
+        self.map_rgb(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    }
+
+    // Linear color to sRGB encoded
+    #[must_use]
+    pub fn to_srgb(self) -> Self {
+        self.map_rgb(|c| {
+            if c <= 0.003_130_8 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        })
+    }
+
+    // Hue in degrees, saturation and value in [0, 1]
+    #[must_use]
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Self {
+        let chroma = value * saturation;
+        Self::from_hue(hue, chroma, value - chroma, alpha)
+    }
+
+    // (hue in degrees, saturation, value)
+    #[must_use]
+    pub fn to_hsv(self) -> Vec3 {
+        let (max, chroma, hue) = self.hue_chroma();
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        Vec3::new(hue, saturation, max)
+    }
+
+    // Hue in degrees, saturation and lightness in [0, 1]
+    #[must_use]
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        Self::from_hue(hue, chroma, lightness - chroma / 2.0, alpha)
+    }
+
+    // (hue in degrees, saturation, lightness)
+    #[must_use]
+    pub fn to_hsl(self) -> Vec3 {
+        let (max, chroma, hue) = self.hue_chroma();
+        let lightness = max - chroma / 2.0;
+        let saturation = if lightness <= 0.0 || lightness >= 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Vec3::new(hue, saturation, lightness)
+    }
+
+    // RGB from a hue, the chroma and the amount added to every component
+    fn from_hue(hue: f32, chroma: f32, offset: f32, alpha: f32) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Self::new(r + offset, g + offset, b + offset, alpha)
+    }
+
+    // (largest component, chroma, hue in degrees), hue is 0 for grays
+    fn hue_chroma(self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let chroma = max - self.r.min(self.g).min(self.b);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / chroma + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / chroma + 4.0)
+        };
+        (max, chroma, hue)
+    }
+
+    // (L, a, b) of a linear color. Distances in OKLab follow perceived differences, so it is
+    // a good space to blend colors in
+    #[must_use]
+    pub fn to_oklab(self) -> Vec3 {
+        let lms = LINEAR_TO_LMS * Vec3::from(self);
+        LMS_TO_OKLAB * lms.map(f32::cbrt)
+    }
+
+    // Linear color from (L, a, b)
+    #[must_use]
+    pub fn from_oklab(lab: Vec3, alpha: f32) -> Self {
+        let lms = LMS_TO_OKLAB.inverse() * lab;
+        Self::from(LINEAR_TO_LMS.inverse() * (lms * lms * lms)).with_alpha(alpha)
+    }
+
+    // 0xRRGGBBAA
+    #[must_use]
+    pub fn from_rgba_u32(rgba: u32) -> Self {
+        let [r, g, b, a] = rgba.to_be_bytes().map(unpack_unorm8);
+        Self::new(r, g, b, a)
+    }
+
+    #[must_use]
+    pub fn to_rgba_u32(self) -> u32 {
+        u32::from_be_bytes([self.r, self.g, self.b, self.a].map(pack_unorm8))
+    }
+
+    // "#RGB", "#RGBA", "#RRGGBB" or "#RRGGBBAA", the # is optional
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let value = u32::from_str_radix(digits, 16)
+            .ok()
+            .filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| format!("Invalid hex color {hex}"))?;
+        // Short forms repeat each digit, so 0xF becomes 0xFF
+        let expand = |value: u32, digits: u32| {
+            (0..digits).rev().fold(0, |expanded, digit| {
+                let nibble = (value >> (digit * 4)) & 0xf;
+                (expanded << 8) | (nibble * 0x11)
+            })
+        };
+        let rgba = match digits.len() {
+            3 => (expand(value, 3) << 8) | 0xff,
+            4 => expand(value, 4),
+            6 => (value << 8) | 0xff,
+            8 => value,
+            _ => return Err(format!("Invalid hex color {hex}")),
+        };
+        Ok(Self::from_rgba_u32(rgba))
+    }
+
+    // "#RRGGBB", or "#RRGGBBAA" when not opaque
+    #[must_use]
+    pub fn to_hex(self) -> String {
+        let rgba = self.to_rgba_u32();
+        if rgba & 0xff == 0xff {
+            format!("#{:06x}", rgba >> 8)
+        } else {
+            format!("#{rgba:08x}")
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+// Component-wise, alpha included
+impl Add for Color {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        (Vec4::from(self) + Vec4::from(rhs)).into()
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        (Vec4::from(self) - Vec4::from(rhs)).into()
+    }
+}
+
+impl Mul for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        (Vec4::from(self) * Vec4::from(rhs)).into()
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        (Vec4::from(self) * rhs).into()
+    }
+}
+
+impl MulAssign<f32> for Color {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl Div<f32> for Color {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self {
+        (Vec4::from(self) / rhs).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(
+            Color::from_hex("#ff8000"),
+            Ok(Color::rgb(1.0, 128.0 / 255.0, 0.0))
+        );
+        assert_eq!(Color::from_hex("f80"), Color::from_hex("#ff8800"));
+        assert_eq!(Color::from_hex("#f808"), Color::from_hex("ff880088"));
+        for hex in ["#123456", "#abcdef80", "#000000", "#ffffff00"] {
+            assert_eq!(Color::from_hex(hex).map(Color::to_hex).as_deref(), Ok(hex));
+        }
+        for invalid in ["", "#", "#12", "#12345", "+fff", "#ggg", "#1234567890"] {
+            assert!(Color::from_hex(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!("#00ff00".parse(), Ok(Color::GREEN));
+    }
+
+    #[test]
+    fn hsv_and_hsl_round_trip() {
+        let colors = [
+            Color::RED,
+            Color::CYAN,
+            Color::GRAY,
+            Color::rgb(0.2, 0.4, 0.9),
+        ];
+        for color in colors {
+            let hsv = color.to_hsv();
+            let back = Color::from_hsv(hsv.x, hsv.y, hsv.z, 1.0);
+            assert!(
+                Vec4::from(back).abs_diff_eq(color.into(), 1e-5),
+                "{color:?}"
+            );
+            let hsl = color.to_hsl();
+            let back = Color::from_hsl(hsl.x, hsl.y, hsl.z, 1.0);
+            assert!(
+                Vec4::from(back).abs_diff_eq(color.into(), 1e-5),
+                "{color:?}"
+            );
+        }
+        assert_eq!(Color::from_hsv(120.0, 1.0, 1.0, 1.0), Color::GREEN);
+        assert_eq!(Color::from_hsl(240.0, 1.0, 0.5, 1.0), Color::BLUE);
+    }
+
+    #[test]
+    fn oklab_round_trip() {
+        // Reference value from Björn Ottosson's post
+        let red = Color::RED.to_oklab();
+        assert!(red.abs_diff_eq(Vec3::new(0.628, 0.2249, 0.1258), 1e-3));
+        assert!(Color::WHITE.to_oklab().abs_diff_eq(Vec3::X, 1e-3));
+        for color in [Color::RED, Color::GRAY, Color::rgb(0.1, 0.7, 0.3)] {
+            let back = Color::from_oklab(color.to_oklab(), 1.0);
+            assert!(
+                Vec4::from(back).abs_diff_eq(color.into(), 1e-4),
+                "{color:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for value in [0.0, 0.002, 0.2, 0.5, 1.0] {
+            let color = Color::rgb(value, value, value);
+            let back = color.to_linear().to_srgb();
+            assert!(Vec4::from(back).abs_diff_eq(color.into(), 1e-5));
         }
     }
 }
@@ -0,0 +1,156 @@
+use super::Color;
+
+// How colors between two stops are found
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    // Each stop applies from its position up to the next one
+    Step,
+    // Component-wise blend of the two stops
+    #[default]
+    Linear,
+    // Blend in OKLab, keeping the perceived lightness even across the gradient
+    Oklab,
+}
+
+// Colors at increasing positions, sampled anywhere in between. Stops are sRGB encoded like
+// the rest of the colors drawn without lighting
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gradient {
+    stops: Vec<(f32, Color)>,
+    interpolation: Interpolation,
+}
+
+impl Gradient {
+    // Stops are (position, color) in any order
+    #[must_use]
+    pub fn new(stops: &[(f32, Color)]) -> Self {
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            stops,
+            interpolation: Interpolation::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    #[must_use]
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+
+    #[must_use]
+    pub const fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    // The first and last stops extend beyond the ends, an empty gradient is white
+    #[must_use]
+    pub fn sample(&self, position: f32) -> Color {
+        let Some(&(_, first)) = self.stops.first() else {
+            return Color::WHITE;
+        };
+        let next = self.stops.partition_point(|(stop, _)| *stop <= position);
+        if next == 0 {
+            return first;
+        }
+        let (start, from) = self.stops[next - 1];
+        let Some(&(end, to)) = self.stops.get(next) else {
+            return from;
+        };
+        let t = (position - start) / (end - start);
+        match self.interpolation {
+            Interpolation::Step => from,
+            Interpolation::Linear => from.lerp(to, t),
+            Interpolation::Oklab => {
+                let lab = from
+                    .to_linear()
+                    .to_oklab()
+                    .lerp(to.to_linear().to_oklab(), t);
+                let alpha = from.a + (to.a - from.a) * t;
+                Color::from_oklab(lab, alpha).to_srgb()
+            }
+        }
+    }
+
+    // Perceptually uniform dark blue to yellow over [0, 1], readable in grayscale and by most
+    // color blind viewers. Sampled from matplotlib's colormap
+    #[must_use]
+    pub fn viridis() -> Self {
+        let stops = [
+            "#440154", "#472c7a", "#3b518b", "#2c718e", "#21908d", "#27ad81", "#5cc863", "#aadc32",
+            "#fde725",
+        ];
+        let last = (stops.len() - 1) as f32;
+        let stops = stops
+            .iter()
+            .enumerate()
+            .map(|(index, hex)| {
+                (
+                    index as f32 / last,
+                    Color::from_hex(hex).unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        Self::new(&stops)
+    }
+
+    // Water, sand, grass, rock and snow at the heights of `TerrainBuilder` terrains with the
+    // default height scale
+    #[must_use]
+    pub fn terrain() -> Self {
+        Self::new(&[
+            (f32::MIN, Color::rgb(0.1, 0.1, 0.3)),
+            (-0.1, Color::rgb(0.6, 0.5, 0.4)),
+            (-0.05, Color::rgb(0.1, 0.4, 0.15)),
+            (0.1, Color::rgb(0.3, 0.3, 0.35)),
+            (0.3, Color::WHITE),
+        ])
+        .with_interpolation(Interpolation::Step)
+    }
+}
+
+// A gradient over [0, 1] stretched over a range of values, such as heights or temperatures
+#[derive(Clone, Debug, PartialEq)]
+pub struct Colormap {
+    gradient: Gradient,
+    min: f32,
+    max: f32,
+}
+
+impl Colormap {
+    #[must_use]
+    pub const fn new(gradient: Gradient, min: f32, max: f32) -> Self {
+        Self { gradient, min, max }
+    }
+
+    #[must_use]
+    pub fn viridis(min: f32, max: f32) -> Self {
+        Self::new(Gradient::viridis(), min, max)
+    }
+
+    #[must_use]
+    pub const fn gradient(&self) -> &Gradient {
+        &self.gradient
+    }
+
+    #[must_use]
+    pub const fn range(&self) -> (f32, f32) {
+        (self.min, self.max)
+    }
+
+    // Values outside the range take the color of the closest end
+    #[must_use]
+    pub fn color_at(&self, value: f32) -> Color {
+        let t = if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        };
+        self.gradient.sample(t.clamp(0.0, 1.0))
+    }
+}
@@ -1,8 +1,29 @@
 use gl::types::GLint;
+use glam::{IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, Quat, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+pub mod pack;
+
+/// Plain old data, so bytes written by GL or read from a file can be reinterpreted as the type
+///
+/// # Safety
+/// The type must have no padding and no pointers, and every bit pattern must be a valid value
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_pod!(Vec2, Vec3, Vec4, IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Mat2, Mat3, Mat4, Quat);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Type {
+    #[default]
     None = gl::NONE,
     Float = gl::FLOAT,
     Fixed = gl::FIXED,
@@ -14,18 +35,117 @@ pub enum Type {
     UShort = gl::UNSIGNED_SHORT,
     Int = gl::INT,
     UInt = gl::UNSIGNED_INT,
-    // And more...
+    // Packed vertex and pixel types, named after GL with the components from the low bits
+    // up for _REV types and from the high bits down otherwise
+    Int2_10_10_10Rev = gl::INT_2_10_10_10_REV,
+    UInt2_10_10_10Rev = gl::UNSIGNED_INT_2_10_10_10_REV,
+    UInt10F11F11FRev = gl::UNSIGNED_INT_10F_11F_11F_REV,
+    // Pixel only
+    UByte3_3_2 = gl::UNSIGNED_BYTE_3_3_2,
+    UByte2_3_3Rev = gl::UNSIGNED_BYTE_2_3_3_REV,
+    UShort5_6_5 = gl::UNSIGNED_SHORT_5_6_5,
+    UShort5_6_5Rev = gl::UNSIGNED_SHORT_5_6_5_REV,
+    UShort4_4_4_4 = gl::UNSIGNED_SHORT_4_4_4_4,
+    UShort4_4_4_4Rev = gl::UNSIGNED_SHORT_4_4_4_4_REV,
+    UShort5_5_5_1 = gl::UNSIGNED_SHORT_5_5_5_1,
+    UShort1_5_5_5Rev = gl::UNSIGNED_SHORT_1_5_5_5_REV,
+    UInt8_8_8_8 = gl::UNSIGNED_INT_8_8_8_8,
+    UInt8_8_8_8Rev = gl::UNSIGNED_INT_8_8_8_8_REV,
+    UInt10_10_10_2 = gl::UNSIGNED_INT_10_10_10_2,
+    UInt5_9_9_9Rev = gl::UNSIGNED_INT_5_9_9_9_REV,
+    UInt24_8 = gl::UNSIGNED_INT_24_8,
+    Float32UInt24_8Rev = gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
 }
 impl Type {
-    pub(crate) const fn get_size(self) -> GLint {
+    // Bytes of one component, or of the whole value for packed types
+    #[must_use]
+    pub const fn get_size(self) -> GLint {
         match self {
-            Self::Byte | Self::UByte => 1,
-            Self::Short | Self::UShort | Self::Half => 2,
-            Self::Float => 4,
-            Self::Int | Self::UInt => 4,
-            Self::Fixed => 2,
-            Self::Double => 8,
+            Self::Byte | Self::UByte | Self::UByte3_3_2 | Self::UByte2_3_3Rev => 1,
+            Self::Short
+            | Self::UShort
+            | Self::Half
+            | Self::UShort5_6_5
+            | Self::UShort5_6_5Rev
+            | Self::UShort4_4_4_4
+            | Self::UShort4_4_4_4Rev
+            | Self::UShort5_5_5_1
+            | Self::UShort1_5_5_5Rev => 2,
+            Self::Float | Self::Int | Self::UInt | Self::Fixed => 4,
+            Self::Int2_10_10_10Rev
+            | Self::UInt2_10_10_10Rev
+            | Self::UInt10F11F11FRev
+            | Self::UInt8_8_8_8
+            | Self::UInt8_8_8_8Rev
+            | Self::UInt10_10_10_2
+            | Self::UInt5_9_9_9Rev
+            | Self::UInt24_8 => 4,
+            Self::Double | Self::Float32UInt24_8Rev => 8,
             Self::None => 0,
         }
     }
+
+    // Number of components packed in one value, None for types holding a single component
+    #[must_use]
+    pub const fn packed_components(self) -> Option<GLint> {
+        match self {
+            Self::UInt24_8 | Self::Float32UInt24_8Rev => Some(2),
+            Self::UInt10F11F11FRev
+            | Self::UByte3_3_2
+            | Self::UByte2_3_3Rev
+            | Self::UShort5_6_5
+            | Self::UShort5_6_5Rev
+            | Self::UInt5_9_9_9Rev => Some(3),
+            Self::Int2_10_10_10Rev
+            | Self::UInt2_10_10_10Rev
+            | Self::UShort4_4_4_4
+            | Self::UShort4_4_4_4Rev
+            | Self::UShort5_5_5_1
+            | Self::UShort1_5_5_5Rev
+            | Self::UInt8_8_8_8
+            | Self::UInt8_8_8_8Rev
+            | Self::UInt10_10_10_2 => Some(4),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_packed(self) -> bool {
+        self.packed_components().is_some()
+    }
+
+    // Whether the values are floating point even without normalization
+    #[must_use]
+    pub const fn is_float(self) -> bool {
+        matches!(
+            self,
+            Self::Float
+                | Self::Half
+                | Self::Double
+                | Self::Fixed
+                | Self::UInt10F11F11FRev
+                | Self::UInt5_9_9_9Rev
+        )
+    }
+
+    // Whether the type can be read by vertex attributes, the rest are for pixel transfers
+    #[must_use]
+    pub const fn is_vertex_type(self) -> bool {
+        matches!(
+            self,
+            Self::Float
+                | Self::Fixed
+                | Self::Half
+                | Self::Double
+                | Self::Byte
+                | Self::UByte
+                | Self::Short
+                | Self::UShort
+                | Self::Int
+                | Self::UInt
+                | Self::Int2_10_10_10Rev
+                | Self::UInt2_10_10_10Rev
+                | Self::UInt10F11F11FRev
+        )
+    }
 }
@@ -0,0 +1,301 @@
+use glam::{Vec2, Vec2Swizzles, Vec3, Vec3Swizzles, Vec4};
+
+// CPU side conversions into the compact types of `Type`, for compressed vertex and pixel data.
+// Normalized values are clamped to their range and rounded to the nearest representable value
+
+// IEEE half float bits, rounding to nearest even. Values too large become infinity
+#[must_use]
+pub const fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        // Infinity, NaN keeps a mantissa bit set
+        let nan = if mantissa == 0 { 0 } else { 0x0200 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, remainder, halfway) = if exponent <= 0 {
+        // Subnormal, or zero when even rounding cannot reach the smallest one
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        (
+            mantissa >> shift,
+            mantissa & ((1 << shift) - 1),
+            1 << (shift - 1),
+        )
+    } else {
+        (
+            ((exponent as u32) << 10) | (mantissa >> 13),
+            mantissa & 0x1fff,
+            0x1000,
+        )
+    };
+    // A carry out of the mantissa correctly moves to the next exponent, or to infinity
+    let round = remainder > halfway || (remainder == halfway && half & 1 == 1);
+    sign | (half + round as u32) as u16
+}
+
+#[must_use]
+pub fn half_to_f32(half: u16) -> f32 {
+    let sign = u32::from(half & 0x8000) << 16;
+    let exponent = u32::from(half >> 10) & 0x1f;
+    let mantissa = u32::from(half & 0x03ff);
+    match exponent {
+        0 => {
+            let value = mantissa as f32 / 16_777_216.0;
+            if sign == 0 {
+                value
+            } else {
+                -value
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+#[must_use]
+pub fn pack_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[must_use]
+pub fn unpack_unorm8(value: u8) -> f32 {
+    f32::from(value) / 255.0
+}
+
+#[must_use]
+pub fn pack_snorm8(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+// -128 and -127 both map to -1
+#[must_use]
+pub fn unpack_snorm8(value: i8) -> f32 {
+    (f32::from(value) / 127.0).max(-1.0)
+}
+
+#[must_use]
+pub fn pack_unorm16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+#[must_use]
+pub fn unpack_unorm16(value: u16) -> f32 {
+    f32::from(value) / 65535.0
+}
+
+#[must_use]
+pub fn pack_snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * 32767.0).round() as i16
+}
+
+#[must_use]
+pub fn unpack_snorm16(value: i16) -> f32 {
+    (f32::from(value) / 32767.0).max(-1.0)
+}
+
+// Colors and other [0, 1] vectors as 4 normalized bytes, in memory order
+#[must_use]
+pub fn pack_unorm8x4(value: Vec4) -> [u8; 4] {
+    value.to_array().map(pack_unorm8)
+}
+
+#[must_use]
+pub fn unpack_unorm8x4(value: [u8; 4]) -> Vec4 {
+    Vec4::from_array(value.map(unpack_unorm8))
+}
+
+// Type::Int2_10_10_10Rev normalized: x in the low 10 bits, w in the top 2. Meant for normals
+// and tangents, w holding the handedness sign
+#[must_use]
+pub fn pack_snorm_2_10_10_10(value: Vec4) -> u32 {
+    let component = |value: f32, max: f32, bits: u32| {
+        ((value.clamp(-1.0, 1.0) * max).round() as i32 as u32) & ((1 << bits) - 1)
+    };
+    component(value.x, 511.0, 10)
+        | component(value.y, 511.0, 10) << 10
+        | component(value.z, 511.0, 10) << 20
+        | component(value.w, 1.0, 2) << 30
+}
+
+#[must_use]
+pub fn unpack_snorm_2_10_10_10(value: u32) -> Vec4 {
+    // Shift each field to the top and back to sign extend it
+    let component = |shift: u32, bits: u32, max: f32| {
+        let field = ((value << (32 - shift - bits)) as i32) >> (32 - bits);
+        (field as f32 / max).max(-1.0)
+    };
+    Vec4::new(
+        component(0, 10, 511.0),
+        component(10, 10, 511.0),
+        component(20, 10, 511.0),
+        component(30, 2, 1.0),
+    )
+}
+
+// Type::UInt2_10_10_10Rev normalized
+#[must_use]
+pub fn pack_unorm_2_10_10_10(value: Vec4) -> u32 {
+    let component = |value: f32, max: f32| (value.clamp(0.0, 1.0) * max).round() as u32;
+    component(value.x, 1023.0)
+        | component(value.y, 1023.0) << 10
+        | component(value.z, 1023.0) << 20
+        | component(value.w, 3.0) << 30
+}
+
+#[must_use]
+pub fn unpack_unorm_2_10_10_10(value: u32) -> Vec4 {
+    let component = |shift: u32, mask: u32| ((value >> shift) & mask) as f32 / mask as f32;
+    Vec4::new(
+        component(0, 0x3ff),
+        component(10, 0x3ff),
+        component(20, 0x3ff),
+        component(30, 0x3),
+    )
+}
+
+// Type::UInt10F11F11FRev: unsigned floats with 5 exponent bits, 6 mantissa bits for x and y
+// and 5 for z. Negative values and NaN become 0, the precision suits HDR colors
+#[must_use]
+pub fn pack_r11g11b10f(value: Vec3) -> u32 {
+    // Same exponent as a half float, so drop the sign and the low mantissa bits
+    let small_float = |value: f32, dropped: u32| {
+        let half = u32::from(f32_to_half(value.max(0.0)));
+        ((half + (1 << (dropped - 1))) >> dropped).min(0x7c0 >> (dropped - 4))
+    };
+    small_float(value.x, 4) | small_float(value.y, 4) << 11 | small_float(value.z, 5) << 22
+}
+
+#[must_use]
+pub fn unpack_r11g11b10f(value: u32) -> Vec3 {
+    let small_float = |bits: u32, dropped: u32| half_to_f32((bits << dropped) as u16);
+    Vec3::new(
+        small_float(value & 0x7ff, 4),
+        small_float((value >> 11) & 0x7ff, 4),
+        small_float(value >> 22, 5),
+    )
+}
+
+// Unit vector folded onto an octahedron and unrolled into [-1, 1]^2, two snorm components keep
+// a nearly uniform precision over the sphere. The zero vector maps to (0, 0)
+#[must_use]
+pub fn octahedral_encode(direction: Vec3) -> Vec2 {
+    let length = direction.x.abs() + direction.y.abs() + direction.z.abs();
+    if length == 0.0 {
+        return Vec2::ZERO;
+    }
+    let direction = direction / length;
+    if direction.z >= 0.0 {
+        direction.xy()
+    } else {
+        (Vec2::ONE - direction.yx().abs()) * Vec2::ONE.copysign(direction.xy())
+    }
+}
+
+#[must_use]
+pub fn octahedral_decode(encoded: Vec2) -> Vec3 {
+    let z = 1.0 - encoded.x.abs() - encoded.y.abs();
+    let xy = if z >= 0.0 {
+        encoded
+    } else {
+        (Vec2::ONE - encoded.yx().abs()) * Vec2::ONE.copysign(encoded)
+    };
+    xy.extend(z).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_edge_cases() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        // Halfway to the next value rounds to even, past the largest half becomes infinity
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xfc00);
+        assert!(half_to_f32(f32_to_half(f32::NAN)).is_nan());
+        // Smallest subnormal and the value below it, which rounds to 0
+        assert_eq!(f32_to_half(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_half(2.0e-8), 0x0000);
+        assert_eq!(half_to_f32(0x0001), 5.960_464_5e-8);
+        assert_eq!(half_to_f32(0x3555), 0.333_251_95);
+    }
+
+    #[test]
+    fn half_round_trip() {
+        for half in (0..=0xffff_u16).filter(|half| half & 0x7c00 != 0x7c00) {
+            assert_eq!(f32_to_half(half_to_f32(half)), half);
+        }
+    }
+
+    #[test]
+    fn normalized_round_trips() {
+        // Within half a step of the original value
+        let close = |unpacked: f32, value: f32, steps: f32| {
+            (unpacked - value).abs() <= 0.5 / steps + f32::EPSILON
+        };
+        for value in [-1.0, -0.5, 0.0, 0.25, 1.0] {
+            assert!(close(unpack_snorm8(pack_snorm8(value)), value, 127.0));
+            assert!(close(unpack_snorm16(pack_snorm16(value)), value, 32767.0));
+            let value = value.abs();
+            assert!(close(unpack_unorm8(pack_unorm8(value)), value, 255.0));
+            assert!(close(unpack_unorm16(pack_unorm16(value)), value, 65535.0));
+        }
+        assert_eq!(pack_unorm8(2.0), 255);
+        assert_eq!(unpack_snorm8(-128), -1.0);
+        let color = Vec4::new(0.0, 0.2, 0.6, 1.0);
+        assert!(unpack_unorm8x4(pack_unorm8x4(color)).abs_diff_eq(color, 0.5 / 255.0));
+    }
+
+    #[test]
+    fn packed_vectors_round_trip() {
+        let normal = Vec4::new(0.6, -0.8, 0.0, -1.0);
+        let unpacked = unpack_snorm_2_10_10_10(pack_snorm_2_10_10_10(normal));
+        assert!(unpacked.abs_diff_eq(normal, 1.0 / 511.0));
+        let value = Vec4::new(0.0, 0.5, 1.0, 1.0);
+        let unpacked = unpack_unorm_2_10_10_10(pack_unorm_2_10_10_10(value));
+        assert!(unpacked.abs_diff_eq(value, 1.0 / 1023.0));
+    }
+
+    #[test]
+    fn r11g11b10f_edge_cases() {
+        let color = Vec3::new(1.0, 0.5, 100.0);
+        assert_eq!(unpack_r11g11b10f(pack_r11g11b10f(color)), color);
+        // Negative values become 0 and values too large become infinity, as with halves
+        let clamped = unpack_r11g11b10f(pack_r11g11b10f(Vec3::new(-1.0, 1.0e9, f32::NAN)));
+        assert_eq!(clamped, Vec3::new(0.0, f32::INFINITY, 0.0));
+        let relative = |value: f32| {
+            let unpacked = unpack_r11g11b10f(pack_r11g11b10f(Vec3::splat(value)));
+            ((unpacked - value) / value).abs()
+        };
+        assert!(relative(3.3).max_element() <= 1.0 / 32.0);
+    }
+
+    #[test]
+    fn octahedral_round_trip() {
+        for direction in [
+            Vec3::X,
+            Vec3::NEG_Y,
+            Vec3::NEG_Z,
+            Vec3::new(-1.0, 2.0, -3.0),
+        ] {
+            let direction = direction.normalize();
+            let decoded = octahedral_decode(octahedral_encode(direction));
+            assert!(decoded.abs_diff_eq(direction, 1e-5));
+        }
+        assert_eq!(octahedral_encode(Vec3::ZERO), Vec2::ZERO);
+    }
+}
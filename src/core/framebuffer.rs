@@ -0,0 +1,111 @@
+use gl::types::{GLenum, GLint, GLsizei};
+
+use crate::error::check_gl_error;
+
+use super::{
+    capabilities::Capabilities,
+    object::{Handle, NullHandle, Object},
+    state,
+    texture::Texture,
+};
+
+#[derive(Debug)]
+pub struct Framebuffer {
+    handle: Handle,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.handle) };
+        check_gl_error();
+        state::with(|state| state.forget_framebuffer(self.handle));
+    }
+}
+
+impl Framebuffer {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut handle = NullHandle;
+        if state::direct_state_access() {
+            unsafe { gl::CreateFramebuffers(1, &mut handle) };
+        } else {
+            unsafe { gl::GenFramebuffers(1, &mut handle) };
+        }
+        check_gl_error();
+        Self { handle }
+    }
+
+    // Go back to drawing into the window
+    pub fn unbind(&self) {
+        state::with(|state| state.bind_framebuffer(gl::FRAMEBUFFER, NullHandle));
+    }
+
+    // Render into mip `level` of `texture` at `attachment`, such as GL_COLOR_ATTACHMENT0 or
+    // GL_DEPTH_ATTACHMENT. Without Direct State Access the framebuffer is left bound
+    pub fn attach_texture(&self, attachment: GLenum, texture: &Texture, level: GLint) {
+        if state::direct_state_access() {
+            unsafe {
+                gl::NamedFramebufferTexture(self.handle, attachment, texture.handle(), level)
+            };
+        } else {
+            self.bind();
+            unsafe {
+                gl::FramebufferTexture(gl::FRAMEBUFFER, attachment, texture.handle(), level);
+            }
+        }
+        check_gl_error();
+    }
+
+    // Color attachments written by fragment outputs 0, 1, ...
+    pub fn set_draw_buffers(&self, attachments: &[GLenum]) {
+        Capabilities::report(|capabilities| capabilities.check_draw_buffers(attachments.len()));
+        let count = attachments.len() as GLsizei;
+        if state::direct_state_access() {
+            unsafe { gl::NamedFramebufferDrawBuffers(self.handle, count, attachments.as_ptr()) };
+        } else {
+            self.bind();
+            unsafe { gl::DrawBuffers(count, attachments.as_ptr()) };
+        }
+        check_gl_error();
+    }
+
+    // Fails with the reason when the attachments cannot be rendered to
+    pub fn check_status(&self) -> Result<(), String> {
+        let status = if state::direct_state_access() {
+            unsafe { gl::CheckNamedFramebufferStatus(self.handle, gl::FRAMEBUFFER) }
+        } else {
+            self.bind();
+            unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) }
+        };
+        check_gl_error();
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => Ok(()),
+            gl::FRAMEBUFFER_UNDEFINED => Err("Framebuffer undefined".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Err("Incomplete attachment".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Err("Missing attachment".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Err("Incomplete draw buffer".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Err("Incomplete read buffer".to_owned()),
+            gl::FRAMEBUFFER_UNSUPPORTED => Err("Unsupported attachment formats".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Err("Incomplete multisample".to_owned()),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Err("Incomplete layer targets".to_owned()),
+            status => Err(format!("Unknown framebuffer status {status:#06x}")),
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Object for Framebuffer {
+    // Bind for both drawing and reading
+    fn bind(&self) {
+        state::with(|state| state.bind_framebuffer(gl::FRAMEBUFFER, self.handle));
+    }
+
+    fn handle(&self) -> Handle {
+        self.handle
+    }
+}
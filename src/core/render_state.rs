@@ -0,0 +1,528 @@
+use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLuint};
+
+use super::state::GlState;
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero = gl::ZERO,
+    One = gl::ONE,
+    SrcColor = gl::SRC_COLOR,
+    OneMinusSrcColor = gl::ONE_MINUS_SRC_COLOR,
+    DstColor = gl::DST_COLOR,
+    OneMinusDstColor = gl::ONE_MINUS_DST_COLOR,
+    SrcAlpha = gl::SRC_ALPHA,
+    OneMinusSrcAlpha = gl::ONE_MINUS_SRC_ALPHA,
+    DstAlpha = gl::DST_ALPHA,
+    OneMinusDstAlpha = gl::ONE_MINUS_DST_ALPHA,
+    ConstantColor = gl::CONSTANT_COLOR,
+    OneMinusConstantColor = gl::ONE_MINUS_CONSTANT_COLOR,
+    ConstantAlpha = gl::CONSTANT_ALPHA,
+    OneMinusConstantAlpha = gl::ONE_MINUS_CONSTANT_ALPHA,
+    SrcAlphaSaturate = gl::SRC_ALPHA_SATURATE,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendEquation {
+    Add = gl::FUNC_ADD,
+    Subtract = gl::FUNC_SUBTRACT,
+    ReverseSubtract = gl::FUNC_REVERSE_SUBTRACT,
+    Min = gl::MIN,
+    Max = gl::MAX,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlendState {
+    pub enabled: bool,
+    pub source_rgb: BlendFactor,
+    pub destination_rgb: BlendFactor,
+    pub source_alpha: BlendFactor,
+    pub destination_alpha: BlendFactor,
+    pub equation_rgb: BlendEquation,
+    pub equation_alpha: BlendEquation,
+    // Used by the Constant* factors
+    pub constant: [GLfloat; 4],
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl BlendState {
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        ..Self::new(BlendFactor::One, BlendFactor::Zero)
+    };
+    // Classic transparency for colors that are not premultiplied
+    pub const ALPHA: Self = Self::new(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+    // Adds the color weighted by its alpha, for glows and particles
+    pub const ADDITIVE: Self = Self::new(BlendFactor::SrcAlpha, BlendFactor::One);
+    // Transparency for colors already multiplied by their alpha
+    pub const PREMULTIPLIED: Self = Self::new(BlendFactor::One, BlendFactor::OneMinusSrcAlpha);
+
+    // Enabled blending with the same factors for color and alpha
+    #[must_use]
+    pub const fn new(source: BlendFactor, destination: BlendFactor) -> Self {
+        Self {
+            enabled: true,
+            source_rgb: source,
+            destination_rgb: destination,
+            source_alpha: source,
+            destination_alpha: destination,
+            equation_rgb: BlendEquation::Add,
+            equation_alpha: BlendEquation::Add,
+            constant: [0.0; 4],
+        }
+    }
+
+    #[must_use]
+    pub const fn with_alpha_factors(
+        mut self,
+        source: BlendFactor,
+        destination: BlendFactor,
+    ) -> Self {
+        self.source_alpha = source;
+        self.destination_alpha = destination;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_equation(mut self, equation: BlendEquation) -> Self {
+        self.equation_rgb = equation;
+        self.equation_alpha = equation;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_constant(mut self, constant: [GLfloat; 4]) -> Self {
+        self.constant = constant;
+        self
+    }
+
+    // Factors and equations are left alone while blending is disabled
+    pub fn apply(&self, state: &mut GlState) {
+        state.set_capability(gl::BLEND, self.enabled);
+        if !self.enabled {
+            return;
+        }
+        state.set_blend_func_separate(
+            self.source_rgb as GLenum,
+            self.destination_rgb as GLenum,
+            self.source_alpha as GLenum,
+            self.destination_alpha as GLenum,
+        );
+        state.set_blend_equation_separate(
+            self.equation_rgb as GLenum,
+            self.equation_alpha as GLenum,
+        );
+        state.set_blend_color(self.constant);
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never = gl::NEVER,
+    Less = gl::LESS,
+    Equal = gl::EQUAL,
+    LessEqual = gl::LEQUAL,
+    Greater = gl::GREATER,
+    NotEqual = gl::NOTEQUAL,
+    GreaterEqual = gl::GEQUAL,
+    Always = gl::ALWAYS,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthState {
+    pub test: bool,
+    pub write: bool,
+    pub func: CompareFunc,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl DepthState {
+    // GL defaults
+    pub const DISABLED: Self = Self {
+        test: false,
+        write: true,
+        func: CompareFunc::Less,
+    };
+    pub const LESS: Self = Self {
+        test: true,
+        write: true,
+        func: CompareFunc::Less,
+    };
+    // Tested but not written, for transparent surfaces drawn after the opaque ones
+    pub const READ_ONLY: Self = Self {
+        test: true,
+        write: false,
+        func: CompareFunc::LessEqual,
+    };
+    // For Projection::InfiniteReverseZ, where depth goes from 1 at the near plane to 0
+    pub const REVERSE_Z: Self = Self {
+        test: true,
+        write: true,
+        func: CompareFunc::Greater,
+    };
+
+    pub fn apply(&self, state: &mut GlState) {
+        state.set_capability(gl::DEPTH_TEST, self.test);
+        state.set_depth_mask(self.write);
+        state.set_depth_func(self.func as GLenum);
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep = gl::KEEP,
+    Zero = gl::ZERO,
+    Replace = gl::REPLACE,
+    Increment = gl::INCR,
+    IncrementWrap = gl::INCR_WRAP,
+    Decrement = gl::DECR,
+    DecrementWrap = gl::DECR_WRAP,
+    Invert = gl::INVERT,
+}
+
+// Stencil test and update for one face
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilFace {
+    pub func: CompareFunc,
+    pub reference: GLint,
+    // Bits compared by the test
+    pub read_mask: GLuint,
+    // Bits written by the operations
+    pub write_mask: GLuint,
+    pub stencil_fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+impl Default for StencilFace {
+    fn default() -> Self {
+        Self::KEEP
+    }
+}
+
+impl StencilFace {
+    // GL defaults, always passes and never changes the buffer
+    pub const KEEP: Self = Self {
+        func: CompareFunc::Always,
+        reference: 0,
+        read_mask: !0,
+        write_mask: !0,
+        stencil_fail: StencilOp::Keep,
+        depth_fail: StencilOp::Keep,
+        pass: StencilOp::Keep,
+    };
+
+    // Write `reference` wherever something is drawn
+    #[must_use]
+    pub const fn write(reference: GLint) -> Self {
+        Self {
+            reference,
+            pass: StencilOp::Replace,
+            ..Self::KEEP
+        }
+    }
+
+    // Only draw where the buffer compares to `reference` with `func`
+    #[must_use]
+    pub const fn test(func: CompareFunc, reference: GLint) -> Self {
+        Self {
+            func,
+            reference,
+            ..Self::KEEP
+        }
+    }
+
+    fn apply(&self, state: &mut GlState, face: GLenum) {
+        state.set_stencil_func(face, self.func as GLenum, self.reference, self.read_mask);
+        state.set_stencil_op(
+            face,
+            self.stencil_fail as GLenum,
+            self.depth_fail as GLenum,
+            self.pass as GLenum,
+        );
+        state.set_stencil_mask(face, self.write_mask);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StencilState {
+    pub enabled: bool,
+    pub front: StencilFace,
+    pub back: StencilFace,
+}
+
+impl StencilState {
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        front: StencilFace::KEEP,
+        back: StencilFace::KEEP,
+    };
+
+    // Enabled with the same settings for both faces
+    #[must_use]
+    pub const fn new(face: StencilFace) -> Self {
+        Self {
+            enabled: true,
+            front: face,
+            back: face,
+        }
+    }
+
+    pub fn apply(&self, state: &mut GlState) {
+        state.set_capability(gl::STENCIL_TEST, self.enabled);
+        if !self.enabled {
+            return;
+        }
+        self.front.apply(state, gl::FRONT);
+        self.back.apply(state, gl::BACK);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrontFace {
+    #[default]
+    CounterClockwise = gl::CCW,
+    Clockwise = gl::CW,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PolygonMode {
+    #[default]
+    Fill = gl::FILL,
+    Line = gl::LINE,
+    Point = gl::POINT,
+}
+
+// Depth offset of factor * slope + units * smallest depth step, against z-fighting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: GLfloat,
+    pub units: GLfloat,
+}
+
+// Rectangle in window pixels, from the bottom left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scissor {
+    pub x: GLint,
+    pub y: GLint,
+    pub width: GLsizei,
+    pub height: GLsizei,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+    pub polygon_offset: Option<PolygonOffset>,
+    pub scissor: Option<Scissor>,
+}
+
+impl RasterizerState {
+    // GL defaults, nothing culled
+    pub const DEFAULT: Self = Self {
+        cull_mode: CullMode::None,
+        front_face: FrontFace::CounterClockwise,
+        polygon_mode: PolygonMode::Fill,
+        polygon_offset: None,
+        scissor: None,
+    };
+    pub const CULL_BACK: Self = Self::DEFAULT.with_cull_mode(CullMode::Back);
+    pub const WIREFRAME: Self = Self::DEFAULT.with_polygon_mode(PolygonMode::Line);
+
+    #[must_use]
+    pub const fn with_cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_front_face(mut self, front_face: FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_polygon_offset(mut self, factor: GLfloat, units: GLfloat) -> Self {
+        self.polygon_offset = Some(PolygonOffset { factor, units });
+        self
+    }
+
+    #[must_use]
+    pub const fn with_scissor(mut self, scissor: Scissor) -> Self {
+        self.scissor = Some(scissor);
+        self
+    }
+
+    pub fn apply(&self, state: &mut GlState) {
+        let cull_face = match self.cull_mode {
+            CullMode::None => None,
+            CullMode::Front => Some(gl::FRONT),
+            CullMode::Back => Some(gl::BACK),
+            CullMode::FrontAndBack => Some(gl::FRONT_AND_BACK),
+        };
+        state.set_capability(gl::CULL_FACE, cull_face.is_some());
+        if let Some(cull_face) = cull_face {
+            state.set_cull_face(cull_face);
+        }
+        state.set_front_face(self.front_face as GLenum);
+        state.set_polygon_mode(self.polygon_mode as GLenum);
+
+        // The offset applies to whatever the polygon mode draws
+        let offset_capability = match self.polygon_mode {
+            PolygonMode::Fill => gl::POLYGON_OFFSET_FILL,
+            PolygonMode::Line => gl::POLYGON_OFFSET_LINE,
+            PolygonMode::Point => gl::POLYGON_OFFSET_POINT,
+        };
+        for capability in [
+            gl::POLYGON_OFFSET_FILL,
+            gl::POLYGON_OFFSET_LINE,
+            gl::POLYGON_OFFSET_POINT,
+        ] {
+            let enabled = self.polygon_offset.is_some() && capability == offset_capability;
+            state.set_capability(capability, enabled);
+        }
+        if let Some(offset) = self.polygon_offset {
+            state.set_polygon_offset(offset.factor, offset.units);
+        }
+
+        state.set_capability(gl::SCISSOR_TEST, self.scissor.is_some());
+        if let Some(scissor) = self.scissor {
+            state.set_scissor(scissor.x, scissor.y, scissor.width, scissor.height);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Default for ColorMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl ColorMask {
+    pub const ALL: Self = Self {
+        red: true,
+        green: true,
+        blue: true,
+        alpha: true,
+    };
+    // For depth or stencil only passes
+    pub const NONE: Self = Self {
+        red: false,
+        green: false,
+        blue: false,
+        alpha: false,
+    };
+    pub const RGB: Self = Self {
+        alpha: false,
+        ..Self::ALL
+    };
+
+    pub fn apply(&self, state: &mut GlState) {
+        state.set_color_mask(self.red, self.green, self.blue, self.alpha);
+    }
+}
+
+// Everything the fixed function stages need for a draw, applied together so no state is left
+// over from a previous draw
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderState {
+    pub blend: BlendState,
+    pub depth: DepthState,
+    pub stencil: StencilState,
+    pub rasterizer: RasterizerState,
+    pub color_mask: ColorMask,
+}
+
+impl RenderState {
+    // Depth tested opaque geometry with back faces culled
+    pub const OPAQUE: Self = Self {
+        blend: BlendState::DISABLED,
+        depth: DepthState::LESS,
+        stencil: StencilState::DISABLED,
+        rasterizer: RasterizerState::CULL_BACK,
+        color_mask: ColorMask::ALL,
+    };
+    // Alpha blended over the opaque geometry without writing depth
+    pub const TRANSPARENT: Self = Self {
+        blend: BlendState::ALPHA,
+        depth: DepthState::READ_ONLY,
+        ..Self::OPAQUE
+    };
+
+    #[must_use]
+    pub const fn with_blend(mut self, blend: BlendState) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_depth(mut self, depth: DepthState) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_stencil(mut self, stencil: StencilState) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_rasterizer(mut self, rasterizer: RasterizerState) -> Self {
+        self.rasterizer = rasterizer;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_color_mask(mut self, color_mask: ColorMask) -> Self {
+        self.color_mask = color_mask;
+        self
+    }
+
+    pub fn apply(&self, state: &mut GlState) {
+        self.blend.apply(state);
+        self.depth.apply(state);
+        self.stencil.apply(state);
+        self.rasterizer.apply(state);
+        self.color_mask.apply(state);
+    }
+}
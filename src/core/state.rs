@@ -0,0 +1,485 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use gl::types::{GLboolean, GLenum, GLfloat, GLint, GLsizei, GLuint};
+
+use crate::error::check_gl_error;
+
+use super::{
+    capabilities::Capabilities,
+    object::{Handle, NullHandle},
+};
+
+// How many state changes reached GL and how many were dropped because they changed nothing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateStats {
+    pub issued: usize,
+    pub skipped: usize,
+}
+
+// Stencil state is kept per face, index 0 is GL_FRONT and 1 is GL_BACK
+const FACES: [GLenum; 2] = [gl::FRONT, gl::BACK];
+
+const fn face_indices(face: GLenum) -> &'static [usize] {
+    match face {
+        gl::FRONT => &[0],
+        gl::BACK => &[1],
+        _ => &[0, 1],
+    }
+}
+
+// Blending and depth writes as cached by `GlState::blending`, None where the cache did not know
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SavedBlending {
+    blend: Option<bool>,
+    blend_func: Option<[GLenum; 4]>,
+    blend_equation: Option<[GLenum; 2]>,
+    blend_color: Option<[GLfloat; 4]>,
+    depth_mask: Option<bool>,
+}
+
+// Mirror of the GL state of the current context. Every value starts unknown (None) and the
+// first change always reaches GL, code calling GL directly must `invalidate` afterwards
+#[derive(Debug, Default)]
+pub struct GlState {
+    // What the current context supports, known after `detect`
+    context: Option<Rc<Capabilities>>,
+    // Edit objects by name (GL 4.5 or ARB_direct_state_access) instead of binding them first
+    direct_state_access: bool,
+    program: Option<Handle>,
+    vertex_array: Option<Handle>,
+    buffers: HashMap<GLenum, Option<Handle>>,
+    active_texture: Option<GLuint>,
+    // Keyed by texture unit and target
+    textures: HashMap<(GLuint, GLenum), Option<Handle>>,
+    // Draw and read framebuffers
+    framebuffers: [Option<Handle>; 2],
+    enabled: HashMap<GLenum, Option<bool>>,
+    // Source and destination factors for RGB, then for alpha
+    blend_func: Option<[GLenum; 4]>,
+    // Equations for RGB and alpha
+    blend_equation: Option<[GLenum; 2]>,
+    blend_color: Option<[GLfloat; 4]>,
+    depth_func: Option<GLenum>,
+    depth_mask: Option<bool>,
+    // Function, reference and mask
+    stencil_func: [Option<(GLenum, GLint, GLuint)>; 2],
+    // Stencil fail, depth fail and pass operations
+    stencil_op: [Option<[GLenum; 3]>; 2],
+    stencil_mask: [Option<GLuint>; 2],
+    cull_face: Option<GLenum>,
+    front_face: Option<GLenum>,
+    polygon_mode: Option<GLenum>,
+    // Factor and units
+    polygon_offset: Option<[GLfloat; 2]>,
+    // Red, green, blue and alpha writes
+    color_mask: Option<[bool; 4]>,
+    // x, y, width, height
+    scissor: Option<[GLint; 4]>,
+    // x, y, width, height
+    viewport: Option<[GLint; 4]>,
+    stats: StateStats,
+}
+
+thread_local! {
+    // GL contexts are current on one thread at a time, so one cache per thread
+    static STATE: RefCell<GlState> = RefCell::new(GlState::default());
+}
+
+// Run `f` with the state cache of the context current on this thread. Do not nest calls
+pub fn with<R>(f: impl FnOnce(&mut GlState) -> R) -> R {
+    STATE.with_borrow_mut(f)
+}
+
+// Whether objects should use the Direct State Access functions, see `GlState::detect`
+#[must_use]
+pub fn direct_state_access() -> bool {
+    with(|state| state.direct_state_access)
+}
+
+// Issue `apply` unless `slot` already holds `value`
+fn update<T: PartialEq + Copy>(
+    stats: &mut StateStats,
+    slot: &mut Option<T>,
+    value: T,
+    apply: impl FnOnce(),
+) -> bool {
+    if *slot == Some(value) {
+        stats.skipped += 1;
+        return false;
+    }
+    apply();
+    check_gl_error();
+    *slot = Some(value);
+    stats.issued += 1;
+    true
+}
+
+const fn gl_bool(value: bool) -> GLboolean {
+    if value {
+        gl::TRUE
+    } else {
+        gl::FALSE
+    }
+}
+
+impl GlState {
+    #[must_use]
+    pub const fn stats(&self) -> StateStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = StateStats::default();
+    }
+
+    // Forget everything, for a new context or after GL was changed behind the cache
+    pub fn invalidate(&mut self) {
+        *self = Self {
+            context: self.context.take(),
+            direct_state_access: self.direct_state_access,
+            stats: self.stats,
+            ..Self::default()
+        };
+    }
+
+    // Forget everything and look at what the new current context supports
+    pub fn detect(&mut self) {
+        self.invalidate();
+        let capabilities = Capabilities::query();
+        self.direct_state_access = capabilities.supports_direct_state_access();
+        self.context = Some(Rc::new(capabilities));
+    }
+
+    #[must_use]
+    pub const fn capabilities(&self) -> Option<&Rc<Capabilities>> {
+        self.context.as_ref()
+    }
+
+    #[must_use]
+    pub const fn direct_state_access(&self) -> bool {
+        self.direct_state_access
+    }
+
+    // Force the bind to edit path, or Direct State Access if the context supports it
+    pub const fn set_direct_state_access(&mut self, enabled: bool) {
+        self.direct_state_access = enabled;
+    }
+
+    pub fn use_program(&mut self, program: Handle) {
+        update(&mut self.stats, &mut self.program, program, || unsafe {
+            gl::UseProgram(program);
+        });
+    }
+
+    pub fn bind_vertex_array(&mut self, vertex_array: Handle) {
+        let changed = update(
+            &mut self.stats,
+            &mut self.vertex_array,
+            vertex_array,
+            || unsafe { gl::BindVertexArray(vertex_array) },
+        );
+        if changed {
+            // The element buffer binding belongs to the VAO
+            self.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+    }
+
+    pub fn bind_buffer(&mut self, target: GLenum, buffer: Handle) {
+        let slot = self.buffers.entry(target).or_default();
+        update(&mut self.stats, slot, buffer, || unsafe {
+            gl::BindBuffer(target, buffer);
+        });
+    }
+
+    pub fn active_texture(&mut self, unit: GLuint) {
+        update(&mut self.stats, &mut self.active_texture, unit, || unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+        });
+    }
+
+    // Bind to the active texture unit
+    pub fn bind_texture(&mut self, target: GLenum, texture: Handle) {
+        let apply = || unsafe { gl::BindTexture(target, texture) };
+        let Some(unit) = self.active_texture else {
+            // Cannot tell which unit this lands on
+            apply();
+            check_gl_error();
+            self.stats.issued += 1;
+            return;
+        };
+        let slot = self.textures.entry((unit, target)).or_default();
+        update(&mut self.stats, slot, texture, apply);
+    }
+
+    pub fn bind_texture_unit(&mut self, unit: GLuint, target: GLenum, texture: Handle) {
+        if self.direct_state_access {
+            // Leaves the active texture unit alone
+            let slot = self.textures.entry((unit, target)).or_default();
+            update(&mut self.stats, slot, texture, || unsafe {
+                gl::BindTextureUnit(unit, texture);
+            });
+        } else {
+            self.active_texture(unit);
+            self.bind_texture(target, texture);
+        }
+    }
+
+    // `target` is GL_FRAMEBUFFER for both, GL_DRAW_FRAMEBUFFER or GL_READ_FRAMEBUFFER
+    pub fn bind_framebuffer(&mut self, target: GLenum, framebuffer: Handle) {
+        let indices: &[usize] = match target {
+            gl::DRAW_FRAMEBUFFER => &[0],
+            gl::READ_FRAMEBUFFER => &[1],
+            _ => &[0, 1],
+        };
+        if indices
+            .iter()
+            .all(|&index| self.framebuffers[index] == Some(framebuffer))
+        {
+            self.stats.skipped += 1;
+            return;
+        }
+        unsafe { gl::BindFramebuffer(target, framebuffer) };
+        check_gl_error();
+        for &index in indices {
+            self.framebuffers[index] = Some(framebuffer);
+        }
+        self.stats.issued += 1;
+    }
+
+    pub fn set_capability(&mut self, capability: GLenum, enabled: bool) {
+        let slot = self.enabled.entry(capability).or_default();
+        update(&mut self.stats, slot, enabled, || unsafe {
+            if enabled {
+                gl::Enable(capability);
+            } else {
+                gl::Disable(capability);
+            }
+        });
+    }
+
+    pub fn enable(&mut self, capability: GLenum) {
+        self.set_capability(capability, true);
+    }
+
+    pub fn disable(&mut self, capability: GLenum) {
+        self.set_capability(capability, false);
+    }
+
+    pub fn set_blend_func(&mut self, source: GLenum, destination: GLenum) {
+        self.set_blend_func_separate(source, destination, source, destination);
+    }
+
+    pub fn set_blend_func_separate(
+        &mut self,
+        source_rgb: GLenum,
+        destination_rgb: GLenum,
+        source_alpha: GLenum,
+        destination_alpha: GLenum,
+    ) {
+        let value = [source_rgb, destination_rgb, source_alpha, destination_alpha];
+        update(&mut self.stats, &mut self.blend_func, value, || unsafe {
+            gl::BlendFuncSeparate(source_rgb, destination_rgb, source_alpha, destination_alpha);
+        });
+    }
+
+    pub fn set_blend_equation(&mut self, mode: GLenum) {
+        self.set_blend_equation_separate(mode, mode);
+    }
+
+    pub fn set_blend_equation_separate(&mut self, mode_rgb: GLenum, mode_alpha: GLenum) {
+        let value = [mode_rgb, mode_alpha];
+        update(
+            &mut self.stats,
+            &mut self.blend_equation,
+            value,
+            || unsafe {
+                gl::BlendEquationSeparate(mode_rgb, mode_alpha);
+            },
+        );
+    }
+
+    pub fn set_blend_color(&mut self, color: [GLfloat; 4]) {
+        update(&mut self.stats, &mut self.blend_color, color, || unsafe {
+            gl::BlendColor(color[0], color[1], color[2], color[3]);
+        });
+    }
+
+    pub fn set_depth_func(&mut self, func: GLenum) {
+        update(&mut self.stats, &mut self.depth_func, func, || unsafe {
+            gl::DepthFunc(func);
+        });
+    }
+
+    pub fn set_depth_mask(&mut self, write: bool) {
+        update(&mut self.stats, &mut self.depth_mask, write, || unsafe {
+            gl::DepthMask(gl_bool(write));
+        });
+    }
+
+    // Save the blending state before a pass changes it, see `restore_blending`
+    #[must_use]
+    pub fn blending(&self) -> SavedBlending {
+        SavedBlending {
+            blend: self.enabled.get(&gl::BLEND).copied().flatten(),
+            blend_func: self.blend_func,
+            blend_equation: self.blend_equation,
+            blend_color: self.blend_color,
+            depth_mask: self.depth_mask,
+        }
+    }
+
+    // Put back the state saved by `blending`. Values the cache did not know when saving go
+    // back to the GL defaults
+    pub fn restore_blending(&mut self, saved: SavedBlending) {
+        self.set_capability(gl::BLEND, saved.blend.unwrap_or(false));
+        let [source_rgb, destination_rgb, source_alpha, destination_alpha] = saved
+            .blend_func
+            .unwrap_or([gl::ONE, gl::ZERO, gl::ONE, gl::ZERO]);
+        self.set_blend_func_separate(source_rgb, destination_rgb, source_alpha, destination_alpha);
+        let [mode_rgb, mode_alpha] = saved.blend_equation.unwrap_or([gl::FUNC_ADD; 2]);
+        self.set_blend_equation_separate(mode_rgb, mode_alpha);
+        self.set_blend_color(saved.blend_color.unwrap_or([0.0; 4]));
+        self.set_depth_mask(saved.depth_mask.unwrap_or(true));
+    }
+
+    // `face` is GL_FRONT, GL_BACK or GL_FRONT_AND_BACK
+    pub fn set_stencil_func(&mut self, face: GLenum, func: GLenum, reference: GLint, mask: GLuint) {
+        for &index in face_indices(face) {
+            let value = (func, reference, mask);
+            update(
+                &mut self.stats,
+                &mut self.stencil_func[index],
+                value,
+                || unsafe {
+                    gl::StencilFuncSeparate(FACES[index], func, reference, mask);
+                },
+            );
+        }
+    }
+
+    pub fn set_stencil_op(
+        &mut self,
+        face: GLenum,
+        stencil_fail: GLenum,
+        depth_fail: GLenum,
+        pass: GLenum,
+    ) {
+        for &index in face_indices(face) {
+            let value = [stencil_fail, depth_fail, pass];
+            update(
+                &mut self.stats,
+                &mut self.stencil_op[index],
+                value,
+                || unsafe {
+                    gl::StencilOpSeparate(FACES[index], stencil_fail, depth_fail, pass);
+                },
+            );
+        }
+    }
+
+    pub fn set_stencil_mask(&mut self, face: GLenum, mask: GLuint) {
+        for &index in face_indices(face) {
+            update(
+                &mut self.stats,
+                &mut self.stencil_mask[index],
+                mask,
+                || unsafe {
+                    gl::StencilMaskSeparate(FACES[index], mask);
+                },
+            );
+        }
+    }
+
+    pub fn set_cull_face(&mut self, mode: GLenum) {
+        update(&mut self.stats, &mut self.cull_face, mode, || unsafe {
+            gl::CullFace(mode);
+        });
+    }
+
+    pub fn set_front_face(&mut self, mode: GLenum) {
+        update(&mut self.stats, &mut self.front_face, mode, || unsafe {
+            gl::FrontFace(mode);
+        });
+    }
+
+    // Core profile only accepts GL_FRONT_AND_BACK, so one mode for both faces
+    pub fn set_polygon_mode(&mut self, mode: GLenum) {
+        update(&mut self.stats, &mut self.polygon_mode, mode, || unsafe {
+            gl::PolygonMode(gl::FRONT_AND_BACK, mode);
+        });
+    }
+
+    pub fn set_polygon_offset(&mut self, factor: GLfloat, units: GLfloat) {
+        let value = [factor, units];
+        update(
+            &mut self.stats,
+            &mut self.polygon_offset,
+            value,
+            || unsafe {
+                gl::PolygonOffset(factor, units);
+            },
+        );
+    }
+
+    pub fn set_color_mask(&mut self, red: bool, green: bool, blue: bool, alpha: bool) {
+        let value = [red, green, blue, alpha];
+        update(&mut self.stats, &mut self.color_mask, value, || unsafe {
+            gl::ColorMask(gl_bool(red), gl_bool(green), gl_bool(blue), gl_bool(alpha));
+        });
+    }
+
+    pub fn set_scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        let value = [x, y, width, height];
+        update(&mut self.stats, &mut self.scissor, value, || unsafe {
+            gl::Scissor(x, y, width, height);
+        });
+    }
+
+    pub fn set_viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        let value = [x, y, width, height];
+        update(&mut self.stats, &mut self.viewport, value, || unsafe {
+            gl::Viewport(x, y, width, height);
+        });
+    }
+
+    // Deleted objects are unbound by GL, and their names can be handed out again
+
+    // A deleted program stays in use until another one is, but its name may be reused before
+    // then, so the next `use_program` must always reach GL
+    pub fn forget_program(&mut self, program: Handle) {
+        if self.program == Some(program) {
+            self.program = None;
+        }
+    }
+
+    pub fn forget_vertex_array(&mut self, vertex_array: Handle) {
+        if self.vertex_array == Some(vertex_array) {
+            self.vertex_array = Some(NullHandle);
+            self.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+    }
+
+    pub fn forget_buffer(&mut self, buffer: Handle) {
+        for slot in self.buffers.values_mut() {
+            if *slot == Some(buffer) {
+                *slot = Some(NullHandle);
+            }
+        }
+    }
+
+    pub fn forget_framebuffer(&mut self, framebuffer: Handle) {
+        for slot in &mut self.framebuffers {
+            if *slot == Some(framebuffer) {
+                *slot = Some(NullHandle);
+            }
+        }
+    }
+
+    pub fn forget_texture(&mut self, texture: Handle) {
+        for slot in self.textures.values_mut() {
+            if *slot == Some(texture) {
+                *slot = Some(NullHandle);
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::cell::{Cell, RefCell};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use crate::error::check_gl_error;
+
+use super::{
+    capabilities::Capabilities,
+    data::{Pod, Type},
+    object::{Handle, NullHandle, Object},
+    state,
+};
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureTarget {
+    Texture1D = gl::TEXTURE_1D,
+    Texture2D = gl::TEXTURE_2D,
+    Texture3D = gl::TEXTURE_3D,
+    Texture2DArray = gl::TEXTURE_2D_ARRAY,
+    CubeMap = gl::TEXTURE_CUBE_MAP,
+    // TODO: There are more types, add them when they are supported
+}
+
+impl TextureTarget {
+    // Whether a sampler uniform of GL type `kind` can read from this target
+    #[must_use]
+    pub const fn matches_sampler(self, kind: GLenum) -> bool {
+        match self {
+            Self::Texture1D => matches!(
+                kind,
+                gl::SAMPLER_1D | gl::INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_1D
+            ),
+            Self::Texture2D => matches!(
+                kind,
+                gl::SAMPLER_2D
+                    | gl::SAMPLER_2D_SHADOW
+                    | gl::INT_SAMPLER_2D
+                    | gl::UNSIGNED_INT_SAMPLER_2D
+            ),
+            Self::Texture3D => matches!(
+                kind,
+                gl::SAMPLER_3D | gl::INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_3D
+            ),
+            Self::Texture2DArray => matches!(
+                kind,
+                gl::SAMPLER_2D_ARRAY
+                    | gl::SAMPLER_2D_ARRAY_SHADOW
+                    | gl::INT_SAMPLER_2D_ARRAY
+                    | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            ),
+            Self::CubeMap => matches!(
+                kind,
+                gl::SAMPLER_CUBE
+                    | gl::SAMPLER_CUBE_SHADOW
+                    | gl::INT_SAMPLER_CUBE
+                    | gl::UNSIGNED_INT_SAMPLER_CUBE
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Texture {
+    // Replaced when Direct State Access storage has to be allocated again
+    handle: Cell<Handle>,
+    target: TextureTarget,
+    // Internal format, width and height of the immutable storage made by the first upload when
+    // using Direct State Access
+    storage: Cell<Option<(GLenum, GLsizei, GLsizei)>>,
+    // Parameters set so far, applied again to a replaced texture
+    parameters: RefCell<Vec<(GLenum, GLint)>>,
+}
+
+// Immutable storage needs a sized internal format. Pixel transfer formats such as RED_INTEGER
+// or BGRA are not internal formats and are rejected
+const fn sized_format(internal_format: GLenum) -> Result<GLenum, &'static str> {
+    match internal_format {
+        gl::RED => Ok(gl::R8),
+        gl::RG => Ok(gl::RG8),
+        gl::RGB => Ok(gl::RGB8),
+        gl::RGBA => Ok(gl::RGBA8),
+        gl::SRGB => Ok(gl::SRGB8),
+        gl::SRGB_ALPHA => Ok(gl::SRGB8_ALPHA8),
+        gl::DEPTH_COMPONENT => Ok(gl::DEPTH_COMPONENT24),
+        gl::DEPTH_STENCIL => Ok(gl::DEPTH24_STENCIL8),
+        gl::STENCIL_INDEX => Ok(gl::STENCIL_INDEX8),
+        gl::RED_INTEGER
+        | gl::RG_INTEGER
+        | gl::RGB_INTEGER
+        | gl::RGBA_INTEGER
+        | gl::BGR_INTEGER
+        | gl::BGRA_INTEGER
+        | gl::BGR
+        | gl::BGRA => Err("is a pixel format, use a sized internal format such as R32UI or RGBA8"),
+        format => Ok(format),
+    }
+}
+
+// Components of each texel in a pixel transfer format
+const fn format_components(format: GLenum) -> Option<usize> {
+    match format {
+        gl::RED
+        | gl::GREEN
+        | gl::BLUE
+        | gl::RED_INTEGER
+        | gl::GREEN_INTEGER
+        | gl::BLUE_INTEGER
+        | gl::DEPTH_COMPONENT
+        | gl::STENCIL_INDEX => Some(1),
+        gl::RG | gl::RG_INTEGER | gl::DEPTH_STENCIL => Some(2),
+        gl::RGB | gl::BGR | gl::RGB_INTEGER | gl::BGR_INTEGER => Some(3),
+        gl::RGBA | gl::BGRA | gl::RGBA_INTEGER | gl::BGRA_INTEGER => Some(4),
+        _ => None,
+    }
+}
+
+// Bytes GL reads for a `width` x `height` image of `format` texels made of `data_type`. Rows
+// start at multiples of GL_UNPACK_ALIGNMENT, which stays at its default of 4, and the last row
+// is read without its padding
+fn upload_size(
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    data_type: Type,
+) -> Result<usize, String> {
+    const ALIGNMENT: usize = 4;
+    let (Ok(width), Ok(height)) = (usize::try_from(width), usize::try_from(height)) else {
+        return Err(format!("Texture size {width}x{height} is negative"));
+    };
+    let components = format_components(format)
+        .ok_or_else(|| format!("Pixel format {format:#x} is not supported"))?;
+    let texel = match data_type {
+        Type::None => return Err("Pixel data needs a type".to_owned()),
+        // A packed value holds every component of the texel
+        data_type if data_type.is_packed() => data_type.get_size() as usize,
+        data_type => components * data_type.get_size() as usize,
+    };
+    let overflow = || format!("Texture of {width}x{height} is too large");
+    let row = width.checked_mul(texel).ok_or_else(overflow)?;
+    if height == 0 {
+        return Ok(0);
+    }
+    row.checked_next_multiple_of(ALIGNMENT)
+        .and_then(|padded| padded.checked_mul(height - 1))
+        .and_then(|rows| rows.checked_add(row))
+        .ok_or_else(overflow)
+}
+
+// Number of levels in a full mip chain
+const fn mip_levels(width: GLsizei, height: GLsizei) -> GLsizei {
+    let size = if width > height { width } else { height };
+    if size <= 1 {
+        return 1;
+    }
+    (GLsizei::BITS - size.leading_zeros()) as GLsizei
+}
+
+fn create_texture(target: TextureTarget) -> Handle {
+    let mut handle = NullHandle;
+    if state::direct_state_access() {
+        unsafe { gl::CreateTextures(target as GLenum, 1, &mut handle) };
+    } else {
+        unsafe { gl::GenTextures(1, &mut handle) };
+    }
+    check_gl_error();
+    handle
+}
+
+fn delete_texture(handle: Handle) {
+    unsafe { gl::DeleteTextures(1, &handle) };
+    check_gl_error();
+    state::with(|state| state.forget_texture(handle));
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        delete_texture(self.handle.get());
+    }
+}
+
+impl Texture {
+    #[must_use]
+    pub fn new(target: TextureTarget) -> Self {
+        Self {
+            handle: Cell::new(create_texture(target)),
+            target,
+            storage: Cell::new(None),
+            parameters: RefCell::new(vec![]),
+        }
+    }
+
+    #[must_use]
+    pub const fn target(&self) -> TextureTarget {
+        self.target
+    }
+
+    // Bind to texture unit `unit`, leaving that unit active
+    pub fn bind_to_unit(&self, unit: GLuint) {
+        state::with(|state| {
+            state.bind_texture_unit(unit, self.target as GLenum, self.handle.get());
+        });
+    }
+
+    pub fn unbind(&self) {
+        state::with(|state| state.bind_texture(self.target as GLenum, NullHandle));
+    }
+
+    // Upload the base level of a 2D texture, `data` holds rows of `format` texels made of
+    // `data_type`, see `generate_mipmap` for the other levels. With Direct State Access the
+    // storage has room for a full mip chain and is immutable, so uploading another size or
+    // format replaces the texture with a new one, which must be bound again. Fails when `data`
+    // is smaller than the image
+    pub fn upload_2d<T: Pod>(
+        &self,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        data_type: Type,
+        data: &[T],
+    ) -> Result<(), String> {
+        debug_assert_eq!(self.target, TextureTarget::Texture2D);
+        let size = upload_size(width, height, format, data_type)?;
+        if size_of_val(data) < size {
+            return Err(format!(
+                "Texture of {width}x{height} needs {size} bytes of data, got {}",
+                size_of_val(data)
+            ));
+        }
+        if let Some(capabilities) = Capabilities::current() {
+            capabilities.check_texture_size(width, height)?;
+        }
+        let sized_format = sized_format(internal_format)
+            .map_err(|error| format!("Internal format {internal_format:#x} {error}"))?;
+        if state::direct_state_access() {
+            let storage = (sized_format, width, height);
+            if self.storage.get() != Some(storage) {
+                if self.storage.get().is_some() {
+                    self.recreate();
+                }
+                let levels = mip_levels(width, height);
+                unsafe {
+                    gl::TextureStorage2D(self.handle.get(), levels, sized_format, width, height);
+                }
+                self.storage.set(Some(storage));
+            }
+            unsafe {
+                gl::TextureSubImage2D(
+                    self.handle.get(),
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                    format,
+                    data_type as GLenum,
+                    data.as_ptr().cast::<gl::types::GLvoid>(),
+                );
+            }
+            check_gl_error();
+            return Ok(());
+        }
+        self.bind();
+        unsafe {
+            gl::TexImage2D(
+                self.target as GLenum,
+                0,
+                internal_format as GLint,
+                width,
+                height,
+                0,
+                format,
+                data_type as GLenum,
+                data.as_ptr().cast::<gl::types::GLvoid>(),
+            );
+        }
+        self.unbind();
+        check_gl_error();
+        Ok(())
+    }
+
+    // New texture name in place of the current one, keeping the parameters
+    fn recreate(&self) {
+        delete_texture(self.handle.get());
+        self.handle.set(create_texture(self.target));
+        self.storage.set(None);
+        for &(name, value) in self.parameters.borrow().iter() {
+            self.apply_parameter(name, value);
+        }
+    }
+
+    pub fn set_parameter(&self, name: GLenum, value: GLint) {
+        let mut parameters = self.parameters.borrow_mut();
+        parameters.retain(|(other, _)| *other != name);
+        parameters.push((name, value));
+        drop(parameters);
+        self.apply_parameter(name, value);
+    }
+
+    fn apply_parameter(&self, name: GLenum, value: GLint) {
+        if state::direct_state_access() {
+            unsafe { gl::TextureParameteri(self.handle.get(), name, value) };
+            check_gl_error();
+            return;
+        }
+        self.bind();
+        unsafe { gl::TexParameteri(self.target as GLenum, name, value) };
+        self.unbind();
+        check_gl_error();
+    }
+
+    pub fn set_filter(&self, min_filter: GLenum, mag_filter: GLenum) {
+        self.set_parameter(gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+        self.set_parameter(gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+    }
+
+    pub fn set_wrap(&self, wrap: GLenum) {
+        self.set_parameter(gl::TEXTURE_WRAP_S, wrap as GLint);
+        self.set_parameter(gl::TEXTURE_WRAP_T, wrap as GLint);
+        self.set_parameter(gl::TEXTURE_WRAP_R, wrap as GLint);
+    }
+
+    pub fn generate_mipmap(&self) {
+        if state::direct_state_access() {
+            unsafe { gl::GenerateTextureMipmap(self.handle.get()) };
+            check_gl_error();
+            return;
+        }
+        self.bind();
+        unsafe { gl::GenerateMipmap(self.target as GLenum) };
+        self.unbind();
+        check_gl_error();
+    }
+}
+
+impl Object for Texture {
+    fn bind(&self) {
+        state::with(|state| state.bind_texture(self.target as GLenum, self.handle.get()));
+    }
+
+    fn handle(&self) -> Handle {
+        self.handle.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upload_sizes() {
+        // 3 byte rows padded to 4, except the last one
+        assert_eq!(upload_size(1, 3, gl::RGB, Type::UByte), Ok(11));
+        assert_eq!(upload_size(2, 2, gl::RGBA, Type::Float), Ok(64));
+        assert_eq!(upload_size(3, 1, gl::RGBA, Type::UInt8_8_8_8Rev), Ok(12));
+        assert_eq!(upload_size(4, 4, gl::DEPTH_STENCIL, Type::UInt24_8), Ok(64));
+        assert_eq!(upload_size(0, 5, gl::RED, Type::UByte), Ok(0));
+        assert_eq!(upload_size(5, 0, gl::RED, Type::UByte), Ok(0));
+        assert!(upload_size(-1, 1, gl::RGBA, Type::UByte).is_err());
+        assert!(upload_size(1, -1, gl::RGBA, Type::UByte).is_err());
+        assert!(upload_size(1, 1, gl::RGBA, Type::None).is_err());
+        assert!(upload_size(1, 1, gl::R8, Type::UByte).is_err());
+        assert!(upload_size(GLsizei::MAX, GLsizei::MAX, gl::RGBA, Type::Double).is_err());
+    }
+}
@@ -2,6 +2,18 @@ use std::ffi::CStr;
 
 // #[cfg(debug_assertion)]
 pub fn check_gl_error() {
+    if let Err(error_string) = gl_error() {
+        log::error!(
+            "OpenGL Error at FILE {} LINE {}: {}",
+            file!(),
+            line!(),
+            error_string
+        );
+    }
+}
+
+// The oldest error GL recorded since the last check, for calls that report failure to the caller
+pub fn gl_error() -> Result<(), String> {
     let error_code = unsafe { gl::GetError() };
 
     if error_code != gl::NO_ERROR {
@@ -14,14 +26,9 @@ pub fn check_gl_error() {
             gl::OUT_OF_MEMORY => "Out of Memory",
             _ => "Unknown Error",
         };
-
-        log::error!(
-            "OpenGL Error at FILE {} LINE {}: {}",
-            file!(),
-            line!(),
-            error_string
-        );
+        return Err(error_string.to_owned());
     }
+    Ok(())
 }
 
 // #[cfg(not(debug_assertion))]
@@ -1,6 +1,14 @@
+pub mod compression;
 pub mod drawcall;
 pub mod element_buffer_object;
+pub mod indirect_buffer_object;
 pub mod mesh;
+pub mod primitives;
+pub mod processing;
+pub mod simplification;
+pub mod terrain;
 pub mod vertex_array_object;
 pub mod vertex_attribute;
 pub mod vertex_buffer_object;
+pub mod vertex_format;
+pub mod vertex_layout;
@@ -0,0 +1,387 @@
+use gl::types::{GLint, GLsizei, GLuint};
+use glam::{Vec2, Vec4};
+
+use crate::core::data::{pack, Type};
+
+use super::{
+    drawcall::{DrawCall, Primitive},
+    element_buffer_object::Index,
+    mesh::Mesh,
+    vertex_attribute::{Layout, VertexAttribute},
+    vertex_layout::{Vertex, VertexLayout},
+};
+
+// Decodes attributes stored with `Quantization::Octahedral`, call it on the vec2 attribute
+pub const OCTAHEDRAL_DECODE_GLSL: &str = "
+vec3 octahedralDecode(vec2 encoded)
+{
+    vec3 direction = vec3(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    if (direction.z < 0.0)
+    {
+        direction.xy = (1.0 - abs(direction.yx)) * vec2(
+            direction.x >= 0.0 ? 1.0 : -1.0,
+            direction.y >= 0.0 ? 1.0 : -1.0);
+    }
+    return normalize(direction);
+}
+";
+
+// Attributes are placed at multiples of 4 bytes, as in `VertexFormat`
+const ALIGNMENT: GLint = 4;
+
+// How a float attribute is stored after compression
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quantization {
+    // Unchanged 32 bit floats
+    #[default]
+    Float,
+    Half,
+    // Normalized integers, values outside [0, 1] or [-1, 1] are clamped
+    Unorm8,
+    Snorm8,
+    Unorm16,
+    Snorm16,
+    // Unit vectors as 2 snorm16, the shader decodes them with OCTAHEDRAL_DECODE_GLSL
+    Octahedral,
+    // 3 or 4 components in [-1, 1] in 4 bytes, w keeps only its sign. Shaders read them as is
+    Snorm2_10_10_10,
+    // 3 non negative floats in 4 bytes, such as HDR colors
+    R11G11B10F,
+}
+
+impl Quantization {
+    // Attribute storing `components` floats with this quantization
+    fn attribute(self, components: GLint) -> Result<VertexAttribute, String> {
+        let (data_type, stored_components, normalized) = match self {
+            Self::Float => (Type::Float, components, false),
+            Self::Half => (Type::Half, components, false),
+            Self::Unorm8 => (Type::UByte, components, true),
+            Self::Snorm8 => (Type::Byte, components, true),
+            Self::Unorm16 => (Type::UShort, components, true),
+            Self::Snorm16 => (Type::Short, components, true),
+            Self::Octahedral if components == 3 => (Type::Short, 2, true),
+            Self::Snorm2_10_10_10 if components >= 3 => (Type::Int2_10_10_10Rev, 4, true),
+            Self::R11G11B10F if components == 3 => (Type::UInt10F11F11FRev, 3, false),
+            _ => return Err(format!("{self:?} cannot store {components} components")),
+        };
+        Ok(VertexAttribute::new(
+            data_type,
+            stored_components,
+            normalized,
+        ))
+    }
+
+    // Append the first `components` of `value` to `output`, returns what the GPU reads back
+    fn encode(self, value: Vec4, components: usize, output: &mut Vec<u8>) -> Vec4 {
+        match self {
+            Self::Float => encode_components(value, components, output, |x| (x.to_ne_bytes(), x)),
+            Self::Half => encode_components(value, components, output, |x| {
+                let half = pack::f32_to_half(x);
+                (half.to_ne_bytes(), pack::half_to_f32(half))
+            }),
+            Self::Unorm8 => encode_components(value, components, output, |x| {
+                let packed = pack::pack_unorm8(x);
+                ([packed], pack::unpack_unorm8(packed))
+            }),
+            Self::Snorm8 => encode_components(value, components, output, |x| {
+                let packed = pack::pack_snorm8(x);
+                (packed.to_ne_bytes(), pack::unpack_snorm8(packed))
+            }),
+            Self::Unorm16 => encode_components(value, components, output, |x| {
+                let packed = pack::pack_unorm16(x);
+                (packed.to_ne_bytes(), pack::unpack_unorm16(packed))
+            }),
+            Self::Snorm16 => encode_components(value, components, output, |x| {
+                let packed = pack::pack_snorm16(x);
+                (packed.to_ne_bytes(), pack::unpack_snorm16(packed))
+            }),
+            Self::Octahedral => {
+                let encoded = pack::octahedral_encode(value.truncate())
+                    .extend(0.0)
+                    .extend(0.0);
+                let stored = Self::Snorm16.encode(encoded, 2, output);
+                pack::octahedral_decode(Vec2::new(stored.x, stored.y)).extend(0.0)
+            }
+            Self::Snorm2_10_10_10 => {
+                let packed = pack::pack_snorm_2_10_10_10(value);
+                output.extend(packed.to_ne_bytes());
+                pack::unpack_snorm_2_10_10_10(packed)
+            }
+            Self::R11G11B10F => {
+                let packed = pack::pack_r11g11b10f(value.truncate());
+                output.extend(packed.to_ne_bytes());
+                pack::unpack_r11g11b10f(packed).extend(0.0)
+            }
+        }
+    }
+}
+
+fn encode_components<const N: usize>(
+    value: Vec4,
+    components: usize,
+    output: &mut Vec<u8>,
+    encode: impl Fn(f32) -> ([u8; N], f32),
+) -> Vec4 {
+    let mut decoded = Vec4::ZERO;
+    for component in 0..components {
+        let (bytes, stored) = encode(value[component]);
+        output.extend(bytes);
+        decoded[component] = stored;
+    }
+    decoded
+}
+
+// Quantization of each attribute location, unlisted attributes stay floats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuantizationPolicy {
+    quantizations: Vec<(GLuint, Quantization)>,
+}
+
+impl QuantizationPolicy {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with(mut self, location: GLuint, quantization: Quantization) -> Self {
+        self.quantizations.retain(|(other, _)| *other != location);
+        self.quantizations.push((location, quantization));
+        self
+    }
+
+    #[must_use]
+    pub fn get(&self, location: GLuint) -> Quantization {
+        self.quantizations
+            .iter()
+            .find(|(other, _)| *other == location)
+            .map_or(Quantization::Float, |(_, quantization)| *quantization)
+    }
+}
+
+// Distance between the original and the decoded attribute, measured over every vertex
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantizationError {
+    pub location: GLuint,
+    pub quantization: Quantization,
+    pub max: f32,
+    pub mean: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompressedVertices {
+    data: Vec<u8>,
+    layout: VertexLayout,
+    vertex_count: usize,
+    original_stride: GLsizei,
+    errors: Vec<QuantizationError>,
+}
+
+impl CompressedVertices {
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Layout of `data`, attributes keep their locations and divisors
+    #[must_use]
+    pub const fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    #[must_use]
+    pub const fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    #[must_use]
+    pub const fn original_stride(&self) -> GLsizei {
+        self.original_stride
+    }
+
+    #[must_use]
+    pub const fn bytes_saved_per_vertex(&self) -> GLsizei {
+        self.original_stride - self.layout.stride()
+    }
+
+    // One entry per attribute, in layout order
+    #[must_use]
+    pub fn errors(&self) -> &[QuantizationError] {
+        &self.errors
+    }
+
+    #[must_use]
+    pub fn error(&self, location: GLuint) -> Option<&QuantizationError> {
+        self.errors.iter().find(|error| error.location == location)
+    }
+
+    // Same as `MeshData::to_mesh` with the compressed vertices
+    #[must_use]
+    pub fn to_mesh<I: Index>(&self, indices: &[I], primitive: Primitive) -> Mesh {
+        let mut mesh = Mesh::new();
+        let vbo = mesh.add_raw_vertex_data(&self.data, self.vertex_count);
+        let ebo = mesh.add_element_data(indices);
+        let vao = mesh.add_vertex_array_from_layout(&self.layout, vbo, Some(ebo));
+        let draw_call = DrawCall::count(
+            DrawCall::index_type(DrawCall::new(primitive), I::DATA_TYPE),
+            indices.len() as i32,
+        );
+        mesh.add_submesh(vao, draw_call, 0);
+        mesh
+    }
+}
+
+// Re-encode the float attributes of `vertices` following `policy` into a tightly packed
+// interleaved buffer. Fails when an attribute is not made of floats or cannot use its quantization
+pub fn compress_vertices<V: Vertex>(
+    vertices: &[V],
+    policy: &QuantizationPolicy,
+) -> Result<CompressedVertices, String> {
+    let layout = V::layout();
+    let vertex_size = size_of::<V>();
+
+    // Place the compressed attributes one after the other
+    let mut attributes = vec![];
+    let mut offset = 0;
+    for (location, source) in layout.attributes() {
+        let attribute = source.attribute();
+        if attribute.data_type() != Type::Float || attribute.is_normalized() {
+            return Err(format!(
+                "Attribute at location {location} is {:?}, only floats can be compressed",
+                attribute.data_type()
+            ));
+        }
+        let components = attribute.components();
+        let end = source.offset() as usize + components as usize * size_of::<f32>();
+        if !(1..=4).contains(&components) || end > vertex_size {
+            return Err(format!(
+                "Attribute at location {location} does not fit in the vertex"
+            ));
+        }
+        let quantization = policy.get(*location);
+        let compressed = quantization
+            .attribute(components)
+            .map_err(|error| format!("Attribute at location {location}: {error}"))?
+            .with_divisor(attribute.divisor());
+        offset = align_up(offset, ALIGNMENT);
+        attributes.push((*location, source, compressed, quantization, offset));
+        offset += compressed.get_size();
+    }
+    let stride = align_up(offset, ALIGNMENT);
+
+    let mut data = Vec::with_capacity(vertices.len() * stride as usize);
+    let mut error_sums = vec![(0.0_f32, 0.0_f32); attributes.len()];
+    let base = vertices.as_ptr().cast::<u8>();
+    for vertex in 0..vertices.len() {
+        let start = data.len();
+        for (index, (_, source, _, quantization, offset)) in attributes.iter().enumerate() {
+            let components = source.attribute().components() as usize;
+            let mut value = Vec4::ZERO;
+            for component in 0..components {
+                let byte = vertex * vertex_size + source.offset() as usize + component * 4;
+                // The `Vertex` contract puts these floats inside the vertex, checked above
+                value[component] = unsafe { base.add(byte).cast::<f32>().read_unaligned() };
+            }
+            data.resize(start + *offset as usize, 0);
+            let decoded = quantization.encode(value, components, &mut data);
+            let distance = decoded.distance(value);
+            let (max, sum) = &mut error_sums[index];
+            *max = max.max(distance);
+            *sum += distance;
+        }
+        data.resize(start + stride as usize, 0);
+    }
+
+    let errors = attributes
+        .iter()
+        .zip(error_sums)
+        .map(
+            |((location, _, _, quantization, _), (max, sum))| QuantizationError {
+                location: *location,
+                quantization: *quantization,
+                max,
+                mean: sum / vertices.len().max(1) as f32,
+            },
+        )
+        .collect();
+    let layout_attributes = attributes
+        .iter()
+        .map(|(location, _, compressed, _, offset)| {
+            (*location, Layout::new(*compressed, *offset, stride))
+        })
+        .collect();
+    Ok(CompressedVertices {
+        data,
+        layout: VertexLayout::new(stride, layout_attributes),
+        vertex_count: vertices.len(),
+        original_stride: layout.stride(),
+        errors,
+    })
+}
+
+const fn align_up(value: GLint, alignment: GLint) -> GLint {
+    (value + alignment - 1) / alignment * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3};
+
+    use super::*;
+    use crate::geometry::primitives::{self, PrimitiveVertex};
+
+    #[test]
+    fn compress_reports_errors() {
+        let sphere = primitives::uv_sphere(1.0, 16, 8);
+        let policy = QuantizationPolicy::new()
+            .with(1, Quantization::Octahedral)
+            .with(2, Quantization::Half)
+            .with(3, Quantization::Snorm2_10_10_10);
+        let compressed = compress_vertices(&sphere.vertices, &policy).unwrap();
+
+        assert_eq!(compressed.vertex_count(), sphere.vertices.len());
+        assert_eq!(compressed.original_stride(), 48);
+        // Float position, 2 snorm16 normal, 2 half uv and a packed tangent
+        assert_eq!(compressed.layout().stride(), 12 + 4 + 4 + 4);
+        assert_eq!(compressed.data().len(), sphere.vertices.len() * 24);
+        assert_eq!(compressed.errors().len(), 4);
+        let position = compressed.error(0).unwrap();
+        assert_eq!((position.max, position.mean), (0.0, 0.0));
+        for (location, tolerance) in [(1, 1e-3), (2, 1e-3), (3, 5e-3)] {
+            let error = compressed.error(location).unwrap();
+            assert!(
+                error.mean <= error.max && error.max < tolerance,
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compress_rejects_unsupported_quantizations() {
+        let vertices = [PrimitiveVertex {
+            position: Vec3::ONE,
+            uv: Vec2::ONE,
+            ..Default::default()
+        }];
+        for (location, quantization) in [
+            (2, Quantization::Octahedral),
+            (2, Quantization::R11G11B10F),
+            (2, Quantization::Snorm2_10_10_10),
+        ] {
+            let policy = QuantizationPolicy::new().with(location, quantization);
+            assert!(compress_vertices(&vertices, &policy).is_err());
+        }
+    }
+
+    #[test]
+    fn quantization_clamps_values() {
+        let vertices = [PrimitiveVertex {
+            position: Vec3::new(2.0, -3.0, 0.5),
+            ..Default::default()
+        }];
+        let policy = QuantizationPolicy::new().with(0, Quantization::Snorm8);
+        let compressed = compress_vertices(&vertices, &policy).unwrap();
+        let error = compressed.error(0).unwrap();
+        assert!((error.max - Vec3::new(1.0, 2.0, 0.0).length()).abs() < 1e-2);
+    }
+}
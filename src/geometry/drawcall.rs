@@ -1,13 +1,18 @@
 use std::ffi::c_void;
 
-use gl::types::{GLenum, GLint, GLsizei};
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
 
 use crate::{
-    core::data, error::check_gl_error, geometry::element_buffer_object::ElementBufferObject,
+    core::{buffer_object::BufferObject, capabilities::Capabilities, data, object::Object},
+    error::check_gl_error,
+    geometry::{
+        element_buffer_object::ElementBufferObject,
+        indirect_buffer_object::{IndirectBufferObject, IndirectCommand},
+    },
 };
 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Primitive {
     Points = gl::POINTS,
     Lines = gl::LINES,
@@ -29,6 +34,10 @@ pub struct DrawCall {
     first: GLint,
     count: GLsizei,
     index_type: data::Type,
+    // None for a regular draw, Some(n) to draw n instances
+    instance_count: Option<GLsizei>,
+    base_instance: GLuint,
+    base_vertex: GLint,
 }
 
 impl DrawCall {
@@ -38,6 +47,9 @@ impl DrawCall {
             first: 0,
             count: 1,
             index_type: data::Type::None,
+            instance_count: None,
+            base_instance: 0,
+            base_vertex: 0,
         }
     }
     pub const fn first(value: Self, first: GLint) -> Self {
@@ -54,23 +66,332 @@ impl DrawCall {
         }
     }
 
+    pub const fn instance_count(value: Self, instance_count: GLsizei) -> Self {
+        Self {
+            instance_count: Some(instance_count),
+            ..value
+        }
+    }
+    // First instance used to fetch per instance attributes, requires GL 4.2. Without an
+    // instance count a single instance is drawn
+    pub const fn base_instance(value: Self, base_instance: GLuint) -> Self {
+        Self {
+            base_instance,
+            ..value
+        }
+    }
+    // Value added to every index before fetching vertices. Array draws add it to `first`
+    pub const fn base_vertex(value: Self, base_vertex: GLint) -> Self {
+        Self {
+            base_vertex,
+            ..value
+        }
+    }
+
+    pub const fn is_indexed(&self) -> bool {
+        !matches!(self.index_type, data::Type::None)
+    }
+
+    pub const fn is_instanced(&self) -> bool {
+        self.instance_count.is_some()
+    }
+
+    pub fn draw(&self) {
+        if self.base_instance != 0 && !Capabilities::report(Capabilities::check_base_instance) {
+            return;
+        }
+        if self.is_indexed() {
+            self.draw_elements();
+        } else {
+            self.draw_arrays();
+        }
+        check_gl_error();
+    }
+
+    // Instances to draw, a base instance alone needs an instanced call with one instance
+    const fn instances(&self) -> Option<GLsizei> {
+        match (self.instance_count, self.base_instance) {
+            (None, 0) => None,
+            (None, _) => Some(1),
+            (instances, _) => instances,
+        }
+    }
+
+    fn draw_arrays(&self) {
+        let primitive = self.primitive as GLenum;
+        let first = self.first + self.base_vertex;
+        match self.instances() {
+            None => unsafe { gl::DrawArrays(primitive, first, self.count) },
+            Some(instances) if self.base_instance == 0 => unsafe {
+                gl::DrawArraysInstanced(primitive, first, self.count, instances);
+            },
+            Some(instances) => unsafe {
+                gl::DrawArraysInstancedBaseInstance(
+                    primitive,
+                    first,
+                    self.count,
+                    instances,
+                    self.base_instance,
+                );
+            },
+        }
+    }
+
+    fn draw_elements(&self) {
+        let primitive = self.primitive as GLenum;
+        let index_type = self.index_type as GLenum;
+        let base_pointer: *const c_void = std::ptr::null();
+        let indices = base_pointer.wrapping_byte_add(self.first as usize);
+        match (self.instances(), self.base_vertex, self.base_instance) {
+            (None, 0, _) => unsafe {
+                gl::DrawElements(primitive, self.count, index_type, indices);
+            },
+            (None, base_vertex, _) => unsafe {
+                gl::DrawElementsBaseVertex(primitive, self.count, index_type, indices, base_vertex);
+            },
+            (Some(instances), 0, 0) => unsafe {
+                gl::DrawElementsInstanced(primitive, self.count, index_type, indices, instances);
+            },
+            (Some(instances), base_vertex, 0) => unsafe {
+                gl::DrawElementsInstancedBaseVertex(
+                    primitive,
+                    self.count,
+                    index_type,
+                    indices,
+                    instances,
+                    base_vertex,
+                );
+            },
+            (Some(instances), 0, base_instance) => unsafe {
+                gl::DrawElementsInstancedBaseInstance(
+                    primitive,
+                    self.count,
+                    index_type,
+                    indices,
+                    instances,
+                    base_instance,
+                );
+            },
+            (Some(instances), base_vertex, base_instance) => unsafe {
+                gl::DrawElementsInstancedBaseVertexBaseInstance(
+                    primitive,
+                    self.count,
+                    index_type,
+                    indices,
+                    instances,
+                    base_vertex,
+                    base_instance,
+                );
+            },
+        }
+    }
+}
+
+// Several draws of the same primitive and index type submitted in a single call
+#[derive(Clone, Debug)]
+pub struct MultiDrawCall {
+    primitive: Primitive,
+    index_type: data::Type,
+    firsts: Vec<GLint>,
+    counts: Vec<GLsizei>,
+}
+
+impl MultiDrawCall {
+    pub const fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            index_type: data::Type::None,
+            firsts: vec![],
+            counts: vec![],
+        }
+    }
+    pub fn index_type(value: Self, index_type: data::Type) -> Self {
+        assert!(ElementBufferObject::is_supported_type(index_type));
+        Self {
+            index_type,
+            ..value
+        }
+    }
+
+    // Batch plain draw calls, they must all share primitive and index type, not be instanced and
+    // have no base vertex or instance
+    pub fn from_draw_calls(draw_calls: &[DrawCall]) -> Option<Self> {
+        let first = draw_calls.first()?;
+        let mut multi_draw = Self {
+            primitive: first.primitive,
+            index_type: first.index_type,
+            firsts: Vec::with_capacity(draw_calls.len()),
+            counts: Vec::with_capacity(draw_calls.len()),
+        };
+        for draw_call in draw_calls {
+            if draw_call.primitive != first.primitive
+                || draw_call.index_type != first.index_type
+                || draw_call.is_instanced()
+                || draw_call.base_vertex != 0
+                || draw_call.base_instance != 0
+            {
+                return None;
+            }
+            multi_draw.push(draw_call.first, draw_call.count);
+        }
+        Some(multi_draw)
+    }
+
+    pub fn push(&mut self, first: GLint, count: GLsizei) {
+        self.firsts.push(first);
+        self.counts.push(count);
+    }
+
+    pub fn clear(&mut self) {
+        self.firsts.clear();
+        self.counts.clear();
+    }
+
+    pub const fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
     pub fn draw(&self) {
         let primitive = self.primitive as GLenum;
+        let draw_count = self.counts.len() as GLsizei;
         if self.index_type == data::Type::None {
-            // If no EBO is present, use glDrawArrays
-            unsafe { gl::DrawArrays(primitive, self.first, self.count) }
-            check_gl_error();
+            unsafe {
+                gl::MultiDrawArrays(
+                    primitive,
+                    self.firsts.as_ptr(),
+                    self.counts.as_ptr(),
+                    draw_count,
+                );
+            }
         } else {
             let base_pointer: *const c_void = std::ptr::null();
-            let index_type = self.index_type as GLenum;
+            let indices: Vec<*const c_void> = self
+                .firsts
+                .iter()
+                .map(|first| base_pointer.wrapping_byte_add(*first as usize))
+                .collect();
             unsafe {
-                gl::DrawElements(
+                gl::MultiDrawElements(
                     primitive,
-                    self.count,
-                    index_type,
-                    base_pointer.wrapping_byte_add(self.first as usize),
-                )
-            };
+                    self.counts.as_ptr(),
+                    self.index_type as GLenum,
+                    indices.as_ptr(),
+                    draw_count,
+                );
+            }
+        }
+        check_gl_error();
+    }
+}
+
+// Draws whose parameters are read from an IndirectBufferObject. Multiple draws are a single call
+// with GL 4.3 and a loop of single indirect draws before. Nothing is drawn without commands or
+// when they do not all fit in the buffer
+#[derive(Clone, Copy, Debug)]
+pub struct IndirectDrawCall {
+    primitive: Primitive,
+    index_type: data::Type,
+    first: usize,
+    draw_count: GLsizei,
+}
+
+impl IndirectDrawCall {
+    pub const fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            index_type: data::Type::None,
+            first: 0,
+            draw_count: 1,
+        }
+    }
+    // Index of the first command in the buffer
+    pub const fn first(value: Self, first: usize) -> Self {
+        Self { first, ..value }
+    }
+    // Number of consecutive commands to execute
+    pub const fn draw_count(value: Self, draw_count: GLsizei) -> Self {
+        Self {
+            draw_count,
+            ..value
+        }
+    }
+    pub const fn index_type(value: Self, index_type: data::Type) -> Self {
+        assert!(ElementBufferObject::is_supported_type(index_type));
+        Self {
+            index_type,
+            ..value
+        }
+    }
+
+    pub fn draw<T: IndirectCommand>(&self, commands: &IndirectBufferObject<T>) {
+        assert_eq!(
+            T::INDEXED,
+            self.index_type != data::Type::None,
+            "Indexed commands need an index type, array commands must not have one"
+        );
+        if self.draw_count <= 0 {
+            return;
+        }
+        // GL would read the commands past the end of the buffer
+        let available = commands.len();
+        if self
+            .first
+            .checked_add(self.draw_count as usize)
+            .is_none_or(|end| end > available)
+        {
+            log::error!(
+                "Indirect draw of commands {} to {} out of {available}",
+                self.first,
+                self.first.saturating_add(self.draw_count as usize)
+            );
+            return;
+        }
+        let primitive = self.primitive as GLenum;
+        let base_pointer: *const c_void = std::ptr::null();
+        let indirect = base_pointer.wrapping_byte_add(self.first * size_of::<T>());
+        let stride = size_of::<T>() as GLsizei;
+
+        commands.bind();
+        let multi_draw = Capabilities::current_supports(4, 3, "GL_ARB_multi_draw_indirect");
+        match (T::INDEXED, self.draw_count) {
+            (_, draw_count) if draw_count > 1 && !multi_draw => {
+                for command in 0..draw_count as usize {
+                    let indirect = indirect.wrapping_byte_add(command * size_of::<T>());
+                    if T::INDEXED {
+                        unsafe {
+                            gl::DrawElementsIndirect(
+                                primitive,
+                                self.index_type as GLenum,
+                                indirect,
+                            );
+                        }
+                    } else {
+                        unsafe { gl::DrawArraysIndirect(primitive, indirect) };
+                    }
+                }
+            }
+            (false, 1) => unsafe { gl::DrawArraysIndirect(primitive, indirect) },
+            (false, draw_count) => unsafe {
+                gl::MultiDrawArraysIndirect(primitive, indirect, draw_count, stride);
+            },
+            (true, 1) => unsafe {
+                gl::DrawElementsIndirect(primitive, self.index_type as GLenum, indirect);
+            },
+            (true, draw_count) => unsafe {
+                gl::MultiDrawElementsIndirect(
+                    primitive,
+                    self.index_type as GLenum,
+                    indirect,
+                    draw_count,
+                    stride,
+                );
+            },
         }
+        check_gl_error();
+        commands.unbind();
     }
 }
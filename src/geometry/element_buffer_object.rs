@@ -1,11 +1,29 @@
 use crate::{
     core::{
-        buffer_object::{BufferObject, Target},
+        buffer_object::{create_buffer, BufferObject, Target},
         data,
-        object::{Handle, NullHandle, Object},
+        object::{Handle, Object},
+        state,
     },
     error::check_gl_error,
 };
+// Integer types that can be stored in an ElementBufferObject
+pub trait Index: Copy {
+    const DATA_TYPE: data::Type;
+}
+
+impl Index for u8 {
+    const DATA_TYPE: data::Type = data::Type::UByte;
+}
+
+impl Index for u16 {
+    const DATA_TYPE: data::Type = data::Type::UShort;
+}
+
+impl Index for u32 {
+    const DATA_TYPE: data::Type = data::Type::UInt;
+}
+
 #[derive(Debug)]
 pub struct ElementBufferObject {
     handle: Handle,
@@ -23,9 +41,7 @@ impl ElementBufferObject {
 impl ElementBufferObject {
     #[must_use]
     pub fn new() -> Self {
-        let mut handle = NullHandle;
-        unsafe { gl::GenBuffers(1, &mut handle) };
-        check_gl_error();
+        let handle = create_buffer();
         Self { handle }
     }
 }
@@ -40,12 +56,12 @@ impl Drop for ElementBufferObject {
     fn drop(&mut self) {
         unsafe { gl::DeleteBuffers(1, &self.handle()) }
         check_gl_error();
+        state::with(|state| state.forget_buffer(self.handle()));
     }
 }
 impl Object for ElementBufferObject {
     fn bind(&self) {
-        unsafe { gl::BindBuffer(self.target() as u32, self.handle()) }
-        check_gl_error();
+        state::with(|state| state.bind_buffer(self.target() as u32, self.handle()));
     }
 
     fn handle(&self) -> Handle {
@@ -0,0 +1,119 @@
+use std::marker::PhantomData;
+
+use gl::types::{GLint, GLsizeiptr, GLuint};
+
+use crate::{
+    core::{
+        buffer_object::{create_buffer, BufferObject, Target, Usage},
+        data::Pod,
+        object::{Handle, Object},
+        state,
+    },
+    error::check_gl_error,
+};
+
+// Command layout read by glDrawArraysIndirect and glMultiDrawArraysIndirect
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawArraysIndirectCommand {
+    pub count: GLuint,
+    pub instance_count: GLuint,
+    pub first: GLuint,
+    pub base_instance: GLuint,
+}
+
+// Command layout read by glDrawElementsIndirect and glMultiDrawElementsIndirect
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawElementsIndirectCommand {
+    pub count: GLuint,
+    pub instance_count: GLuint,
+    pub first_index: GLuint,
+    pub base_vertex: GLint,
+    pub base_instance: GLuint,
+}
+
+// Both commands are #[repr(C)] structs of 32 bit integers
+unsafe impl Pod for DrawArraysIndirectCommand {}
+unsafe impl Pod for DrawElementsIndirectCommand {}
+
+pub trait IndirectCommand: Copy {
+    // Whether the command draws from the element buffer of the bound VAO
+    const INDEXED: bool;
+}
+
+impl IndirectCommand for DrawArraysIndirectCommand {
+    const INDEXED: bool = false;
+}
+
+impl IndirectCommand for DrawElementsIndirectCommand {
+    const INDEXED: bool = true;
+}
+
+// Buffer holding draw commands of a single type, bound to GL_DRAW_INDIRECT_BUFFER
+#[derive(Debug)]
+pub struct IndirectBufferObject<T: IndirectCommand> {
+    handle: Handle,
+    command: PhantomData<T>,
+}
+
+impl<T: IndirectCommand> IndirectBufferObject<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        let handle = create_buffer();
+        Self {
+            handle,
+            command: PhantomData,
+        }
+    }
+
+    pub fn allocate_commands(&self, commands: &[T], usage: Usage) {
+        self.allocate_data(commands, usage);
+    }
+
+    // Number of commands the buffer has room for
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.size() as usize / size_of::<T>()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Overwrite the commands starting at command index `first`
+    pub fn update_commands(&self, commands: &[T], first: usize) {
+        self.update_data(commands, (first * size_of::<T>()) as GLsizeiptr);
+    }
+}
+
+impl<T: IndirectCommand> Default for IndirectBufferObject<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: IndirectCommand> Drop for IndirectBufferObject<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.handle()) }
+        check_gl_error();
+        state::with(|state| state.forget_buffer(self.handle()));
+    }
+}
+
+impl<T: IndirectCommand> Object for IndirectBufferObject<T> {
+    fn bind(&self) {
+        state::with(|state| state.bind_buffer(self.target() as u32, self.handle()));
+    }
+
+    fn handle(&self) -> Handle {
+        self.handle
+    }
+}
+
+impl<T: IndirectCommand> BufferObject for IndirectBufferObject<T> {
+    fn target(&self) -> Target {
+        Target::DrawIndirectBuffer
+    }
+}
@@ -1,25 +1,286 @@
-use crate::core::buffer_object::{BufferObject, Usage};
+use crate::core::{
+    buffer_object::{BufferObject, Usage},
+    data,
+    object::Object,
+    state,
+};
 
 use super::{
-    element_buffer_object::ElementBufferObject, vertex_array_object::VertexArrayObject,
+    drawcall::{DrawCall, Primitive},
+    element_buffer_object::{ElementBufferObject, Index},
+    vertex_array_object::VertexArrayObject,
     vertex_buffer_object::VertexBufferObject,
+    vertex_format::VertexFormat,
+    vertex_layout::{Vertex, VertexLayout},
 };
 
-#[derive(Debug)]
+// Part of a mesh drawn with a single draw call and material
+#[derive(Clone, Copy, Debug)]
+pub struct Submesh {
+    vao: usize,
+    draw_call: DrawCall,
+    material: usize,
+}
+
+impl Submesh {
+    #[must_use]
+    pub const fn vao(&self) -> usize {
+        self.vao
+    }
+
+    #[must_use]
+    pub const fn draw_call(&self) -> DrawCall {
+        self.draw_call
+    }
+
+    #[must_use]
+    pub const fn material(&self) -> usize {
+        self.material
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Mesh {
     vbos: Vec<VertexBufferObject>,
     ebos: Vec<ElementBufferObject>,
-    abos: Vec<VertexArrayObject>,
+    vaos: Vec<VertexArrayObject>,
+    submeshes: Vec<Submesh>,
+    // Vertices in each VBO and (type, count) of the indices in each EBO
+    vertex_counts: Vec<usize>,
+    index_formats: Vec<(data::Type, usize)>,
+    // Vertices read by all the VAOs, buffers shared by several VAOs count once per VAO
+    vertex_count: usize,
 }
+
 impl Mesh {
-    pub fn add_vertex_data<T>(&mut self, vertices: &[T]) {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Mesh with one interleaved VBO and a single submesh drawing all the vertices
+    #[must_use]
+    pub fn from_vertices<T>(format: &VertexFormat, vertices: &[T], primitive: Primitive) -> Self {
+        let mut mesh = Self::new();
+        let vbo = mesh.add_vertex_data(vertices);
+        let vao = mesh.add_vertex_array(format, &[vbo], None);
+        let draw_call = DrawCall::count(DrawCall::new(primitive), vertices.len() as i32);
+        mesh.add_submesh(vao, draw_call, 0);
+        mesh
+    }
+
+    // Mesh with one interleaved VBO, one EBO and a single submesh drawing all the indices
+    #[must_use]
+    pub fn from_indexed_vertices<T, I: Index>(
+        format: &VertexFormat,
+        vertices: &[T],
+        indices: &[I],
+        primitive: Primitive,
+    ) -> Self {
+        let mut mesh = Self::new();
+        let vbo = mesh.add_vertex_data(vertices);
+        let ebo = mesh.add_element_data(indices);
+        let vao = mesh.add_vertex_array(format, &[vbo], Some(ebo));
+        let draw_call = DrawCall::count(
+            DrawCall::index_type(DrawCall::new(primitive), I::DATA_TYPE),
+            indices.len() as i32,
+        );
+        mesh.add_submesh(vao, draw_call, 0);
+        mesh
+    }
+
+    // Same as `from_indexed_vertices`, with the layout taken from a #[derive(Vertex)] struct
+    #[must_use]
+    pub fn from_indexed<V: Vertex, I: Index>(
+        vertices: &[V],
+        indices: &[I],
+        primitive: Primitive,
+    ) -> Self {
+        let mut mesh = Self::new();
+        let vbo = mesh.add_vertex_data(vertices);
+        let ebo = mesh.add_element_data(indices);
+        let vao = mesh.add_vertex_array_from_layout(&V::layout(), vbo, Some(ebo));
+        let draw_call = DrawCall::count(
+            DrawCall::index_type(DrawCall::new(primitive), I::DATA_TYPE),
+            indices.len() as i32,
+        );
+        mesh.add_submesh(vao, draw_call, 0);
+        mesh
+    }
+
+    // Returns the index of the new VBO
+    pub fn add_vertex_data<T>(&mut self, vertices: &[T]) -> usize {
         let vbo = VertexBufferObject::new();
         vbo.allocate_data(vertices, Usage::StaticDraw);
-        self.vbos.push(vbo);
+        self.push_vertex_buffer(vbo, vertices.len())
     }
-    pub fn add_element_data<T>(&mut self, indices: &[T]) {
+
+    // Returns the index of the new EBO
+    pub fn add_element_data<I: Index>(&mut self, indices: &[I]) -> usize {
         let ebo = ElementBufferObject::new();
         ebo.allocate_data(indices, Usage::StaticDraw);
+        self.push_element_buffer(ebo, I::DATA_TYPE, indices.len())
+    }
+
+    // Same as `add_vertex_data` for already encoded vertices, such as those read from a file
+    pub fn add_raw_vertex_data(&mut self, data: &[u8], vertex_count: usize) -> usize {
+        let vbo = VertexBufferObject::new();
+        vbo.allocate_data(data, Usage::StaticDraw);
+        self.push_vertex_buffer(vbo, vertex_count)
+    }
+
+    // Same as `add_element_data` for already encoded indices of `index_type`
+    pub fn add_raw_element_data(
+        &mut self,
+        data: &[u8],
+        index_type: data::Type,
+        index_count: usize,
+    ) -> Result<usize, String> {
+        if !ElementBufferObject::is_supported_type(index_type) {
+            return Err(format!("{index_type:?} cannot be used as index type"));
+        }
+        let ebo = ElementBufferObject::new();
+        ebo.allocate_data(data, Usage::StaticDraw);
+        Ok(self.push_element_buffer(ebo, index_type, index_count))
+    }
+
+    fn push_vertex_buffer(&mut self, vbo: VertexBufferObject, vertex_count: usize) -> usize {
+        self.vbos.push(vbo);
+        self.vertex_counts.push(vertex_count);
+        self.vbos.len() - 1
+    }
+
+    fn push_element_buffer(
+        &mut self,
+        ebo: ElementBufferObject,
+        index_type: data::Type,
+        index_count: usize,
+    ) -> usize {
         self.ebos.push(ebo);
+        self.index_formats.push((index_type, index_count));
+        self.ebos.len() - 1
+    }
+
+    // Create a VAO reading `vbos` with the given format, returns its index
+    pub fn add_vertex_array(
+        &mut self,
+        format: &VertexFormat,
+        vbos: &[usize],
+        ebo: Option<usize>,
+    ) -> usize {
+        let vao = VertexArrayObject::new();
+        // A VAO reads as many vertices as its shortest buffer holds
+        self.vertex_count += vbos
+            .iter()
+            .map(|&i| self.vertex_counts[i])
+            .min()
+            .unwrap_or(0);
+        let vbos: Vec<&VertexBufferObject> = vbos.iter().map(|&i| &self.vbos[i]).collect();
+        format.apply(&vao, &vbos);
+        self.push_vertex_array(vao, ebo)
+    }
+
+    // Create a VAO reading a single interleaved VBO with the layout of a #[derive(Vertex)] struct
+    pub fn add_vertex_array_from_layout(
+        &mut self,
+        layout: &VertexLayout,
+        vbo: usize,
+        ebo: Option<usize>,
+    ) -> usize {
+        let vao = VertexArrayObject::new();
+        self.vertex_count += self.vertex_counts[vbo];
+        layout.apply_to(&vao, &self.vbos[vbo]);
+        if !state::direct_state_access() {
+            vao.unbind();
+            self.vbos[vbo].unbind();
+        }
+        self.push_vertex_array(vao, ebo)
+    }
+
+    fn push_vertex_array(&mut self, vao: VertexArrayObject, ebo: Option<usize>) -> usize {
+        if let Some(ebo) = ebo {
+            vao.set_element_buffer(&self.ebos[ebo]);
+            if !state::direct_state_access() {
+                vao.unbind();
+                self.ebos[ebo].unbind();
+            }
+        }
+        self.vaos.push(vao);
+        self.vaos.len() - 1
+    }
+
+    // Returns the index of the new submesh
+    pub fn add_submesh(&mut self, vao: usize, draw_call: DrawCall, material: usize) -> usize {
+        assert!(vao < self.vaos.len(), "Invalid VAO index {vao}");
+        self.submeshes.push(Submesh {
+            vao,
+            draw_call,
+            material,
+        });
+        self.submeshes.len() - 1
+    }
+
+    pub fn draw(&self) {
+        for index in 0..self.submeshes.len() {
+            self.draw_submesh(index);
+        }
+    }
+
+    pub fn draw_submesh(&self, index: usize) {
+        let submesh = &self.submeshes[index];
+        self.vaos[submesh.vao].bind();
+        submesh.draw_call.draw();
+    }
+
+    #[must_use]
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    #[must_use]
+    pub const fn submesh_count(&self) -> usize {
+        self.submeshes.len()
+    }
+
+    #[must_use]
+    pub fn vertex_buffer(&self, index: usize) -> &VertexBufferObject {
+        &self.vbos[index]
+    }
+
+    #[must_use]
+    pub fn element_buffer(&self, index: usize) -> &ElementBufferObject {
+        &self.ebos[index]
+    }
+
+    #[must_use]
+    pub fn vertex_array(&self, index: usize) -> &VertexArrayObject {
+        &self.vaos[index]
+    }
+
+    // Vertices read by all the vertex arrays
+    #[must_use]
+    pub const fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    // Indices in all the element buffers
+    #[must_use]
+    pub fn index_count(&self) -> usize {
+        self.index_formats.iter().map(|(_, count)| count).sum()
+    }
+
+    #[must_use]
+    pub fn buffer_vertex_count(&self, vbo: usize) -> usize {
+        self.vertex_counts[vbo]
+    }
+
+    #[must_use]
+    pub fn buffer_index_count(&self, ebo: usize) -> usize {
+        self.index_formats[ebo].1
+    }
+
+    #[must_use]
+    pub fn index_type(&self, ebo: usize) -> data::Type {
+        self.index_formats[ebo].0
     }
 }
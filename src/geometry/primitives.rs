@@ -0,0 +1,473 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+use glam::{Vec2, Vec3, Vec4};
+
+use super::{drawcall::Primitive, mesh::Mesh, vertex_layout::Vertex};
+
+// Vertex produced by all the generators. Tangent w is the bitangent sign, B = w * cross(N, T)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Vertex)]
+#[repr(C)]
+pub struct PrimitiveVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub tangent: Vec4,
+}
+
+// CPU-side geometry, indexed as a triangle list with counter-clockwise front faces
+#[derive(Clone, Debug)]
+pub struct MeshData<V = PrimitiveVertex> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+}
+
+impl<V> Default for MeshData<V> {
+    fn default() -> Self {
+        Self {
+            vertices: vec![],
+            indices: vec![],
+        }
+    }
+}
+
+impl<V: Vertex> MeshData<V> {
+    #[must_use]
+    pub fn to_mesh(&self) -> Mesh {
+        Mesh::from_indexed(&self.vertices, &self.indices, Primitive::Triangles)
+    }
+}
+
+impl MeshData {
+    fn push_vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2, tangent: Vec3) -> u32 {
+        self.vertices.push(PrimitiveVertex {
+            position,
+            normal,
+            uv,
+            tangent: tangent.extend(1.0),
+        });
+        (self.vertices.len() - 1) as u32
+    }
+
+    // Grid of (columns + 1) * (rows + 1) vertices, `surface` maps (u, v) in [0, 1] to position,
+    // normal and tangent. cross(dP/du, dP/dv) must point along the normal for correct winding
+    fn push_surface(
+        &mut self,
+        columns: u32,
+        rows: u32,
+        surface: impl Fn(f32, f32) -> (Vec3, Vec3, Vec3),
+    ) {
+        let base = self.vertices.len() as u32;
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let uv = Vec2::new(i as f32 / columns as f32, j as f32 / rows as f32);
+                let (position, normal, tangent) = surface(uv.x, uv.y);
+                self.push_vertex(position, normal, uv, tangent);
+            }
+        }
+        let row_length = columns + 1;
+        for j in 0..rows {
+            for i in 0..columns {
+                let bottom_left = base + j * row_length + i;
+                let bottom_right = bottom_left + 1;
+                let top_left = bottom_left + row_length;
+                let top_right = top_left + 1;
+                self.indices
+                    .extend_from_slice(&[bottom_left, bottom_right, top_right]);
+                self.indices
+                    .extend_from_slice(&[bottom_left, top_right, top_left]);
+            }
+        }
+    }
+
+    // Surface of revolution around Y. Each profile point is (radius, height) with its normal in
+    // the same (radial, vertical) plane, listed from bottom to top. Profile v is the arc length
+    fn push_lathe(&mut self, profile: &[(Vec2, Vec2)], segments: u32) {
+        let mut lengths = vec![0.0];
+        for pair in profile.windows(2) {
+            let length = lengths.last().copied().unwrap_or_default();
+            lengths.push(length + pair[0].0.distance(pair[1].0));
+        }
+        let total_length = lengths
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .max(f32::EPSILON);
+
+        let base = self.vertices.len() as u32;
+        for (&(point, normal), length) in profile.iter().zip(&lengths) {
+            for i in 0..=segments {
+                let u = i as f32 / segments as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                self.push_vertex(
+                    Vec3::new(point.x * sin, point.y, point.x * cos),
+                    Vec3::new(normal.x * sin, normal.y, normal.x * cos).normalize_or_zero(),
+                    Vec2::new(u, length / total_length),
+                    Vec3::new(cos, 0.0, -sin),
+                );
+            }
+        }
+        // Rings on the axis collapse to a point, skip the triangles that would be degenerate
+        let max_radius = profile
+            .iter()
+            .fold(0.0_f32, |max, (p, _)| max.max(p.x.abs()));
+        let on_axis = |j: usize| profile[j].0.x.abs() <= max_radius * 1e-6;
+        let row_length = segments + 1;
+        for j in 0..profile.len().saturating_sub(1) {
+            for i in 0..segments {
+                let bottom_left = base + j as u32 * row_length + i;
+                let bottom_right = bottom_left + 1;
+                let top_left = bottom_left + row_length;
+                let top_right = top_left + 1;
+                if !on_axis(j) {
+                    self.indices
+                        .extend_from_slice(&[bottom_left, bottom_right, top_right]);
+                }
+                if !on_axis(j + 1) {
+                    self.indices
+                        .extend_from_slice(&[bottom_left, top_right, top_left]);
+                }
+            }
+        }
+    }
+
+    // Flat disk at `height` facing up or down, as a fan around its center
+    fn push_disk(&mut self, radius: f32, height: f32, segments: u32, up: bool) {
+        let normal = if up { Vec3::Y } else { Vec3::NEG_Y };
+        // v grows towards +Z on both caps, but cross(N, T) only points there when facing down
+        let bitangent_sign = if up { -1.0 } else { 1.0 };
+        let push = |data: &mut Self, position: Vec3, uv: Vec2| {
+            let index = data.push_vertex(position, normal, uv, Vec3::X);
+            data.vertices[index as usize].tangent.w = bitangent_sign;
+            index
+        };
+        let center = push(self, Vec3::new(0.0, height, 0.0), Vec2::splat(0.5));
+        for i in 0..=segments {
+            let (sin, cos) = (i as f32 / segments as f32 * TAU).sin_cos();
+            let position = Vec3::new(radius * sin, height, radius * cos);
+            let index = push(self, position, Vec2::new(0.5 + 0.5 * sin, 0.5 + 0.5 * cos));
+            if i > 0 {
+                if up {
+                    self.indices.extend_from_slice(&[center, index - 1, index]);
+                } else {
+                    self.indices.extend_from_slice(&[center, index, index - 1]);
+                }
+            }
+        }
+    }
+}
+
+// Plane on XZ facing +Y, centered at the origin
+#[must_use]
+pub fn plane(size: Vec2, columns: u32, rows: u32) -> MeshData {
+    let mut data = MeshData::default();
+    data.push_surface(columns.max(1), rows.max(1), |u, v| {
+        let position = Vec3::new((u - 0.5) * size.x, 0.0, (0.5 - v) * size.y);
+        (position, Vec3::Y, Vec3::X)
+    });
+    data
+}
+
+// Unit grid on XY facing +Z, centered at the origin, like the one in the terrain exercise
+#[must_use]
+pub fn grid(columns: u32, rows: u32) -> MeshData {
+    let mut data = MeshData::default();
+    data.push_surface(columns.max(1), rows.max(1), |u, v| {
+        (Vec3::new(u - 0.5, v - 0.5, 0.0), Vec3::Z, Vec3::X)
+    });
+    data
+}
+
+// Axis aligned cube centered at the origin, each face split in subdivisions * subdivisions quads
+#[must_use]
+pub fn cube(size: f32, subdivisions: u32) -> MeshData {
+    const FACES: [(Vec3, Vec3, Vec3); 6] = [
+        // normal, u axis, v axis
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    let subdivisions = subdivisions.max(1);
+    let mut data = MeshData::default();
+    for (normal, u_axis, v_axis) in FACES {
+        data.push_surface(subdivisions, subdivisions, |u, v| {
+            let position = (normal * 0.5 + u_axis * (u - 0.5) + v_axis * (v - 0.5)) * size;
+            (position, normal, u_axis)
+        });
+    }
+    data
+}
+
+// Sphere made of `segments` meridians and `rings` parallels
+#[must_use]
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(2);
+    let profile: Vec<(Vec2, Vec2)> = (0..=rings)
+        .map(|j| {
+            let (sin, cos) = (PI * (1.0 - j as f32 / rings as f32)).sin_cos();
+            let normal = Vec2::new(sin, cos);
+            (normal * radius, normal)
+        })
+        .collect();
+    let mut data = MeshData::default();
+    data.push_lathe(&profile, segments.max(3));
+    data
+}
+
+// Sphere from a subdivided icosahedron, with evenly distributed vertices.
+// UVs use a spherical mapping, so triangles crossing the seam at -Z stretch the texture
+#[must_use]
+pub fn ico_sphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .into_iter()
+    .map(|(x, y, z)| Vec3::new(x, y, z).normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vec3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let position = (positions[a as usize] + positions[b as usize]).normalize();
+                positions.push(position);
+                (positions.len() - 1) as u32
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut data = MeshData::default();
+    for normal in positions {
+        let longitude = normal.x.atan2(normal.z);
+        let uv = Vec2::new(
+            0.5 + longitude / TAU,
+            0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI,
+        );
+        let tangent = Vec3::new(longitude.cos(), 0.0, -longitude.sin());
+        data.push_vertex(normal * radius, normal, uv, tangent);
+    }
+    for [a, b, c] in triangles {
+        // Keep counter-clockwise winding when seen from outside
+        let [pa, pb, pc] = [a, b, c].map(|i| data.vertices[i as usize].position);
+        if (pb - pa).cross(pc - pa).dot(pa + pb + pc) >= 0.0 {
+            data.indices.extend_from_slice(&[a, b, c]);
+        } else {
+            data.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+    data
+}
+
+// Cylinder along Y centered at the origin, with both caps
+#[must_use]
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let half_height = height * 0.5;
+    let mut data = MeshData::default();
+    data.push_lathe(
+        &[
+            (Vec2::new(radius, -half_height), Vec2::X),
+            (Vec2::new(radius, half_height), Vec2::X),
+        ],
+        segments,
+    );
+    data.push_disk(radius, half_height, segments, true);
+    data.push_disk(radius, -half_height, segments, false);
+    data
+}
+
+// Cone along Y centered at the origin, with the apex at +Y and the base cap at -Y
+#[must_use]
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let half_height = height * 0.5;
+    let normal = Vec2::new(height, radius).normalize();
+    let mut data = MeshData::default();
+    data.push_lathe(
+        &[
+            (Vec2::new(radius, -half_height), normal),
+            (Vec2::new(0.0, half_height), normal),
+        ],
+        segments,
+    );
+    data.push_disk(radius, -half_height, segments, false);
+    data
+}
+
+// Cylinder of the given height with hemispheres on both ends, total height is height + 2 * radius
+#[must_use]
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(1);
+    let half_height = height * 0.5;
+    let mut profile = vec![];
+    // Bottom hemisphere, from the south pole to the equator
+    for j in 0..=rings {
+        let (sin, cos) = (PI * (1.0 - 0.5 * j as f32 / rings as f32)).sin_cos();
+        let normal = Vec2::new(sin, cos);
+        profile.push((normal * radius - Vec2::new(0.0, half_height), normal));
+    }
+    // Top hemisphere, from the equator to the north pole
+    for j in 0..=rings {
+        let (sin, cos) = (PI * 0.5 * (1.0 - j as f32 / rings as f32)).sin_cos();
+        let normal = Vec2::new(sin, cos);
+        profile.push((normal * radius + Vec2::new(0.0, half_height), normal));
+    }
+    let mut data = MeshData::default();
+    data.push_lathe(&profile, segments.max(3));
+    data
+}
+
+// Torus around Y, `major_radius` to the center of the tube and `minor_radius` of the tube
+#[must_use]
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> MeshData {
+    let mut data = MeshData::default();
+    data.push_surface(major_segments.max(3), minor_segments.max(3), |u, v| {
+        let (sin_major, cos_major) = (u * TAU).sin_cos();
+        let (sin_minor, cos_minor) = (v * TAU).sin_cos();
+        let normal = Vec3::new(cos_minor * sin_major, sin_minor, cos_minor * cos_major);
+        let center = Vec3::new(sin_major, 0.0, cos_major) * major_radius;
+        let tangent = Vec3::new(cos_major, 0.0, -sin_major);
+        (center + normal * minor_radius, normal, tangent)
+    });
+    data
+}
+
+// Single triangle covering the whole clip space, UVs are [0, 1] inside the viewport
+#[must_use]
+pub fn fullscreen_triangle() -> MeshData {
+    let mut data = MeshData::default();
+    for (x, y) in [(-1.0, -1.0), (3.0, -1.0), (-1.0, 3.0)] {
+        let uv = Vec2::new((x + 1.0) * 0.5, (y + 1.0) * 0.5);
+        data.push_vertex(Vec3::new(x, y, 0.0), Vec3::Z, uv, Vec3::X);
+    }
+    data.indices.extend_from_slice(&[0, 1, 2]);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_winding(data: &MeshData) {
+        assert_eq!(data.indices.len() % 3, 0);
+        for vertex in &data.vertices {
+            assert!(vertex.normal.is_normalized(), "{vertex:?}");
+            assert!(
+                vertex.tangent.truncate().dot(vertex.normal).abs() < 1e-4,
+                "{vertex:?}"
+            );
+        }
+        for triangle in data.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize]);
+            let face = (b.position - a.position).cross(c.position - a.position);
+            // Triangles collapsed at the poles of lathes have no orientation
+            if face.length() < 1e-6 {
+                continue;
+            }
+            let normal = a.normal + b.normal + c.normal;
+            assert!(
+                face.dot(normal) > 0.0,
+                "{triangle:?} faces away from its normals"
+            );
+        }
+    }
+
+    // Sum of the signed volumes of the tetrahedra from the origin, negative if inside out
+    fn volume(data: &MeshData) -> f32 {
+        data.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize].position);
+                a.dot(b.cross(c)) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn front_faces_follow_the_normals() {
+        for data in [
+            plane(Vec2::new(2.0, 3.0), 4, 2),
+            grid(3, 5),
+            cube(2.0, 3),
+            uv_sphere(1.5, 16, 8),
+            ico_sphere(1.5, 2),
+            cylinder(1.0, 2.0, 12),
+            cone(1.0, 2.0, 12),
+            capsule(0.5, 1.0, 12, 4),
+            torus(2.0, 0.5, 16, 8),
+            fullscreen_triangle(),
+        ] {
+            check_winding(&data);
+        }
+    }
+
+    #[test]
+    fn closed_shapes_enclose_their_volume() {
+        let close = |data: &MeshData, expected: f32| {
+            let volume = volume(data);
+            assert!(
+                (volume - expected).abs() < expected * 0.05,
+                "{volume} != {expected}"
+            );
+        };
+        close(&cube(2.0, 3), 8.0);
+        close(&uv_sphere(1.0, 64, 32), 4.0 / 3.0 * PI);
+        close(&ico_sphere(1.0, 4), 4.0 / 3.0 * PI);
+        close(&cylinder(1.0, 2.0, 64), 2.0 * PI);
+        close(&cone(1.0, 3.0, 64), PI);
+        close(&torus(2.0, 0.5, 64, 32), 2.0 * PI * PI * 2.0 * 0.25);
+    }
+}
@@ -0,0 +1,833 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::core::data;
+
+use super::mesh::Mesh;
+
+// Size of the simulated post-transform cache used by the vertex cache optimizer
+const CACHE_SIZE: usize = 32;
+
+// Smooth normals for an indexed triangle list, each face contributes weighted by its corner angle
+#[must_use]
+pub fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let corners = triangle_corners(positions, triangle);
+        let normal = (corners[1] - corners[0])
+            .cross(corners[2] - corners[0])
+            .normalize_or_zero();
+        for (corner, &index) in triangle.iter().enumerate() {
+            normals[index as usize] += normal * corner_angle(&corners, corner);
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or(Vec3::Z))
+        .collect()
+}
+
+// Vertices, one tangent for each of them and the indices into both
+pub type TangentMesh<V> = (Vec<V>, Vec<Vec4>, Vec<u32>);
+
+// MikkTSpace tangents, the ones normal map bakers and glTF expect, with the bitangent sign in w
+// so B = w * cross(N, T). `attributes` gives the position, normal and UV of a vertex. Corners
+// of a vertex that get different tangents are split, so returns the vertices, a tangent for
+// each of them and the remapped indices
+pub fn generate_tangents<V: Copy>(
+    vertices: &[V],
+    indices: &[u32],
+    attributes: impl Fn(&V) -> (Vec3, Vec3, Vec2),
+) -> Result<TangentMesh<V>, String> {
+    validate_indices(indices, vertices.len())?;
+    let mut geometry = TangentGeometry {
+        vertices,
+        indices,
+        attributes,
+        tangents: vec![Vec4::ZERO; indices.len()],
+    };
+    if !bevy_mikktspace::generate_tangents(&mut geometry) {
+        return Err("MikkTSpace could not generate tangents for the mesh".to_owned());
+    }
+
+    // Corners of the same vertex with the same tangent share one output vertex
+    let mut split: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    let mut output = vec![];
+    let mut tangents = vec![];
+    let indices = indices
+        .iter()
+        .zip(&geometry.tangents)
+        .map(|(&index, &tangent)| {
+            let key = (index, tangent.to_array().map(f32::to_bits));
+            *split.entry(key).or_insert_with(|| {
+                output.push(vertices[index as usize]);
+                tangents.push(tangent);
+                (output.len() - 1) as u32
+            })
+        })
+        .collect();
+    Ok((output, tangents, indices))
+}
+
+// Triangle list seen through the interface of the MikkTSpace implementation, which writes one
+// tangent for every corner
+struct TangentGeometry<'a, V, F> {
+    vertices: &'a [V],
+    indices: &'a [u32],
+    attributes: F,
+    tangents: Vec<Vec4>,
+}
+
+impl<V, F: Fn(&V) -> (Vec3, Vec3, Vec2)> TangentGeometry<'_, V, F> {
+    fn corner(&self, face: usize, vert: usize) -> (Vec3, Vec3, Vec2) {
+        (self.attributes)(&self.vertices[self.indices[face * 3 + vert] as usize])
+    }
+}
+
+impl<V, F: Fn(&V) -> (Vec3, Vec3, Vec2)> bevy_mikktspace::Geometry for TangentGeometry<'_, V, F> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.corner(face, vert).0.to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.corner(face, vert).1.to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.corner(face, vert).2.to_array()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vec4::from_array(tangent);
+    }
+}
+
+// Cheaper tangents that never split vertices: per face UV gradients accumulated by corner angle,
+// orthogonalized against the normal, with the bitangent sign in w. Normal maps baked by
+// MikkTSpace tools can show small seams with these, use `generate_tangents` for those
+#[must_use]
+pub fn generate_angle_weighted_tangents(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    uvs: &[Vec2],
+    indices: &[u32],
+) -> Vec<Vec4> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let corners = triangle_corners(positions, triangle);
+        let [uv0, uv1, uv2] = [0, 1, 2].map(|corner| uvs[triangle[corner] as usize]);
+        let (edge1, edge2) = (corners[1] - corners[0], corners[2] - corners[0]);
+        let (delta1, delta2) = (uv1 - uv0, uv2 - uv0);
+        let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = ((edge1 * delta2.y - edge2 * delta1.y) / determinant).normalize_or_zero();
+        let bitangent = ((edge2 * delta1.x - edge1 * delta2.x) / determinant).normalize_or_zero();
+        for (corner, &index) in triangle.iter().enumerate() {
+            let weight = corner_angle(&corners, corner);
+            tangents[index as usize] += tangent * weight;
+            bitangents[index as usize] += bitangent * weight;
+        }
+    }
+    normals
+        .iter()
+        .zip(tangents.iter().zip(&bitangents))
+        .map(|(&normal, (&tangent, &bitangent))| {
+            let tangent = (tangent - normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let sign = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(sign)
+        })
+        .collect()
+}
+
+fn triangle_corners(positions: &[Vec3], triangle: &[u32]) -> [Vec3; 3] {
+    [0, 1, 2].map(|corner| positions[triangle[corner] as usize])
+}
+
+fn corner_angle(corners: &[Vec3; 3], corner: usize) -> f32 {
+    let origin = corners[corner];
+    let a = corners[(corner + 1) % 3] - origin;
+    let b = corners[(corner + 2) % 3] - origin;
+    a.angle_between(b)
+}
+
+// Merge vertices that are exactly equal, the first occurrence is kept. Returns the unique
+// vertices and the remapped indices
+#[must_use]
+pub fn deduplicate_vertices<V: Copy + PartialEq>(
+    vertices: &[V],
+    indices: &[u32],
+    position: impl Fn(&V) -> Vec3,
+) -> (Vec<V>, Vec<u32>) {
+    // Vertices are bucketed by position so only candidates at the same spot are compared
+    let mut buckets: HashMap<[u32; 3], Vec<u32>> = HashMap::new();
+    let mut unique: Vec<V> = vec![];
+    let remap: Vec<u32> = vertices
+        .iter()
+        .map(|vertex| {
+            let key = position(vertex).to_array().map(f32::to_bits);
+            let bucket = buckets.entry(key).or_default();
+            if let Some(&existing) = bucket.iter().find(|&&i| unique[i as usize] == *vertex) {
+                existing
+            } else {
+                unique.push(*vertex);
+                let index = (unique.len() - 1) as u32;
+                bucket.push(index);
+                index
+            }
+        })
+        .collect();
+    (unique, remap_indices(indices, &remap))
+}
+
+// Merge vertices whose positions are closer than `tolerance`, keeping the attributes of the
+// first one. Triangles collapsed by the welding are removed
+#[must_use]
+pub fn weld_vertices<V: Copy>(
+    vertices: &[V],
+    indices: &[u32],
+    tolerance: f32,
+    position: impl Fn(&V) -> Vec3,
+) -> (Vec<V>, Vec<u32>) {
+    let cell_size = tolerance.max(f32::EPSILON);
+    let cell = |point: Vec3| (point / cell_size).floor().as_ivec3();
+    let mut grid: HashMap<IVec3, Vec<u32>> = HashMap::new();
+    let mut welded: Vec<V> = vec![];
+    let mut remap = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        let point = position(vertex);
+        let center = cell(point);
+        // Points within tolerance can only be in the neighbouring cells
+        let existing = (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))))
+            .filter_map(|offset| grid.get(&(center + offset)))
+            .flatten()
+            .find(|&&i| position(&welded[i as usize]).distance(point) <= tolerance)
+            .copied();
+        remap.push(existing.unwrap_or_else(|| {
+            welded.push(*vertex);
+            let index = (welded.len() - 1) as u32;
+            grid.entry(center).or_default().push(index);
+            index
+        }));
+    }
+    let indices = remove_degenerate_triangles(&remap_indices(indices, &remap));
+    (welded, indices)
+}
+
+// A triangle list whose indices all refer to one of `vertex_count` vertices
+pub fn validate_indices(indices: &[u32], vertex_count: usize) -> Result<(), String> {
+    if !indices.len().is_multiple_of(3) {
+        return Err(format!(
+            "{} indices do not make a triangle list",
+            indices.len()
+        ));
+    }
+    if let Some(index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(format!(
+            "Index {index} is out of range for {vertex_count} vertices"
+        ));
+    }
+    Ok(())
+}
+
+#[must_use]
+pub fn remap_indices(indices: &[u32], remap: &[u32]) -> Vec<u32> {
+    indices.iter().map(|&index| remap[index as usize]).collect()
+}
+
+// Drop triangles that reference the same vertex more than once
+#[must_use]
+pub fn remove_degenerate_triangles(indices: &[u32]) -> Vec<u32> {
+    indices
+        .chunks_exact(3)
+        .filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+        .flatten()
+        .copied()
+        .collect()
+}
+
+// Indices stored with the smallest type that fits, all of them can be used by an EBO
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexData {
+    UByte(Vec<u8>),
+    UShort(Vec<u16>),
+    UInt(Vec<u32>),
+}
+
+impl IndexData {
+    #[must_use]
+    pub fn narrow(indices: &[u32]) -> Self {
+        let max = indices.iter().copied().max().unwrap_or(0);
+        if u8::try_from(max).is_ok() {
+            Self::UByte(indices.iter().map(|&index| index as u8).collect())
+        } else if u16::try_from(max).is_ok() {
+            Self::UShort(indices.iter().map(|&index| index as u16).collect())
+        } else {
+            Self::UInt(indices.to_vec())
+        }
+    }
+
+    #[must_use]
+    pub const fn data_type(&self) -> data::Type {
+        match self {
+            Self::UByte(_) => data::Type::UByte,
+            Self::UShort(_) => data::Type::UShort,
+            Self::UInt(_) => data::Type::UInt,
+        }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::UByte(indices) => indices.len(),
+            Self::UShort(indices) => indices.len(),
+            Self::UInt(indices) => indices.len(),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Upload as a new EBO of the mesh, returns its index
+    pub fn add_to(&self, mesh: &mut Mesh) -> usize {
+        match self {
+            Self::UByte(indices) => mesh.add_element_data(indices),
+            Self::UShort(indices) => mesh.add_element_data(indices),
+            Self::UInt(indices) => mesh.add_element_data(indices),
+        }
+    }
+}
+
+// Triangle list from a strip, alternating the winding back so every triangle keeps the
+// orientation of the first one. Degenerate triangles used to stitch strips are skipped
+#[must_use]
+pub fn triangle_strip_to_list(strip: &[u32]) -> Vec<u32> {
+    let triangles = strip.windows(3).enumerate().map(|(i, window)| {
+        if i % 2 == 0 {
+            [window[0], window[1], window[2]]
+        } else {
+            [window[1], window[0], window[2]]
+        }
+    });
+    remove_degenerate_triangles(&triangles.flatten().collect::<Vec<_>>())
+}
+
+#[must_use]
+pub fn triangle_fan_to_list(fan: &[u32]) -> Vec<u32> {
+    let Some((&center, rest)) = fan.split_first() else {
+        return vec![];
+    };
+    let triangles = rest
+        .windows(2)
+        .flat_map(|window| [center, window[0], window[1]]);
+    remove_degenerate_triangles(&triangles.collect::<Vec<_>>())
+}
+
+// Average number of vertex shader invocations per triangle with a FIFO cache of `cache_size`,
+// between 0.5 for an ideal order and 3 for no reuse at all
+#[must_use]
+pub fn average_cache_miss_ratio(indices: &[u32], cache_size: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for &index in indices {
+        if !cache.contains(&index) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(index);
+        }
+    }
+    misses as f32 / triangle_count as f32
+}
+
+// Reorder triangles to reuse recently transformed vertices (Forsyth's linear-speed algorithm).
+// Fails when the indices are not a triangle list of `vertex_count` vertices
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Result<Vec<u32>, String> {
+    validate_indices(indices, vertex_count)?;
+    let triangle_count = indices.len() / 3;
+
+    // Triangles using each vertex, the first `valence` entries are the ones not emitted yet
+    let mut valence = vec![0u32; vertex_count];
+    for &index in indices {
+        valence[index as usize] += 1;
+    }
+    let mut offsets = Vec::with_capacity(vertex_count + 1);
+    offsets.push(0);
+    for &count in &valence {
+        offsets.push(offsets.last().copied().unwrap_or(0) + count as usize);
+    }
+    let mut adjacency = vec![0u32; indices.len()];
+    let mut filled = vec![0usize; vertex_count];
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        for &vertex in vertices {
+            let vertex = vertex as usize;
+            adjacency[offsets[vertex] + filled[vertex]] = triangle as u32;
+            filled[vertex] += 1;
+        }
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = valence
+        .iter()
+        .map(|&valence| vertex_score(None, valence))
+        .collect();
+    let mut triangle_scores: Vec<f32> = indices
+        .chunks_exact(3)
+        .map(|t| t.iter().map(|&v| vertex_scores[v as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0;
+
+    for _ in 0..triangle_count {
+        // Best triangle touching the cache, or the next one in the original order
+        let best = cache
+            .iter()
+            .flat_map(|&v| {
+                let v = v as usize;
+                &adjacency[offsets[v]..offsets[v] + valence[v] as usize]
+            })
+            .copied()
+            .max_by(|&a, &b| triangle_scores[a as usize].total_cmp(&triangle_scores[b as usize]))
+            .map(|triangle| triangle as usize)
+            .unwrap_or_else(|| {
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            });
+        emitted[best] = true;
+        let triangle = &indices[best * 3..best * 3 + 3];
+        output.extend_from_slice(triangle);
+
+        for &vertex in triangle {
+            let v = vertex as usize;
+            let live = &mut adjacency[offsets[v]..offsets[v] + valence[v] as usize];
+            if let Some(slot) = live.iter().position(|&t| t as usize == best) {
+                live.swap(slot, live.len() - 1);
+            }
+            valence[v] -= 1;
+            cache.retain(|&cached| cached != vertex);
+        }
+        for &vertex in triangle.iter().rev() {
+            cache.insert(0, vertex);
+        }
+
+        // Vertices pushed out of the cache still need their score lowered
+        let evicted = cache.split_off(cache.len().min(CACHE_SIZE));
+        for &vertex in &evicted {
+            cache_position[vertex as usize] = None;
+        }
+        for (position, &vertex) in cache.iter().enumerate() {
+            cache_position[vertex as usize] = Some(position);
+        }
+        for &vertex in cache.iter().chain(&evicted) {
+            let v = vertex as usize;
+            let score = vertex_score(cache_position[v], valence[v]);
+            let delta = score - vertex_scores[v];
+            vertex_scores[v] = score;
+            for &t in &adjacency[offsets[v]..offsets[v] + valence[v] as usize] {
+                triangle_scores[t as usize] += delta;
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn vertex_score(cache_position: Option<usize>, valence: u32) -> f32 {
+    if valence == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // Vertices of the last triangle get a fixed score so it is not reused straight away
+        Some(position) if position < 3 => 0.75,
+        Some(position) => {
+            let scale = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(1.5)
+        }
+    };
+    // Favour vertices with few triangles left so they leave the working set early
+    cache_score + 2.0 / (valence as f32).sqrt()
+}
+
+// Reorder clusters of a cache-optimized index list so triangles facing outwards are drawn
+// first, which lets early depth testing reject more of the hidden ones. Clusters are split
+// where the cache order restarts, so the vertex cache efficiency is mostly preserved
+#[must_use]
+pub fn optimize_overdraw(indices: &[u32], positions: &[Vec3]) -> Vec<u32> {
+    let triangles: Vec<&[u32]> = indices.chunks_exact(3).collect();
+    if triangles.is_empty() {
+        return vec![];
+    }
+
+    // A triangle with all vertices missing the cache starts a new cluster
+    let mut clusters = vec![0];
+    let mut cache = std::collections::VecDeque::with_capacity(CACHE_SIZE);
+    for (index, triangle) in triangles.iter().enumerate() {
+        let mut misses = 0;
+        for &vertex in *triangle {
+            if !cache.contains(&vertex) {
+                misses += 1;
+                if cache.len() == CACHE_SIZE {
+                    cache.pop_front();
+                }
+                cache.push_back(vertex);
+            }
+        }
+        if misses == 3 && index > 0 {
+            clusters.push(index);
+        }
+    }
+    clusters.push(triangles.len());
+
+    let mesh_center = BoundingBox::from_points(positions).center();
+    let mut sorted: Vec<(f32, &[&[u32]])> = clusters
+        .windows(2)
+        .map(|range| {
+            let cluster = &triangles[range[0]..range[1]];
+            let mut area_normal = Vec3::ZERO;
+            let mut centroid = Vec3::ZERO;
+            let mut area = 0.0;
+            for triangle in cluster {
+                let [a, b, c] = triangle_corners(positions, triangle);
+                let normal = (b - a).cross(c - a);
+                let triangle_area = normal.length();
+                area_normal += normal;
+                centroid += (a + b + c) / 3.0 * triangle_area;
+                area += triangle_area;
+            }
+            let centroid = if area > 0.0 {
+                centroid / area
+            } else {
+                mesh_center
+            };
+            let key = (centroid - mesh_center).dot(area_normal.normalize_or_zero());
+            (key, cluster)
+        })
+        .collect();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    sorted
+        .into_iter()
+        .flat_map(|(_, cluster)| cluster.iter().flat_map(|triangle| triangle.iter()))
+        .copied()
+        .collect()
+}
+
+// Axis aligned bounding box, empty boxes have min > max
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl BoundingBox {
+    pub const EMPTY: Self = Self {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    #[must_use]
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    #[must_use]
+    pub fn from_points(points: &[Vec3]) -> Self {
+        points
+            .iter()
+            .fold(Self::EMPTY, |bounds, &point| bounds.expanded(point))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    #[must_use]
+    pub fn center(&self) -> Vec3 {
+        if self.is_empty() {
+            Vec3::ZERO
+        } else {
+            (self.min + self.max) * 0.5
+        }
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Vec3 {
+        (self.max - self.min).max(Vec3::ZERO)
+    }
+
+    #[must_use]
+    pub fn expanded(&self, point: Vec3) -> Self {
+        Self::new(self.min.min(point), self.max.max(point))
+    }
+
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    #[must_use]
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    // Distance along the ray to the first hit with the box, 0 when the origin is inside.
+    // `direction` does not need to be normalized, the distance is then in multiples of it
+    #[must_use]
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        // Slab test, divisions by zero give infinities that compare correctly
+        let inverse = direction.recip();
+        let t0 = (self.min - origin) * inverse;
+        let t1 = (self.max - origin) * inverse;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element();
+        (near <= far && !self.is_empty()).then_some(near)
+    }
+
+    // Box around this one after the transform, larger than the exact bounds when rotated
+    #[must_use]
+    pub fn transformed(&self, transform: &Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = transform.transform_point3(self.center());
+        let half_size = self.size() * 0.5;
+        let extents = Vec3::new(
+            transform.row(0).truncate().abs().dot(half_size),
+            transform.row(1).truncate().abs().dot(half_size),
+            transform.row(2).truncate().abs().dot(half_size),
+        );
+        Self::new(center - extents, center + extents)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    #[must_use]
+    pub const fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    // Ritter's approximation, at most a few percent larger than the minimal sphere
+    #[must_use]
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let Some(&first) = points.first() else {
+            return Self::default();
+        };
+        let farthest_from = |origin: Vec3| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| {
+                    a.distance_squared(origin)
+                        .total_cmp(&b.distance_squared(origin))
+                })
+                .unwrap_or(origin)
+        };
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+        let mut sphere = Self::new((a + b) * 0.5, a.distance(b) * 0.5);
+        for &point in points {
+            let distance = point.distance(sphere.center);
+            if distance > sphere.radius {
+                // Grow just enough to touch the point, keeping the opposite side in place
+                let radius = (sphere.radius + distance) * 0.5;
+                sphere.center += (point - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        sphere
+    }
+
+    #[must_use]
+    pub fn from_box(bounds: &BoundingBox) -> Self {
+        Self::new(bounds.center(), bounds.size().length() * 0.5)
+    }
+
+    #[must_use]
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    // The radius grows with the largest scale of the transform
+    #[must_use]
+    pub fn transformed(&self, transform: &Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .truncate()
+            .length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+        Self::new(transform.transform_point3(self.center), self.radius * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Triangles of a `size` x `size` grid of quads on the XY plane
+    fn grid(size: u32) -> Vec<u32> {
+        let index = |x: u32, y: u32| y * (size + 1) + x;
+        (0..size * size)
+            .flat_map(|quad| {
+                let (x, y) = (quad % size, quad / size);
+                let corners = [index(x, y), index(x + 1, y), index(x + 1, y + 1)];
+                let other = [index(x, y), index(x + 1, y + 1), index(x, y + 1)];
+                corners.into_iter().chain(other)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tangents_follow_the_uvs() {
+        // The second triangle mirrors U, so the vertices it shares with the first are split
+        let vertices = [
+            (Vec3::ZERO, Vec2::ZERO),
+            (Vec3::X, Vec2::X),
+            (Vec3::Y, Vec2::Y),
+            (Vec3::NEG_X, Vec2::X),
+        ];
+        let indices = [0, 1, 2, 0, 2, 3];
+        let (split, tangents, remapped) =
+            generate_tangents(&vertices, &indices, |&(position, uv)| {
+                (position, Vec3::Z, uv)
+            })
+            .unwrap();
+        assert_eq!(split.len(), 6);
+        assert_eq!(tangents.len(), split.len());
+        for (corner, &index) in remapped.iter().enumerate() {
+            assert_eq!(split[index as usize], vertices[indices[corner] as usize]);
+            let expected = if corner < 3 {
+                Vec4::new(1.0, 0.0, 0.0, 1.0)
+            } else {
+                Vec4::new(-1.0, 0.0, 0.0, -1.0)
+            };
+            assert!(tangents[index as usize].abs_diff_eq(expected, 1e-5));
+        }
+
+        let positions = vertices.map(|(position, _)| position);
+        let uvs = vertices.map(|(_, uv)| uv);
+        let tangents =
+            generate_angle_weighted_tangents(&positions, &[Vec3::Z; 4], &uvs, &indices[..3]);
+        assert!(tangents[1].abs_diff_eq(Vec4::new(1.0, 0.0, 0.0, 1.0), 1e-5));
+        assert!(generate_tangents(&vertices, &[0, 1, 4], |&(p, uv)| (p, Vec3::Z, uv)).is_err());
+    }
+
+    #[test]
+    fn validate_index_ranges() {
+        assert!(validate_indices(&[0, 1, 2], 3).is_ok());
+        assert!(validate_indices(&[0, 1, 3], 3).is_err());
+        assert!(validate_indices(&[0, 1], 3).is_err());
+    }
+
+    #[test]
+    fn vertex_cache_keeps_triangles() {
+        let size = 16;
+        let indices = grid(size);
+        let vertex_count = ((size + 1) * (size + 1)) as usize;
+        // Visit the triangles in a scattered order that defeats the cache
+        let triangles: Vec<&[u32]> = indices.chunks_exact(3).collect();
+        let scattered: Vec<u32> = (0..triangles.len())
+            .flat_map(|i| triangles[i * 97 % triangles.len()])
+            .copied()
+            .collect();
+        let optimized = optimize_vertex_cache(&scattered, vertex_count).unwrap();
+
+        let sorted = |indices: &[u32]| {
+            let mut triangles: Vec<Vec<u32>> =
+                indices.chunks_exact(3).map(<[u32]>::to_vec).collect();
+            triangles.sort();
+            triangles
+        };
+        assert_eq!(sorted(&optimized), sorted(&indices));
+        let before = average_cache_miss_ratio(&scattered, CACHE_SIZE);
+        let after = average_cache_miss_ratio(&optimized, CACHE_SIZE);
+        assert!(after < before && after < 1.0, "{before} -> {after}");
+
+        assert!(optimize_vertex_cache(&[0, 1, 2], 2).is_err());
+        assert!(optimize_vertex_cache(&[0, 1], 2).is_err());
+        assert_eq!(optimize_vertex_cache(&[], 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn bounding_volumes() {
+        let points = [
+            Vec3::new(-1.0, 2.0, 0.0),
+            Vec3::new(3.0, -2.0, 1.0),
+            Vec3::ONE,
+        ];
+        let bounds = BoundingBox::from_points(&points);
+        assert_eq!(bounds.min, Vec3::new(-1.0, -2.0, 0.0));
+        assert_eq!(bounds.max, Vec3::new(3.0, 2.0, 1.0));
+        assert!(BoundingBox::EMPTY.is_empty());
+        assert_eq!(BoundingBox::from_points(&[]), BoundingBox::EMPTY);
+
+        assert_eq!(
+            bounds.intersect_ray(Vec3::new(-5.0, 0.0, 0.5), Vec3::X),
+            Some(4.0)
+        );
+        assert_eq!(bounds.intersect_ray(Vec3::splat(0.5), Vec3::X), Some(0.0));
+        assert_eq!(
+            bounds.intersect_ray(Vec3::new(-5.0, 5.0, 0.5), Vec3::X),
+            None
+        );
+
+        let moved = bounds.transformed(&Mat4::from_translation(Vec3::Z));
+        assert_eq!(
+            moved,
+            BoundingBox::new(bounds.min + Vec3::Z, bounds.max + Vec3::Z)
+        );
+        let rotated = bounds.transformed(&Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        assert!(rotated.size().abs_diff_eq(Vec3::new(4.0, 4.0, 1.0), 1e-5));
+
+        let sphere = BoundingSphere::from_points(&points);
+        for point in points {
+            assert!(sphere.radius + 1e-5 >= point.distance(sphere.center));
+        }
+        let scaled = sphere.transformed(&Mat4::from_scale(Vec3::new(1.0, 3.0, 1.0)));
+        assert!((scaled.radius - sphere.radius * 3.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,639 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    ops::Add,
+};
+
+use glam::{DVec3, Vec3};
+
+use super::{
+    drawcall::{DrawCall, Primitive},
+    mesh::Mesh,
+    processing::{validate_indices, IndexData},
+    vertex_layout::Vertex,
+};
+
+// Minimum cosine between a triangle normal before and after a collapse, to reject fold-overs
+const MIN_NORMAL_COSINE: f64 = 0.25;
+// Weight of the planes perpendicular to border edges that keep borders in place
+const BORDER_WEIGHT: f64 = 10.0;
+
+// Index buffer of one level of detail, `error` is the geometric deviation from the original
+// mesh in object space units
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    pub error: f32,
+}
+
+// Edge-collapse simplification driven by quadric error metrics (Garland & Heckbert). Vertices are
+// only ever collapsed onto existing ones, so every level reuses the original vertex buffer
+#[derive(Clone, Debug)]
+pub struct Simplifier<'a> {
+    positions: &'a [Vec3],
+    max_error: f32,
+    lock_border: bool,
+}
+
+impl<'a> Simplifier<'a> {
+    #[must_use]
+    pub const fn new(positions: &'a [Vec3]) -> Self {
+        Self {
+            positions,
+            max_error: f32::INFINITY,
+            lock_border: false,
+        }
+    }
+
+    // Stop before the error goes over this distance, even if the target was not reached
+    #[must_use]
+    pub const fn with_max_error(mut self, max_error: f32) -> Self {
+        self.max_error = max_error;
+        self
+    }
+
+    // Keep border vertices in place, so meshes split in chunks still line up
+    #[must_use]
+    pub const fn with_locked_border(mut self, lock_border: bool) -> Self {
+        self.lock_border = lock_border;
+        self
+    }
+
+    // Simplify a triangle list down to `target_index_count` indices or the maximum error. Fails
+    // when the indices are not a triangle list of the positions
+    pub fn simplify(&self, indices: &[u32], target_index_count: usize) -> Result<Lod, String> {
+        validate_indices(indices, self.positions.len())?;
+        let mut state = State::new(self, indices);
+        let mut error: f64 = 0.0;
+        let max_error = f64::from(self.max_error) * f64::from(self.max_error);
+        while state.index_count > target_index_count {
+            let Some(collapse) = state.heap.pop() else {
+                break;
+            };
+            if !state.is_current(&collapse) {
+                continue;
+            }
+            if collapse.cost > max_error {
+                break;
+            }
+            if state.can_collapse(collapse.from, collapse.to) {
+                state.collapse(collapse.from, collapse.to);
+                error = error.max(collapse.cost);
+            }
+        }
+        Ok(Lod {
+            indices: state.indices(),
+            error: error.sqrt() as f32,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VertexKind {
+    Manifold,
+    // On an edge used by a single triangle, it can only move along the border
+    Border,
+    // Attribute seams and non-manifold vertices never move
+    Locked,
+}
+
+// Symmetric 4x4 matrix summing squared distances to planes, weighted by area
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric {
+    xx: f64,
+    xy: f64,
+    xz: f64,
+    xw: f64,
+    yy: f64,
+    yz: f64,
+    yw: f64,
+    zz: f64,
+    zw: f64,
+    ww: f64,
+    weight: f64,
+}
+
+impl Quadric {
+    fn from_plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(point);
+        Self {
+            xx: a * a * weight,
+            xy: a * b * weight,
+            xz: a * c * weight,
+            xw: a * d * weight,
+            yy: b * b * weight,
+            yz: b * c * weight,
+            yw: b * d * weight,
+            zz: c * c * weight,
+            zw: c * d * weight,
+            ww: d * d * weight,
+            weight,
+        }
+    }
+
+    // Weighted mean of the squared distances from `point` to the planes
+    fn error(&self, point: DVec3) -> f64 {
+        let DVec3 { x, y, z } = point;
+        let sum = self.xx * x * x
+            + self.yy * y * y
+            + self.zz * z * z
+            + 2.0 * (self.xy * x * y + self.xz * x * z + self.yz * y * z)
+            + 2.0 * (self.xw * x + self.yw * y + self.zw * z)
+            + self.ww;
+        (sum / self.weight.max(f64::EPSILON)).max(0.0)
+    }
+}
+
+impl Add for Quadric {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            xx: self.xx + other.xx,
+            xy: self.xy + other.xy,
+            xz: self.xz + other.xz,
+            xw: self.xw + other.xw,
+            yy: self.yy + other.yy,
+            yz: self.yz + other.yz,
+            yw: self.yw + other.yw,
+            zz: self.zz + other.zz,
+            zw: self.zw + other.zw,
+            ww: self.ww + other.ww,
+            weight: self.weight + other.weight,
+        }
+    }
+}
+
+// Candidate collapse of `from` onto `to`, stale once either vertex changed
+#[derive(Clone, Copy, Debug)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+// Ordered so the heap pops the cheapest collapse first
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+const fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+struct State {
+    positions: Vec<DVec3>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    vertex_triangles: Vec<Vec<usize>>,
+    kinds: Vec<VertexKind>,
+    quadrics: Vec<Quadric>,
+    border_edges: HashSet<(u32, u32)>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+    index_count: usize,
+}
+
+impl State {
+    fn new(simplifier: &Simplifier, indices: &[u32]) -> Self {
+        let positions: Vec<DVec3> = simplifier.positions.iter().map(|p| p.as_dvec3()).collect();
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+            .collect();
+        let mut vertex_triangles = vec![vec![]; positions.len()];
+        let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            for corner in 0..3 {
+                vertex_triangles[triangle[corner] as usize].push(index);
+                let key = edge_key(triangle[corner], triangle[(corner + 1) % 3]);
+                *edge_counts.entry(key).or_default() += 1;
+            }
+        }
+
+        let mut kinds = vec![VertexKind::Manifold; positions.len()];
+        let mut border_edges = HashSet::new();
+        for (&(a, b), &count) in &edge_counts {
+            let kind = match count {
+                1 if simplifier.lock_border => VertexKind::Locked,
+                1 => {
+                    border_edges.insert((a, b));
+                    VertexKind::Border
+                }
+                2 => continue,
+                _ => VertexKind::Locked,
+            };
+            for vertex in [a, b] {
+                if kinds[vertex as usize] != VertexKind::Locked {
+                    kinds[vertex as usize] = kind;
+                }
+            }
+        }
+        // Vertices sharing a position with another one are on a UV or normal seam, moving
+        // them would tear the surface apart
+        let mut position_counts: HashMap<[u64; 3], u32> = HashMap::new();
+        for position in &positions {
+            *position_counts
+                .entry(position.to_array().map(f64::to_bits))
+                .or_default() += 1;
+        }
+        for (kind, position) in kinds.iter_mut().zip(&positions) {
+            if position_counts[&position.to_array().map(f64::to_bits)] > 1 {
+                *kind = VertexKind::Locked;
+            }
+        }
+
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        for triangle in &triangles {
+            let [a, b, c] = triangle.map(|vertex| positions[vertex as usize]);
+            let normal = (b - a).cross(c - a);
+            let area = normal.length() * 0.5;
+            let normal = normal.normalize_or_zero();
+            let quadric = Quadric::from_plane(normal, a, area);
+            for &vertex in triangle {
+                quadrics[vertex as usize] = quadrics[vertex as usize] + quadric;
+            }
+            for corner in 0..3 {
+                let (start, end) = (triangle[corner], triangle[(corner + 1) % 3]);
+                if border_edges.contains(&edge_key(start, end)) {
+                    let (p, q) = (positions[start as usize], positions[end as usize]);
+                    let edge = q - p;
+                    let plane = edge.cross(normal).normalize_or_zero();
+                    let quadric =
+                        Quadric::from_plane(plane, p, edge.length_squared() * BORDER_WEIGHT);
+                    for vertex in [start, end] {
+                        quadrics[vertex as usize] = quadrics[vertex as usize] + quadric;
+                    }
+                }
+            }
+        }
+
+        let mut state = Self {
+            alive: vec![true; triangles.len()],
+            index_count: triangles.len() * 3,
+            versions: vec![0; positions.len()],
+            heap: BinaryHeap::new(),
+            positions,
+            triangles,
+            vertex_triangles,
+            kinds,
+            quadrics,
+            border_edges,
+        };
+        // Sorted so ties between equal costs always resolve the same way
+        let mut edges: Vec<(u32, u32)> = edge_counts.into_keys().collect();
+        edges.sort_unstable();
+        for (a, b) in edges {
+            state.push_collapse(a, b);
+            state.push_collapse(b, a);
+        }
+        state
+    }
+
+    fn push_collapse(&mut self, from: u32, to: u32) {
+        if self.kinds[from as usize] == VertexKind::Locked {
+            return;
+        }
+        let quadric = self.quadrics[from as usize] + self.quadrics[to as usize];
+        self.heap.push(Collapse {
+            cost: quadric.error(self.positions[to as usize]),
+            from,
+            to,
+            from_version: self.versions[from as usize],
+            to_version: self.versions[to as usize],
+        });
+    }
+
+    fn is_current(&self, collapse: &Collapse) -> bool {
+        self.versions[collapse.from as usize] == collapse.from_version
+            && self.versions[collapse.to as usize] == collapse.to_version
+    }
+
+    fn neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self.vertex_triangles[vertex as usize]
+            .iter()
+            .filter(|&&t| self.alive[t])
+            .flat_map(|&t| self.triangles[t])
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        if self.kinds[from as usize] == VertexKind::Border
+            && !self.border_edges.contains(&edge_key(from, to))
+        {
+            return false;
+        }
+
+        // Link condition: the only common neighbours are the opposite corners of the shared
+        // triangles, otherwise the collapse pinches the surface
+        let shared_triangles = self.vertex_triangles[from as usize]
+            .iter()
+            .filter(|&&t| self.alive[t] && self.triangles[t].contains(&to))
+            .count();
+        let to_neighbors = self.neighbors(to);
+        let common = self
+            .neighbors(from)
+            .into_iter()
+            .filter(|vertex| to_neighbors.binary_search(vertex).is_ok())
+            .count();
+        if shared_triangles == 0 || common > shared_triangles {
+            return false;
+        }
+
+        // Reject collapses that flip or squash the remaining triangles
+        let target = self.positions[to as usize];
+        self.vertex_triangles[from as usize]
+            .iter()
+            .filter(|&&t| self.alive[t] && !self.triangles[t].contains(&to))
+            .all(|&t| {
+                let corners = self.triangles[t].map(|vertex| self.positions[vertex as usize]);
+                let moved = self.triangles[t].map(|vertex| {
+                    if vertex == from {
+                        target
+                    } else {
+                        self.positions[vertex as usize]
+                    }
+                });
+                let before = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                match (before.try_normalize(), after.try_normalize()) {
+                    (Some(before), Some(after)) => before.dot(after) >= MIN_NORMAL_COSINE,
+                    _ => false,
+                }
+            })
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        for neighbor in self.neighbors(from) {
+            if self.border_edges.remove(&edge_key(from, neighbor)) && neighbor != to {
+                self.border_edges.insert(edge_key(to, neighbor));
+            }
+        }
+        for t in std::mem::take(&mut self.vertex_triangles[from as usize]) {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&to) {
+                self.alive[t] = false;
+                self.index_count -= 3;
+            } else {
+                for vertex in &mut self.triangles[t] {
+                    if *vertex == from {
+                        *vertex = to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+        let alive = &self.alive;
+        self.vertex_triangles[to as usize].retain(|&t| alive[t]);
+        self.quadrics[to as usize] = self.quadrics[to as usize] + self.quadrics[from as usize];
+        self.versions[from as usize] += 1;
+        self.versions[to as usize] += 1;
+        for neighbor in self.neighbors(to) {
+            self.push_collapse(to, neighbor);
+            self.push_collapse(neighbor, to);
+        }
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.triangles
+            .iter()
+            .zip(&self.alive)
+            .filter(|(_, alive)| **alive)
+            .flat_map(|(triangle, _)| *triangle)
+            .collect()
+    }
+}
+
+// Levels of detail from the original mesh down, errors never decrease along the chain
+#[derive(Clone, Debug, Default)]
+pub struct LodChain {
+    lods: Vec<Lod>,
+}
+
+impl LodChain {
+    // Level 0 is `indices` itself, each following level targets `reduction` times the indices of
+    // the previous one. The chain ends early once the simplifier can not go any further
+    pub fn generate(
+        simplifier: &Simplifier,
+        indices: &[u32],
+        level_count: usize,
+        reduction: f32,
+    ) -> Result<Self, String> {
+        let mut lods = vec![Lod {
+            indices: indices.to_vec(),
+            error: 0.0,
+        }];
+        let mut target = indices.len();
+        for _ in 1..level_count {
+            target = (target as f32 * reduction) as usize / 3 * 3;
+            let lod = simplifier.simplify(indices, target)?;
+            let Some(previous) = lods.last() else {
+                break;
+            };
+            if lod.indices.len() >= previous.indices.len() {
+                break;
+            }
+            let error = lod.error.max(previous.error);
+            lods.push(Lod { error, ..lod });
+        }
+        Ok(Self { lods })
+    }
+
+    #[must_use]
+    pub fn lods(&self) -> &[Lod] {
+        &self.lods
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.lods.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.lods.is_empty()
+    }
+
+    // Size in pixels of the error of `lod` seen at `distance` through a perspective projection
+    // with vertical field of view `fov_y` covering `viewport_height` pixels. None when there is
+    // no such level
+    #[must_use]
+    pub fn screen_space_error(
+        &self,
+        lod: usize,
+        distance: f32,
+        fov_y: f32,
+        viewport_height: f32,
+    ) -> Option<f32> {
+        let pixels_per_unit = viewport_height / (2.0 * (fov_y * 0.5).tan());
+        let error = self.lods.get(lod)?.error;
+        Some(error * pixels_per_unit / distance.max(f32::EPSILON))
+    }
+
+    // Coarsest level whose screen space error stays within `max_pixel_error`
+    #[must_use]
+    pub fn select(
+        &self,
+        distance: f32,
+        fov_y: f32,
+        viewport_height: f32,
+        max_pixel_error: f32,
+    ) -> usize {
+        (0..self.lods.len())
+            .rev()
+            .find(|&lod| {
+                self.screen_space_error(lod, distance, fov_y, viewport_height)
+                    .is_some_and(|error| error <= max_pixel_error)
+            })
+            .unwrap_or(0)
+    }
+
+    // Mesh with a single VBO and all the levels in one EBO, submesh n draws level n
+    #[must_use]
+    pub fn to_mesh<V: Vertex>(&self, vertices: &[V]) -> Mesh {
+        let indices: Vec<u32> = self
+            .lods
+            .iter()
+            .flat_map(|lod| lod.indices.iter().copied())
+            .collect();
+        let indices = IndexData::narrow(&indices);
+        let mut mesh = Mesh::new();
+        let vbo = mesh.add_vertex_data(vertices);
+        let ebo = indices.add_to(&mut mesh);
+        let vao = mesh.add_vertex_array_from_layout(&V::layout(), vbo, Some(ebo));
+        let index_size = indices.data_type().get_size() as usize;
+        let mut first = 0;
+        for lod in &self.lods {
+            let draw_call = DrawCall::count(
+                DrawCall::first(
+                    DrawCall::index_type(DrawCall::new(Primitive::Triangles), indices.data_type()),
+                    (first * index_size) as i32,
+                ),
+                lod.indices.len() as i32,
+            );
+            mesh.add_submesh(vao, draw_call, 0);
+            first += lod.indices.len();
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::primitives;
+
+    #[test]
+    fn flat_grid_simplifies_without_error() {
+        let grid = primitives::grid(8, 8);
+        let positions: Vec<Vec3> = grid.vertices.iter().map(|vertex| vertex.position).collect();
+        let lod = Simplifier::new(&positions)
+            .simplify(&grid.indices, 24)
+            .unwrap();
+        assert!(lod.indices.len() <= 24, "{}", lod.indices.len());
+        assert!(lod.indices.len().is_multiple_of(3));
+        assert!(lod.error < 1e-4);
+        assert!(lod
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < positions.len()));
+    }
+
+    #[test]
+    fn max_error_stops_simplification() {
+        // Grid folded into a ridge, flattening the fold costs its height
+        let grid = primitives::grid(8, 8);
+        let positions: Vec<Vec3> = grid
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let position = vertex.position;
+                position + Vec3::Z * (0.5 - position.x.abs())
+            })
+            .collect();
+        let simplifier = Simplifier::new(&positions).with_max_error(1e-3);
+        let lod = simplifier.simplify(&grid.indices, 0).unwrap();
+        // Each side of the ridge needs at least two triangles
+        assert!(lod.indices.len() >= 12 && lod.indices.len() < grid.indices.len());
+        assert!(lod.error <= 1e-3);
+    }
+
+    #[test]
+    fn locked_border_keeps_border_vertices() {
+        let grid = primitives::grid(8, 8);
+        let positions: Vec<Vec3> = grid.vertices.iter().map(|vertex| vertex.position).collect();
+        let (min, max) = positions
+            .iter()
+            .fold((Vec3::MAX, Vec3::MIN), |(min, max), p| {
+                (min.min(*p), max.max(*p))
+            });
+        let on_border = |p: Vec3| p.x == min.x || p.x == max.x || p.y == min.y || p.y == max.y;
+        let lod = Simplifier::new(&positions)
+            .with_locked_border(true)
+            .simplify(&grid.indices, 0)
+            .unwrap();
+        let border_count = positions.iter().filter(|&&p| on_border(p)).count();
+        let kept = lod.indices.iter().collect::<HashSet<_>>();
+        let kept_border = kept
+            .iter()
+            .filter(|&&&index| on_border(positions[index as usize]))
+            .count();
+        assert_eq!(kept_border, border_count);
+    }
+
+    #[test]
+    fn reject_invalid_indices() {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let simplifier = Simplifier::new(&positions);
+        assert!(simplifier.simplify(&[0, 1, 3], 0).is_err());
+        assert!(simplifier.simplify(&[0, 1], 0).is_err());
+        assert!(LodChain::generate(&simplifier, &[0, 1, 3], 3, 0.5).is_err());
+    }
+
+    #[test]
+    fn lod_chain_selection() {
+        let grid = primitives::grid(8, 8);
+        let positions: Vec<Vec3> = grid.vertices.iter().map(|vertex| vertex.position).collect();
+        let chain =
+            LodChain::generate(&Simplifier::new(&positions), &grid.indices, 3, 0.5).unwrap();
+        assert!(chain.len() > 1);
+        assert_eq!(chain.lods()[0].indices, grid.indices);
+        assert_eq!(chain.screen_space_error(0, 1.0, 1.0, 1080.0), Some(0.0));
+        assert_eq!(
+            chain.screen_space_error(chain.len(), 1.0, 1.0, 1080.0),
+            None
+        );
+        // The grid is flat, so every level is exact and the coarsest one is picked
+        assert_eq!(chain.select(10.0, 1.0, 1080.0, 0.5), chain.len() - 1);
+    }
+}
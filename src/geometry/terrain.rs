@@ -0,0 +1,298 @@
+use glam::{UVec2, Vec2, Vec3, Vec4};
+use noise::NoiseFn;
+
+use crate::core::color::{gradient::Gradient, Color};
+
+use super::{primitives::MeshData, vertex_layout::Vertex};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Vertex)]
+#[repr(C)]
+pub struct TerrainVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub tangent: Vec4,
+    pub color: Color,
+}
+
+// Builds heightmap terrains on the XY plane with heights along +Z, sampled from any 2D noise.
+// Heights, normals and tangents only depend on the world position, so chunks of the same
+// terrain built at different levels of detail line up and shade the same at their borders
+#[derive(Clone, Debug)]
+pub struct TerrainBuilder<N> {
+    noise: N,
+    resolution: UVec2,
+    size: Vec2,
+    height_scale: f32,
+    noise_scale: f32,
+    gradient: Gradient,
+    skirt_depth: f32,
+}
+
+// Normal of the surface z = height(x, y) from its partial derivatives
+fn normal_from_slope(slope: Vec2) -> Vec3 {
+    Vec3::new(-slope.x, -slope.y, 1.0).normalize()
+}
+
+impl<N: NoiseFn<f64, 2>> TerrainBuilder<N> {
+    #[must_use]
+    pub fn new(noise: N) -> Self {
+        Self {
+            noise,
+            resolution: UVec2::splat(256),
+            size: Vec2::ONE,
+            height_scale: 1.0,
+            noise_scale: 1.0,
+            gradient: Gradient::default(),
+            skirt_depth: 0.0,
+        }
+    }
+
+    // Number of quads along X and Y for the whole terrain at the highest level of detail
+    #[must_use]
+    pub fn with_resolution(mut self, resolution: UVec2) -> Self {
+        self.resolution = resolution.max(UVec2::ONE);
+        self
+    }
+
+    // World size on XY, the terrain is centered at the origin
+    #[must_use]
+    pub const fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    // Multiplier applied to the noise value to get the height
+    #[must_use]
+    pub const fn with_height_scale(mut self, height_scale: f32) -> Self {
+        self.height_scale = height_scale;
+        self
+    }
+
+    // Multiplier applied to world coordinates before sampling the noise
+    #[must_use]
+    pub const fn with_noise_scale(mut self, noise_scale: f32) -> Self {
+        self.noise_scale = noise_scale;
+        self
+    }
+
+    // Vertex colors sampled at each height, such as `Gradient::terrain()`
+    #[must_use]
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    // Add vertical skirts of this depth around each chunk to hide cracks between LODs
+    #[must_use]
+    pub const fn with_skirt(mut self, depth: f32) -> Self {
+        self.skirt_depth = depth;
+        self
+    }
+
+    #[must_use]
+    pub fn height_at(&self, position: Vec2) -> f32 {
+        let point = position.as_dvec2() * f64::from(self.noise_scale);
+        self.noise.get(point.to_array()) as f32 * self.height_scale
+    }
+
+    // Smooth normal from central differences of the height, one cell apart
+    #[must_use]
+    pub fn normal_at(&self, position: Vec2) -> Vec3 {
+        normal_from_slope(self.slope_at(position))
+    }
+
+    #[must_use]
+    pub fn build(&self) -> MeshData<TerrainVertex> {
+        self.build_chunk(UVec2::ZERO, UVec2::ONE, 0)
+    }
+
+    // Build the `chunk` of a terrain split in `chunk_count` chunks, `lod` halves the resolution
+    // each level. The resolution must be divisible by chunk_count * 2^lod
+    #[must_use]
+    pub fn build_chunk(
+        &self,
+        chunk: UVec2,
+        chunk_count: UVec2,
+        lod: u32,
+    ) -> MeshData<TerrainVertex> {
+        let step = 1 << lod;
+        let chunk_cells = self.resolution / chunk_count;
+        assert!(
+            chunk.cmplt(chunk_count).all(),
+            "Chunk {chunk} out of {chunk_count}"
+        );
+        assert!(
+            chunk_cells * chunk_count == self.resolution
+                && (chunk_cells % step).cmpeq(UVec2::ZERO).all(),
+            "Resolution {} is not divisible in {chunk_count} chunks at LOD {lod}",
+            self.resolution
+        );
+        let quads = chunk_cells / step;
+        let first_cell = chunk * chunk_cells;
+
+        let mut data = MeshData::default();
+        for j in 0..=quads.y {
+            for i in 0..=quads.x {
+                let cell = first_cell + UVec2::new(i, j) * step;
+                data.vertices.push(self.vertex_at(cell));
+            }
+        }
+        let row_length = quads.x + 1;
+        for j in 0..quads.y {
+            for i in 0..quads.x {
+                let bottom_left = j * row_length + i;
+                let bottom_right = bottom_left + 1;
+                let top_left = bottom_left + row_length;
+                let top_right = top_left + 1;
+                data.indices
+                    .extend_from_slice(&[bottom_left, bottom_right, top_right]);
+                data.indices
+                    .extend_from_slice(&[bottom_left, top_right, top_left]);
+            }
+        }
+        if self.skirt_depth > 0.0 {
+            self.add_skirt(&mut data, quads);
+        }
+        data
+    }
+
+    fn cell_size(&self) -> Vec2 {
+        self.size / self.resolution.as_vec2()
+    }
+
+    fn slope_at(&self, position: Vec2) -> Vec2 {
+        let delta = self.cell_size();
+        let dx = Vec2::new(delta.x, 0.0);
+        let dy = Vec2::new(0.0, delta.y);
+        Vec2::new(
+            (self.height_at(position + dx) - self.height_at(position - dx)) / (2.0 * delta.x),
+            (self.height_at(position + dy) - self.height_at(position - dy)) / (2.0 * delta.y),
+        )
+    }
+
+    fn vertex_at(&self, cell: UVec2) -> TerrainVertex {
+        let uv = cell.as_vec2() / self.resolution.as_vec2();
+        let xy = (uv - 0.5) * self.size;
+        let height = self.height_at(xy);
+        let slope = self.slope_at(xy);
+        TerrainVertex {
+            position: xy.extend(height),
+            normal: normal_from_slope(slope),
+            uv,
+            // Along +X following the surface, the bitangent then follows +Y
+            tangent: Vec3::new(1.0, 0.0, slope.x).normalize().extend(1.0),
+            color: self.gradient.sample(height),
+        }
+    }
+
+    // Duplicate the border vertices lowered by the skirt depth and connect them to the border
+    fn add_skirt(&self, data: &mut MeshData<TerrainVertex>, quads: UVec2) {
+        let row_length = quads.x + 1;
+        let index = |i: u32, j: u32| j * row_length + i;
+
+        // Border walked counter-clockwise seen from above, so the skirt faces outwards
+        let mut border = vec![];
+        border.extend((0..quads.x).map(|i| index(i, 0)));
+        border.extend((0..quads.y).map(|j| index(quads.x, j)));
+        border.extend((1..=quads.x).rev().map(|i| index(i, quads.y)));
+        border.extend((1..=quads.y).rev().map(|j| index(0, j)));
+
+        let first_skirt = data.vertices.len() as u32;
+        for &top in &border {
+            let mut vertex = data.vertices[top as usize];
+            vertex.position.z -= self.skirt_depth;
+            data.vertices.push(vertex);
+        }
+        let count = border.len() as u32;
+        for k in 0..count {
+            let next = (k + 1) % count;
+            let (a, b) = (border[k as usize], border[next as usize]);
+            let (a_low, b_low) = (first_skirt + k, first_skirt + next);
+            data.indices.extend_from_slice(&[a, a_low, b_low]);
+            data.indices.extend_from_slice(&[a, b_low, b]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::Perlin;
+
+    use super::*;
+
+    // Plane with a constant slope, z = 0.5 x + 0.25 y
+    struct Slope;
+
+    impl NoiseFn<f64, 2> for Slope {
+        fn get(&self, point: [f64; 2]) -> f64 {
+            0.5 * point[0] + 0.25 * point[1]
+        }
+    }
+
+    fn perlin() -> TerrainBuilder<Perlin> {
+        TerrainBuilder::new(Perlin::new(7))
+            .with_resolution(UVec2::new(8, 4))
+            .with_size(Vec2::new(4.0, 2.0))
+            .with_noise_scale(1.3)
+    }
+
+    #[test]
+    fn normals_of_a_sloped_plane() {
+        let terrain = TerrainBuilder::new(Slope).with_resolution(UVec2::splat(4));
+        let expected = Vec3::new(-0.5, -0.25, 1.0).normalize();
+        assert!(terrain
+            .normal_at(Vec2::new(0.3, -0.2))
+            .abs_diff_eq(expected, 1e-5));
+        for vertex in terrain.build().vertices {
+            assert!(vertex.normal.abs_diff_eq(expected, 1e-5));
+            assert!(vertex.tangent.truncate().dot(vertex.normal).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn chunks_line_up_across_levels_of_detail() {
+        let terrain = perlin();
+        let left = terrain.build_chunk(UVec2::ZERO, UVec2::new(2, 1), 0);
+        let right = terrain.build_chunk(UVec2::X, UVec2::new(2, 1), 1);
+        // The right chunk has every other row, each must match the last column of the left one
+        let border = |data: &MeshData<TerrainVertex>, columns: usize, column: usize| {
+            data.vertices
+                .chunks_exact(columns + 1)
+                .map(|row| row[column])
+                .collect::<Vec<_>>()
+        };
+        let left_border = border(&left, 4, 4);
+        let right_border = border(&right, 2, 0);
+        assert_eq!(right_border.len(), 3);
+        for (j, vertex) in right_border.iter().enumerate() {
+            assert_eq!(*vertex, left_border[j * 2]);
+        }
+        let whole = terrain.build();
+        assert_eq!(whole.vertices.len(), 9 * 5);
+        assert_eq!(whole.vertices[4], left.vertices[4]);
+    }
+
+    #[test]
+    fn skirts_face_outwards() {
+        let terrain = perlin().with_height_scale(0.1).with_skirt(0.5);
+        let data = terrain.build_chunk(UVec2::ZERO, UVec2::ONE, 1);
+        let surface_indices = 4 * 2 * 6;
+        for (k, triangle) in data.indices.chunks_exact(3).enumerate() {
+            let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize].position);
+            let face = (b - a).cross(c - a);
+            if k * 3 < surface_indices {
+                assert!(face.z > 0.0);
+            } else {
+                let center = (a + b + c) / 3.0;
+                assert!(face.truncate().dot(center.truncate()) > 0.0, "{triangle:?}");
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "is not divisible"]
+    fn reject_indivisible_chunks() {
+        let _ = perlin().build_chunk(UVec2::ZERO, UVec2::new(2, 1), 3);
+    }
+}
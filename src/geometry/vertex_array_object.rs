@@ -1,13 +1,20 @@
 use std::{ffi::c_void, ptr::null_mut};
 
-use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use gl::types::{GLenum, GLint, GLintptr, GLsizei, GLuint};
 
 use crate::{
-    core::object::{Handle, NullHandle, Object},
+    core::{
+        capabilities::Capabilities,
+        object::{Handle, NullHandle, Object},
+        state,
+    },
     error::check_gl_error,
 };
 
-use super::vertex_attribute::VertexAttribute;
+use super::{
+    element_buffer_object::ElementBufferObject, vertex_attribute::VertexAttribute,
+    vertex_buffer_object::VertexBufferObject,
+};
 
 #[derive(Debug)]
 pub struct VertexArrayObject {
@@ -18,6 +25,7 @@ impl Drop for VertexArrayObject {
     fn drop(&mut self) {
         unsafe { gl::DeleteVertexArrays(1, &self.handle) };
         check_gl_error();
+        state::with(|state| state.forget_vertex_array(self.handle));
     }
 }
 
@@ -25,11 +33,168 @@ impl VertexArrayObject {
     #[must_use]
     pub fn new() -> Self {
         let mut handle = NullHandle;
-        unsafe { gl::GenVertexArrays(1, &mut handle) };
+        if state::direct_state_access() {
+            unsafe { gl::CreateVertexArrays(1, &mut handle) };
+        } else {
+            unsafe { gl::GenVertexArrays(1, &mut handle) };
+        }
         check_gl_error();
         Self { handle }
     }
 
+    // Same as `set_attribute` reading from `vbo` instead of the bound VBO. With Direct State
+    // Access nothing is bound, otherwise the VAO and VBO are left bound
+    pub fn set_attribute_buffer(
+        &self,
+        location: GLuint,
+        attribute: &VertexAttribute,
+        vbo: &VertexBufferObject,
+        offset: GLint,
+        stride: GLsizei,
+    ) {
+        if !state::direct_state_access() {
+            self.bind();
+            vbo.bind();
+            self.set_attribute(location, attribute, offset, stride);
+            return;
+        }
+
+        // One buffer binding point per attribute, the offset goes in the binding
+        self.bind_vertex_buffer(location, vbo, offset as GLintptr, stride);
+        self.set_attribute_format(location, attribute, 0);
+        self.set_attribute_binding(location, location);
+        self.set_binding_divisor(location, attribute.divisor());
+    }
+
+    // Describe how attribute `location` reads a vertex, without tying it to any buffer.
+    // `relative_offset` is from the start of the vertex in the buffer of its binding point.
+    // Without Direct State Access the VAO is left bound, for this and the binding functions below.
+    // These need separate vertex formats and do nothing but log an error without them
+    pub fn set_attribute_format(
+        &self,
+        location: GLuint,
+        attribute: &VertexAttribute,
+        relative_offset: GLuint,
+    ) {
+        if !Capabilities::report(|capabilities| capabilities.check_vertex_attribute(location))
+            || !Capabilities::report(Capabilities::check_separate_vertex_formats)
+        {
+            return;
+        }
+        let direct_state_access = state::direct_state_access();
+        if !direct_state_access {
+            self.bind();
+        }
+
+        let components = attribute.components();
+        let data_type = attribute.data_type() as GLenum;
+        let normalized = if attribute.is_normalized() {
+            gl::TRUE
+        } else {
+            gl::FALSE
+        };
+        let floating_point = attribute.is_floating_point() || attribute.is_normalized();
+        unsafe {
+            match (direct_state_access, floating_point) {
+                (true, true) => gl::VertexArrayAttribFormat(
+                    self.handle,
+                    location,
+                    components,
+                    data_type,
+                    normalized,
+                    relative_offset,
+                ),
+                (true, false) => gl::VertexArrayAttribIFormat(
+                    self.handle,
+                    location,
+                    components,
+                    data_type,
+                    relative_offset,
+                ),
+                (false, true) => gl::VertexAttribFormat(
+                    location,
+                    components,
+                    data_type,
+                    normalized,
+                    relative_offset,
+                ),
+                (false, false) => {
+                    gl::VertexAttribIFormat(location, components, data_type, relative_offset);
+                }
+            }
+            if direct_state_access {
+                gl::EnableVertexArrayAttrib(self.handle, location);
+            } else {
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+        check_gl_error();
+    }
+
+    // Read attribute `location` from the buffer at binding point `binding`
+    pub fn set_attribute_binding(&self, location: GLuint, binding: GLuint) {
+        if !Capabilities::report(|capabilities| capabilities.check_vertex_binding(binding)) {
+            return;
+        }
+        if state::direct_state_access() {
+            unsafe { gl::VertexArrayAttribBinding(self.handle, location, binding) };
+        } else {
+            self.bind();
+            unsafe { gl::VertexAttribBinding(location, binding) };
+        }
+        check_gl_error();
+    }
+
+    // Point `binding` at the vertices in `vbo`, starting `offset` bytes in. Attributes keep
+    // their format, so the same VAO can draw from any buffer with the same layout
+    pub fn bind_vertex_buffer(
+        &self,
+        binding: GLuint,
+        vbo: &VertexBufferObject,
+        offset: GLintptr,
+        stride: GLsizei,
+    ) {
+        if !Capabilities::report(|capabilities| capabilities.check_vertex_binding(binding)) {
+            return;
+        }
+        if state::direct_state_access() {
+            unsafe {
+                gl::VertexArrayVertexBuffer(self.handle, binding, vbo.handle(), offset, stride);
+            }
+        } else {
+            self.bind();
+            unsafe { gl::BindVertexBuffer(binding, vbo.handle(), offset, stride) };
+        }
+        check_gl_error();
+    }
+
+    // Advance every attribute reading from `binding` once every `divisor` instances, 0 means
+    // per vertex
+    pub fn set_binding_divisor(&self, binding: GLuint, divisor: GLuint) {
+        if !Capabilities::report(|capabilities| capabilities.check_vertex_binding(binding)) {
+            return;
+        }
+        if state::direct_state_access() {
+            unsafe { gl::VertexArrayBindingDivisor(self.handle, binding, divisor) };
+        } else {
+            self.bind();
+            unsafe { gl::VertexBindingDivisor(binding, divisor) };
+        }
+        check_gl_error();
+    }
+
+    // Element buffer used by indexed draws with this VAO
+    pub fn set_element_buffer(&self, ebo: &ElementBufferObject) {
+        if state::direct_state_access() {
+            unsafe { gl::VertexArrayElementBuffer(self.handle, ebo.handle()) };
+            check_gl_error();
+        } else {
+            // The EBO binding is stored in the VAO, so it must be bound while the VAO is
+            self.bind();
+            ebo.bind();
+        }
+    }
+
     pub fn set_attribute(
         &self,
         location: GLuint,
@@ -37,6 +202,7 @@ impl VertexArrayObject {
         offset: GLint,
         stride: GLsizei,
     ) {
+        Capabilities::report(|capabilities| capabilities.check_vertex_attribute(location));
         let components = attribute.components();
         let data_type = attribute.data_type() as GLenum;
         let normalized = if attribute.is_normalized() {
@@ -72,6 +238,10 @@ impl VertexArrayObject {
             };
         }
         check_gl_error();
+
+        // Per instance attributes advance every `divisor` instances, 0 means per vertex
+        unsafe { gl::VertexAttribDivisor(location, attribute.divisor()) };
+        check_gl_error();
         // Set the VertexAttribute pointer in this location
 
         // Finally, we enable the VertexAttribute in this location
@@ -80,8 +250,7 @@ impl VertexArrayObject {
     }
 
     pub fn unbind(&self) {
-        unsafe { gl::BindVertexArray(NullHandle) };
-        check_gl_error();
+        state::with(|state| state.bind_vertex_array(NullHandle));
     }
 }
 
@@ -93,8 +262,7 @@ impl Default for VertexArrayObject {
 
 impl Object for VertexArrayObject {
     fn bind(&self) {
-        unsafe { gl::BindVertexArray(self.handle) };
-        check_gl_error();
+        state::with(|state| state.bind_vertex_array(self.handle));
     }
 
     fn handle(&self) -> Handle {
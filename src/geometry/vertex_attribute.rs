@@ -1,4 +1,4 @@
-use gl::types::{GLint, GLsizei};
+use gl::types::{GLint, GLsizei, GLuint};
 
 use crate::core::data::{self, Type};
 #[derive(Clone, Copy, Debug)]
@@ -28,6 +28,10 @@ impl Layout {
     pub const fn stride(&self) -> i32 {
         self.stride
     }
+
+    pub const fn divisor(&self) -> GLuint {
+        self.attribute.divisor()
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -35,6 +39,7 @@ pub struct VertexAttribute {
     data_type: data::Type,
     components: GLint,
     normalized: bool,
+    divisor: GLuint,
 }
 
 impl VertexAttribute {
@@ -44,11 +49,22 @@ impl VertexAttribute {
             data_type,
             components,
             normalized,
+            divisor: 0,
         }
     }
+    // Advance the attribute once every `divisor` instances instead of once per vertex
+    #[must_use]
+    pub const fn with_divisor(self, divisor: GLuint) -> Self {
+        Self { divisor, ..self }
+    }
     #[must_use]
     pub const fn get_size(&self) -> GLint {
-        self.data_type.get_size() * self.components
+        // Packed types hold every component in one value
+        if self.data_type.is_packed() {
+            self.data_type.get_size()
+        } else {
+            self.data_type.get_size() * self.components
+        }
     }
 
     #[must_use]
@@ -65,10 +81,30 @@ impl VertexAttribute {
     pub const fn is_normalized(&self) -> bool {
         self.normalized
     }
+
     #[must_use]
-    pub fn is_floating_point(&self) -> bool {
-        self.data_type == data::Type::Float
-            || self.data_type == data::Type::Double
-            || self.data_type == data::Type::Fixed
+    pub const fn divisor(&self) -> GLuint {
+        self.divisor
     }
+
+    #[must_use]
+    pub const fn is_instanced(&self) -> bool {
+        self.divisor != 0
+    }
+    // Whether the shader reads floats, packed types are always converted to floats
+    #[must_use]
+    pub const fn is_floating_point(&self) -> bool {
+        self.data_type.is_float() || self.data_type.is_packed()
+    }
+}
+
+// Divisor of a buffer binding read by `attributes`, which must all advance together
+pub(crate) fn binding_divisor(attributes: impl IntoIterator<Item = VertexAttribute>) -> GLuint {
+    let mut divisors = attributes.into_iter().map(|attribute| attribute.divisor());
+    let divisor = divisors.next().unwrap_or(0);
+    assert!(
+        divisors.all(|other| other == divisor),
+        "Attributes sharing a buffer binding must have the same divisor"
+    );
+    divisor
 }
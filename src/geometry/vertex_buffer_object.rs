@@ -1,7 +1,8 @@
 use crate::{
     core::{
-        buffer_object::{BufferObject, Target},
-        object::{Handle, NullHandle, Object},
+        buffer_object::{create_buffer, BufferObject, Target},
+        object::{Handle, Object},
+        state,
     },
     error::check_gl_error,
 };
@@ -14,9 +15,7 @@ pub struct VertexBufferObject {
 impl VertexBufferObject {
     #[must_use]
     pub fn new() -> Self {
-        let mut handle = NullHandle;
-        unsafe { gl::GenBuffers(1, &mut handle) };
-        check_gl_error();
+        let handle = create_buffer();
         Self { handle }
     }
 }
@@ -31,12 +30,12 @@ impl Drop for VertexBufferObject {
     fn drop(&mut self) {
         unsafe { gl::DeleteBuffers(1, &self.handle()) }
         check_gl_error();
+        state::with(|state| state.forget_buffer(self.handle()));
     }
 }
 impl Object for VertexBufferObject {
     fn bind(&self) {
-        unsafe { gl::BindBuffer(self.target() as u32, self.handle()) }
-        check_gl_error();
+        state::with(|state| state.bind_buffer(self.target() as u32, self.handle()));
     }
 
     fn handle(&self) -> Handle {
@@ -0,0 +1,303 @@
+use gl::types::{GLint, GLsizei, GLuint};
+
+use crate::core::{buffer_object::BufferObject, state};
+
+use super::{
+    vertex_array_object::VertexArrayObject,
+    vertex_attribute::{Layout, VertexAttribute},
+    vertex_buffer_object::VertexBufferObject,
+    vertex_layout::VertexLayout,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrangement {
+    // All attributes of a vertex are stored together in a single VBO
+    Interleaved,
+    // Each attribute is stored in its own VBO
+    Planar,
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexFormat {
+    arrangement: Arrangement,
+    alignment: GLint,
+    attributes: Vec<VertexAttribute>,
+    locations: Vec<GLuint>,
+    layouts: Vec<Layout>,
+    stride: GLsizei,
+    // What each VBO holds, all the attributes when interleaved or one each when planar
+    buffers: Vec<VertexLayout>,
+}
+
+impl VertexFormat {
+    // Default alignment, 4 bytes is the minimum most drivers handle without a slow path
+    pub const DEFAULT_ALIGNMENT: GLint = 4;
+
+    #[must_use]
+    pub fn new(arrangement: Arrangement, attributes: &[VertexAttribute]) -> Self {
+        let mut format = Self {
+            arrangement,
+            alignment: Self::DEFAULT_ALIGNMENT,
+            attributes: attributes.to_vec(),
+            locations: (0..attributes.len() as GLuint).collect(),
+            layouts: vec![],
+            stride: 0,
+            buffers: vec![],
+        };
+        format.compute_layouts();
+        format
+    }
+
+    #[must_use]
+    pub fn interleaved(attributes: &[VertexAttribute]) -> Self {
+        Self::new(Arrangement::Interleaved, attributes)
+    }
+
+    #[must_use]
+    pub fn planar(attributes: &[VertexAttribute]) -> Self {
+        Self::new(Arrangement::Planar, attributes)
+    }
+
+    #[must_use]
+    pub fn with_alignment(mut self, alignment: GLint) -> Self {
+        assert!(
+            alignment > 0 && (alignment & (alignment - 1)) == 0,
+            "Alignment must be a power of two"
+        );
+        self.alignment = alignment;
+        self.compute_layouts();
+        self
+    }
+
+    // Append an attribute at the location after the last one
+    #[must_use]
+    pub fn with_attribute(self, attribute: VertexAttribute) -> Self {
+        let location = self.locations.last().map_or(0, |location| location + 1);
+        self.with_attribute_at(location, attribute)
+    }
+
+    // Each location can only hold one attribute
+    #[must_use]
+    pub fn with_attribute_at(mut self, location: GLuint, attribute: VertexAttribute) -> Self {
+        assert!(
+            !self.locations.contains(&location),
+            "Location {location} already has an attribute"
+        );
+        self.attributes.push(attribute);
+        self.locations.push(location);
+        self.compute_layouts();
+        self
+    }
+
+    #[must_use]
+    pub const fn arrangement(&self) -> Arrangement {
+        self.arrangement
+    }
+
+    #[must_use]
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    // Shader location of each attribute, by default its index
+    #[must_use]
+    pub fn locations(&self) -> &[GLuint] {
+        &self.locations
+    }
+
+    // Layouts in attribute order
+    #[must_use]
+    pub fn layouts(&self) -> &[Layout] {
+        &self.layouts
+    }
+
+    // Stride of the interleaved vertex, for planar formats it is the size of all attributes together
+    #[must_use]
+    pub const fn stride(&self) -> GLsizei {
+        self.stride
+    }
+
+    // Number of VBOs that `apply` expects
+    #[must_use]
+    pub const fn buffer_count(&self) -> usize {
+        self.buffers.len()
+    }
+
+    // Layout of the vertices in each VBO, the one at index i is read from binding point i
+    #[must_use]
+    pub fn buffer_layouts(&self) -> &[VertexLayout] {
+        &self.buffers
+    }
+
+    // Set all the attributes in the VAO, with one VBO for interleaved formats or one per attribute for planar
+    pub fn apply(&self, vao: &VertexArrayObject, vbos: &[&VertexBufferObject]) {
+        self.check_buffer_count(vbos);
+        for (layout, vbo) in self.buffers.iter().zip(vbos) {
+            layout.apply_to(vao, vbo);
+        }
+        if !state::direct_state_access() {
+            vao.unbind();
+            if let Some(vbo) = vbos.last() {
+                vbo.unbind();
+            }
+        }
+    }
+
+    // Buffer binding point attribute `index` reads from, 0 for interleaved formats and the
+    // attribute index for planar ones
+    #[must_use]
+    pub const fn binding(&self, index: usize) -> GLuint {
+        match self.arrangement {
+            Arrangement::Interleaved => 0,
+            Arrangement::Planar => index as GLuint,
+        }
+    }
+
+    // Set the attribute formats, bindings and divisors in the VAO without any buffer, then
+    // `bind_buffers` picks the VBOs. Needs GL 4.3
+    pub fn apply_format(&self, vao: &VertexArrayObject) {
+        for (binding, layout) in self.buffers.iter().enumerate() {
+            layout.apply_format(vao, binding as GLuint);
+        }
+    }
+
+    // Read the vertices from `vbos` in a VAO set up by `apply_format`, with one VBO for
+    // interleaved formats or one per attribute for planar, skipping `first_vertex` vertices
+    pub fn bind_buffers(
+        &self,
+        vao: &VertexArrayObject,
+        vbos: &[&VertexBufferObject],
+        first_vertex: usize,
+    ) {
+        self.check_buffer_count(vbos);
+        for (binding, (layout, vbo)) in self.buffers.iter().zip(vbos).enumerate() {
+            layout.bind_buffer(vao, binding as GLuint, vbo, first_vertex);
+        }
+    }
+
+    fn check_buffer_count(&self, vbos: &[&VertexBufferObject]) {
+        assert_eq!(
+            vbos.len(),
+            self.buffer_count(),
+            "Vertex format expects {} buffers",
+            self.buffer_count()
+        );
+    }
+
+    fn compute_layouts(&mut self) {
+        self.layouts.clear();
+        match self.arrangement {
+            Arrangement::Interleaved => {
+                let mut offset = 0;
+                let mut max_alignment = self.alignment;
+                for attribute in &self.attributes {
+                    let alignment = self.attribute_alignment(attribute);
+                    max_alignment = max_alignment.max(alignment);
+                    offset = align_up(offset, alignment);
+                    self.layouts.push(Layout::new(*attribute, offset, 0));
+                    offset += attribute.get_size();
+                }
+                self.stride = align_up(offset, max_alignment);
+                for layout in &mut self.layouts {
+                    *layout = Layout::new(layout.attribute(), layout.offset(), self.stride);
+                }
+            }
+            Arrangement::Planar => {
+                self.stride = 0;
+                for attribute in &self.attributes {
+                    let stride =
+                        align_up(attribute.get_size(), self.attribute_alignment(attribute));
+                    self.layouts.push(Layout::new(*attribute, 0, stride));
+                    self.stride += stride;
+                }
+            }
+        }
+        let attributes = self
+            .locations
+            .iter()
+            .copied()
+            .zip(self.layouts.iter().copied());
+        self.buffers = match self.arrangement {
+            Arrangement::Interleaved => vec![VertexLayout::new(self.stride, attributes.collect())],
+            Arrangement::Planar => attributes
+                .map(|(location, layout)| {
+                    VertexLayout::new(layout.stride(), vec![(location, layout)])
+                })
+                .collect(),
+        };
+    }
+
+    fn attribute_alignment(&self, attribute: &VertexAttribute) -> GLint {
+        attribute.data_type().get_size().max(self.alignment)
+    }
+}
+
+const fn align_up(value: GLint, alignment: GLint) -> GLint {
+    (value + alignment - 1) / alignment * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::Type;
+
+    use super::*;
+
+    const POSITION: VertexAttribute = VertexAttribute::new(Type::Float, 3, false);
+    const COLOR: VertexAttribute = VertexAttribute::new(Type::UByte, 3, true);
+    const WEIGHT: VertexAttribute = VertexAttribute::new(Type::Double, 1, false);
+
+    fn offsets(format: &VertexFormat) -> Vec<(GLint, GLsizei)> {
+        let layouts = format.layouts().iter();
+        layouts
+            .map(|layout| (layout.offset(), layout.stride()))
+            .collect()
+    }
+
+    #[test]
+    fn align_up_to_multiples() {
+        assert_eq!(align_up(0, 4), 0);
+        assert_eq!(align_up(5, 4), 8);
+        assert_eq!(align_up(8, 8), 8);
+        assert_eq!(align_up(3, 1), 3);
+    }
+
+    #[test]
+    fn interleaved_layouts() {
+        // The double starts at the next multiple of 8 after the 3 color bytes
+        let format = VertexFormat::interleaved(&[POSITION, COLOR, WEIGHT]);
+        assert_eq!(offsets(&format), [(0, 24), (12, 24), (16, 24)]);
+        assert_eq!(format.stride(), 24);
+        assert_eq!(format.buffer_count(), 1);
+        assert_eq!(format.buffer_layouts()[0].stride(), 24);
+        assert_eq!(format.locations(), [0, 1, 2]);
+
+        // Floats keep their 4 byte alignment, bytes can be packed
+        let format = VertexFormat::interleaved(&[COLOR, POSITION]).with_alignment(1);
+        assert_eq!(offsets(&format), [(0, 16), (4, 16)]);
+        let format = VertexFormat::interleaved(&[COLOR, COLOR]).with_alignment(1);
+        assert_eq!(offsets(&format), [(0, 6), (3, 6)]);
+    }
+
+    #[test]
+    fn planar_layouts() {
+        let format = VertexFormat::planar(&[POSITION, COLOR]);
+        assert_eq!(offsets(&format), [(0, 12), (0, 4)]);
+        assert_eq!(format.stride(), 16);
+        let buffers = format.buffer_layouts();
+        assert_eq!(buffers.len(), 2);
+        assert_eq!(buffers[1].stride(), 4);
+        assert_eq!(buffers[1].attributes()[0].0, 1);
+        assert_eq!(format.binding(1), 1);
+
+        let format = format.with_alignment(1).with_attribute_at(5, WEIGHT);
+        assert_eq!(offsets(&format), [(0, 12), (0, 3), (0, 8)]);
+        let format = format.with_attribute(POSITION);
+        assert_eq!(format.locations(), [0, 1, 5, 6]);
+    }
+
+    #[test]
+    #[should_panic = "Location 1 already has an attribute"]
+    fn reject_duplicate_locations() {
+        let _ = VertexFormat::planar(&[POSITION, COLOR]).with_attribute_at(1, WEIGHT);
+    }
+}
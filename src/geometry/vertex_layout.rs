@@ -0,0 +1,154 @@
+use gl::types::{GLintptr, GLsizei, GLuint};
+use glam::{IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+use crate::core::{color::Color, data::Type, state};
+
+use super::{
+    vertex_array_object::VertexArrayObject,
+    vertex_attribute::{binding_divisor, Layout, VertexAttribute},
+    vertex_buffer_object::VertexBufferObject,
+};
+
+pub use itugl_derive::Vertex;
+
+/// A type that can be stored as a single vertex attribute
+///
+/// # Safety
+/// The type must be laid out exactly as `ATTRIBUTE` describes, `components` tightly packed
+/// values of `data_type` with no padding
+pub unsafe trait VertexAttributeType {
+    const ATTRIBUTE: VertexAttribute;
+}
+
+/// A #[repr(C)] struct describing one vertex, usually implemented with #[derive(Vertex)]
+///
+/// # Safety
+/// Every attribute of `layout` must lie inside the struct, at the offset of a field whose
+/// bytes match the attribute type, since code such as `compress_vertices` reads the vertices
+/// through those offsets. The stride must be the size of the struct
+pub unsafe trait Vertex: Sized {
+    fn layout() -> VertexLayout;
+}
+
+// Attributes read from a single VBO at fixed offsets, as described by a #[derive(Vertex)] struct.
+// `VertexFormat` computes one of these for each of its buffers
+#[derive(Clone, Debug)]
+pub struct VertexLayout {
+    stride: GLsizei,
+    attributes: Vec<(GLuint, Layout)>,
+}
+
+impl VertexLayout {
+    #[must_use]
+    pub const fn new(stride: GLsizei, attributes: Vec<(GLuint, Layout)>) -> Self {
+        Self { stride, attributes }
+    }
+
+    #[must_use]
+    pub const fn stride(&self) -> GLsizei {
+        self.stride
+    }
+
+    #[must_use]
+    pub fn attributes(&self) -> &[(GLuint, Layout)] {
+        &self.attributes
+    }
+
+    // Set every attribute on the VAO, reading from `vbo`
+    pub fn apply_to(&self, vao: &VertexArrayObject, vbo: &VertexBufferObject) {
+        for (location, layout) in &self.attributes {
+            vao.set_attribute_buffer(
+                *location,
+                &layout.attribute(),
+                vbo,
+                layout.offset(),
+                layout.stride(),
+            );
+        }
+    }
+
+    // Set every attribute format on the VAO, reading from buffer binding point `binding`.
+    // Several layouts can share a VAO on different bindings, such as per vertex and per
+    // instance data. Needs GL 4.3
+    pub fn apply_format(&self, vao: &VertexArrayObject, binding: GLuint) {
+        for (location, layout) in &self.attributes {
+            vao.set_attribute_format(*location, &layout.attribute(), layout.offset() as GLuint);
+            vao.set_attribute_binding(*location, binding);
+        }
+        let divisor = binding_divisor(self.attributes.iter().map(|(_, layout)| layout.attribute()));
+        vao.set_binding_divisor(binding, divisor);
+        if !state::direct_state_access() {
+            vao.unbind();
+        }
+    }
+
+    // Read the vertices at `binding` from `vbo`, skipping `first_vertex` vertices
+    pub fn bind_buffer(
+        &self,
+        vao: &VertexArrayObject,
+        binding: GLuint,
+        vbo: &VertexBufferObject,
+        first_vertex: usize,
+    ) {
+        let offset = (first_vertex * self.stride as usize) as GLintptr;
+        vao.bind_vertex_buffer(binding, vbo, offset, self.stride);
+        if !state::direct_state_access() {
+            vao.unbind();
+        }
+    }
+
+    // Set every attribute on the VAO, reading from the currently bound VBO
+    pub fn apply(&self, vao: &VertexArrayObject) {
+        for (location, layout) in &self.attributes {
+            vao.set_attribute(
+                *location,
+                &layout.attribute(),
+                layout.offset(),
+                layout.stride(),
+            );
+        }
+    }
+}
+
+macro_rules! impl_vertex_attribute_type {
+    ($($ty:ty => $data_type:expr, $components:expr;)*) => {
+        $(
+            unsafe impl VertexAttributeType for $ty {
+                const ATTRIBUTE: VertexAttribute =
+                    VertexAttribute::new($data_type, $components, false);
+            }
+        )*
+    };
+}
+
+impl_vertex_attribute_type! {
+    f32 => Type::Float, 1;
+    f64 => Type::Double, 1;
+    i8 => Type::Byte, 1;
+    u8 => Type::UByte, 1;
+    i16 => Type::Short, 1;
+    u16 => Type::UShort, 1;
+    i32 => Type::Int, 1;
+    u32 => Type::UInt, 1;
+    Vec2 => Type::Float, 2;
+    Vec3 => Type::Float, 3;
+    Vec4 => Type::Float, 4;
+    IVec2 => Type::Int, 2;
+    IVec3 => Type::Int, 3;
+    IVec4 => Type::Int, 4;
+    UVec2 => Type::UInt, 2;
+    UVec3 => Type::UInt, 3;
+    UVec4 => Type::UInt, 4;
+    Color => Type::Float, 4;
+}
+
+// Arrays of up to 4 scalars map to a vector attribute of the same type
+unsafe impl<T: VertexAttributeType, const N: usize> VertexAttributeType for [T; N] {
+    const ATTRIBUTE: VertexAttribute = {
+        assert!(
+            N >= 1 && N <= 4 && T::ATTRIBUTE.components() == 1,
+            "Only arrays of 1 to 4 scalars can be vertex attributes"
+        );
+        VertexAttribute::new(T::ATTRIBUTE.data_type(), N as i32, false)
+    };
+}
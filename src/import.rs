@@ -0,0 +1,3 @@
+pub mod gltf;
+pub(crate) mod json;
+pub mod obj;
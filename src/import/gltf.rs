@@ -0,0 +1,1587 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gl::types::{GLenum, GLuint};
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::{
+    core::{color::Color, data},
+    geometry::{
+        drawcall::{DrawCall, Primitive},
+        element_buffer_object::ElementBufferObject,
+        mesh::Mesh,
+        vertex_attribute::VertexAttribute,
+        vertex_format::VertexFormat,
+    },
+};
+
+use super::json::Json;
+
+// Shader locations used for the vertex attributes by `GltfDocument::to_mesh`,
+// attributes not listed here are ignored
+pub const ATTRIBUTE_LOCATIONS: [(&str, GLuint); 8] = [
+    ("POSITION", 0),
+    ("NORMAL", 1),
+    ("TEXCOORD_0", 2),
+    ("TANGENT", 3),
+    ("COLOR_0", 4),
+    ("JOINTS_0", 5),
+    ("WEIGHTS_0", 6),
+    ("TEXCOORD_1", 7),
+];
+
+// Extensions we can load, files requiring anything else are rejected
+const SUPPORTED_EXTENSIONS: [&str; 1] = ["KHR_lights_punctual"];
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const GLB_CHUNK_JSON: usize = 0x4E4F_534A;
+const GLB_CHUNK_BIN: usize = 0x004E_4942;
+
+// Elements of an accessor, tightly packed with any byte stride and sparse substitution resolved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessorData {
+    pub data_type: data::Type,
+    pub components: i32,
+    pub normalized: bool,
+    pub count: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl AccessorData {
+    #[must_use]
+    pub const fn element_size(&self) -> usize {
+        self.data_type.get_size() as usize * self.components as usize
+    }
+
+    #[must_use]
+    pub const fn attribute(&self) -> VertexAttribute {
+        VertexAttribute::new(self.data_type, self.components, self.normalized)
+    }
+
+    // All the components as floats, normalized integers are mapped to [0, 1] or [-1, 1]
+    #[must_use]
+    pub fn to_f32(&self) -> Vec<f32> {
+        let size = self.data_type.get_size() as usize;
+        self.bytes
+            .chunks_exact(size)
+            .map(|bytes| read_component(self.data_type, bytes, self.normalized))
+            .collect()
+    }
+
+    // All the components as unsigned integers, meant for indices and joints
+    #[must_use]
+    pub fn to_u32(&self) -> Vec<u32> {
+        let size = self.data_type.get_size() as usize;
+        self.bytes
+            .chunks_exact(size)
+            .map(|bytes| read_unsigned(self.data_type, bytes))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GltfPrimitive {
+    // (shader location, data) following `ATTRIBUTE_LOCATIONS`
+    pub attributes: Vec<(GLuint, AccessorData)>,
+    pub indices: Option<AccessorData>,
+    pub primitive: Primitive,
+    pub material: Option<usize>,
+}
+
+impl GltfPrimitive {
+    #[must_use]
+    pub fn vertex_count(&self) -> usize {
+        self.attributes.first().map_or(0, |(_, data)| data.count)
+    }
+
+    #[must_use]
+    pub fn attribute(&self, name: &str) -> Option<&AccessorData> {
+        let (_, location) = ATTRIBUTE_LOCATIONS.iter().find(|(n, _)| *n == name)?;
+        self.attributes
+            .iter()
+            .find(|(l, _)| l == location)
+            .map(|(_, data)| data)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GltfMesh {
+    pub name: String,
+    pub primitives: Vec<GltfPrimitive>,
+    // Default morph target weights
+    pub weights: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    // Fragments with alpha below the cutoff are discarded
+    Mask(f32),
+    Blend,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureRef {
+    pub texture: usize,
+    // Which TEXCOORD_n set samples the texture
+    pub tex_coord: u32,
+}
+
+// Metallic-roughness material, factors multiply the matching textures
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: String,
+    pub base_color: Color,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic: f32,
+    pub roughness: f32,
+    // Roughness in the green channel, metalness in the blue one
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive: Color,
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for GltfMaterial {
+    // Defaults from the specification, also used by primitives without material
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: Color::new(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Color::new(0.0, 0.0, 0.0, 1.0),
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GltfTexture {
+    pub name: String,
+    pub image: Option<usize>,
+    pub sampler: Option<usize>,
+}
+
+// Filters and wraps are GL enums, filters are None when left to the implementation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GltfSampler {
+    pub mag_filter: Option<GLenum>,
+    pub min_filter: Option<GLenum>,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+
+impl Default for GltfSampler {
+    fn default() -> Self {
+        Self {
+            mag_filter: None,
+            min_filter: None,
+            wrap_s: gl::REPEAT,
+            wrap_t: gl::REPEAT,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageSource {
+    // External file, resolved relative to the glTF file
+    Path(PathBuf),
+    // Encoded image from a data URI or a buffer view
+    Embedded {
+        mime_type: Option<String>,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GltfImage {
+    pub name: String,
+    pub source: ImageSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: String,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub skin: Option<usize>,
+    pub light: Option<usize>,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    pub weights: Vec<f32>,
+}
+
+impl GltfNode {
+    #[must_use]
+    pub fn local_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GltfScene {
+    pub name: String,
+    // Root nodes
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GltfProjection {
+    // Infinite projection when zfar is None, aspect ratio from the viewport when None
+    Perspective {
+        yfov: f32,
+        aspect_ratio: Option<f32>,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfCamera {
+    pub name: String,
+    pub projection: GltfProjection,
+}
+
+impl GltfCamera {
+    // `aspect_ratio` of the viewport is used unless the camera defines its own
+    #[must_use]
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        match self.projection {
+            GltfProjection::Perspective {
+                yfov,
+                aspect_ratio: camera_aspect_ratio,
+                znear,
+                zfar,
+            } => {
+                let aspect_ratio = camera_aspect_ratio.unwrap_or(aspect_ratio);
+                zfar.map_or_else(
+                    || perspective_infinite_rh_gl(yfov, aspect_ratio, znear),
+                    |zfar| Mat4::perspective_rh_gl(yfov, aspect_ratio, znear, zfar),
+                )
+            }
+            GltfProjection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => Mat4::orthographic_rh_gl(-xmag, xmag, -ymag, ymag, znear, zfar),
+        }
+    }
+}
+
+// Limit of `Mat4::perspective_rh_gl` as zfar goes to infinity, with the [-1, 1] depth range
+// of OpenGL. glam's `perspective_infinite_rh` maps depth to [0, 1]
+fn perspective_infinite_rh_gl(fov_y: f32, aspect_ratio: f32, z_near: f32) -> Mat4 {
+    let f = 1.0 / (0.5 * fov_y).tan();
+    Mat4::from_cols(
+        Vec4::new(f / aspect_ratio, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, f, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, -1.0, -1.0),
+        Vec4::new(0.0, 0.0, -2.0 * z_near, 0.0),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // Cone angles in radians from the light direction
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// Light from KHR_lights_punctual, lights point down the -Z axis of their node
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfLight {
+    pub name: String,
+    pub kind: LightKind,
+    pub color: Color,
+    pub intensity: f32,
+    // None for infinite range
+    pub range: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GltfSkin {
+    pub name: String,
+    pub joints: Vec<usize>,
+    // One per joint, identity when the file does not provide them
+    pub inverse_bind_matrices: Vec<Mat4>,
+    pub skeleton: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationPath {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    // Each keyframe stores (in tangent, value, out tangent)
+    CubicSpline,
+}
+
+// Channel with its sampler resolved, values are flattened keyframe by keyframe
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfChannel {
+    pub node: Option<usize>,
+    pub path: AnimationPath,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+impl GltfChannel {
+    const fn values_per_key(&self) -> usize {
+        match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            Interpolation::Linear | Interpolation::Step => 1,
+        }
+    }
+
+    // Number of floats in a single value, 4 for rotations or the morph target count for weights
+    #[must_use]
+    pub const fn value_size(&self) -> usize {
+        match self.times.len() {
+            0 => 0,
+            keys => self.values.len() / (keys * self.values_per_key()),
+        }
+    }
+
+    // At least one keyframe, strictly increasing times and exactly one value (three for cubic
+    // splines) of the size the path expects per keyframe
+    pub fn validate(&self) -> Result<(), String> {
+        if self.times.is_empty() {
+            return Err("Animation channel without keyframes".to_owned());
+        }
+        if !self.times.iter().all(|time| time.is_finite())
+            || self.times.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err("Animation times are not strictly increasing".to_owned());
+        }
+        let size = self.value_size();
+        let expected = match self.path {
+            AnimationPath::Translation | AnimationPath::Scale => 3,
+            AnimationPath::Rotation => 4,
+            AnimationPath::Weights => size.max(1),
+        };
+        if size != expected || self.values.len() != self.times.len() * size * self.values_per_key()
+        {
+            return Err(format!(
+                "{} animation values for {} keyframes of {expected} floats",
+                self.values.len(),
+                self.times.len()
+            ));
+        }
+        Ok(())
+    }
+
+    // Value at `time`, clamped to the first and last keyframes. Empty when the channel does not
+    // pass `validate`
+    #[must_use]
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let size = self.value_size();
+        let keys = self.times.len();
+        let per_key = self.values_per_key();
+        if size == 0
+            || self.values.len() != keys * size * per_key
+            || (self.path == AnimationPath::Rotation && size != 4)
+        {
+            return vec![];
+        }
+        // Value `offset` of keyframe `key`, 0 to 2 for (in tangent, value, out tangent)
+        let element =
+            |key: usize, offset: usize| &self.values[(key * per_key + offset) * size..][..size];
+        let value = |key: usize| element(key, per_key / 2);
+        let (first, last) = (self.times[0], self.times[keys - 1]);
+        if time <= first || keys == 1 {
+            return value(0).to_vec();
+        }
+        if time >= last {
+            return value(keys - 1).to_vec();
+        }
+        // Clamped in case the times are not increasing
+        let next = self
+            .times
+            .partition_point(|&t| t <= time)
+            .clamp(1, keys - 1);
+        let key = next - 1;
+        let delta = self.times[next] - self.times[key];
+        let t = if delta > 0.0 {
+            ((time - self.times[key]) / delta).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        match self.interpolation {
+            Interpolation::Step => value(key).to_vec(),
+            Interpolation::Linear if self.path == AnimationPath::Rotation => {
+                let a = Quat::from_slice(value(key));
+                let b = Quat::from_slice(value(next));
+                a.slerp(b, t).to_array().to_vec()
+            }
+            Interpolation::Linear => value(key)
+                .iter()
+                .zip(value(next))
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+            Interpolation::CubicSpline => {
+                let out_tangent = element(key, 2);
+                let in_tangent = element(next, 0);
+                let (t2, t3) = (t * t, t * t * t);
+                let result = (0..size).map(|i| {
+                    (2.0 * t3 - 3.0 * t2 + 1.0) * value(key)[i]
+                        + (t3 - 2.0 * t2 + t) * delta * out_tangent[i]
+                        + (-2.0 * t3 + 3.0 * t2) * value(next)[i]
+                        + (t3 - t2) * delta * in_tangent[i]
+                });
+                if self.path == AnimationPath::Rotation {
+                    let result: Vec<f32> = result.collect();
+                    Quat::from_slice(&result).normalize().to_array().to_vec()
+                } else {
+                    result.collect()
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GltfAnimation {
+    pub name: String,
+    pub channels: Vec<GltfChannel>,
+}
+
+impl GltfAnimation {
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|channel| channel.times.last())
+            .fold(0.0, |duration, &time| duration.max(time))
+    }
+}
+
+// Everything in a .gltf or .glb file, with all the buffer data already decoded
+#[derive(Clone, Debug, Default)]
+pub struct GltfDocument {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<GltfImage>,
+    pub samplers: Vec<GltfSampler>,
+    pub nodes: Vec<GltfNode>,
+    pub scenes: Vec<GltfScene>,
+    pub scene: Option<usize>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+    pub skins: Vec<GltfSkin>,
+    pub animations: Vec<GltfAnimation>,
+}
+
+impl GltfDocument {
+    // Load a .gltf or .glb file, external buffers and images are relative to it
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+        Self::from_slice(&bytes, path.parent().unwrap_or_else(|| Path::new("")))
+            .map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    // Parse glTF JSON or binary glTF, external URIs are resolved relative to `base_dir`
+    pub fn from_slice(bytes: &[u8], base_dir: &Path) -> Result<Self, String> {
+        let (source, binary) = if bytes.starts_with(GLB_MAGIC) {
+            parse_glb(bytes)?
+        } else {
+            let source = std::str::from_utf8(bytes)
+                .map_err(|_| "glTF JSON is not valid UTF-8".to_owned())?;
+            (source, None)
+        };
+        let json = Json::parse(source)?;
+        let version = json.get("asset").get("version").as_str().unwrap_or("");
+        if !version.starts_with("2.") {
+            return Err(format!("Unsupported glTF version '{version}'"));
+        }
+        for extension in json.get("extensionsRequired").elements() {
+            let name = extension.as_str().unwrap_or("");
+            if !SUPPORTED_EXTENSIONS.contains(&name) {
+                return Err(format!("Required extension {name} is not supported"));
+            }
+        }
+        let buffers = load_buffers(&json, binary, base_dir)?;
+        let loader = Loader {
+            json: &json,
+            buffers,
+        };
+        loader.load(base_dir)
+    }
+
+    #[must_use]
+    pub fn default_scene(&self) -> Option<&GltfScene> {
+        self.scene
+            .map_or_else(|| self.scenes.first(), |scene| self.scenes.get(scene))
+    }
+
+    // World matrix of every node, nodes are placed relative to their parent
+    #[must_use]
+    pub fn world_matrices(&self) -> Vec<Mat4> {
+        let mut matrices = vec![Mat4::IDENTITY; self.nodes.len()];
+        let mut has_parent = vec![false; self.nodes.len()];
+        for node in &self.nodes {
+            for &child in &node.children {
+                has_parent[child] = true;
+            }
+        }
+        let mut stack: Vec<(usize, Mat4)> = (0..self.nodes.len())
+            .filter(|&node| !has_parent[node])
+            .map(|node| (node, Mat4::IDENTITY))
+            .collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = &self.nodes[index];
+            matrices[index] = parent * node.local_matrix();
+            stack.extend(node.children.iter().map(|&child| (child, matrices[index])));
+        }
+        matrices
+    }
+
+    // Upload a mesh with one VAO and submesh per primitive, using planar VBOs with the
+    // attributes at `ATTRIBUTE_LOCATIONS`. The material index is the submesh slot,
+    // primitives without material use the slot after the last material
+    pub fn to_mesh(&self, index: usize) -> Result<Mesh, String> {
+        let gltf_mesh = self
+            .meshes
+            .get(index)
+            .ok_or_else(|| format!("No mesh {index} in {} meshes", self.meshes.len()))?;
+        let mut mesh = Mesh::new();
+        for primitive in &gltf_mesh.primitives {
+            // Accessor data is tightly packed
+            let mut format = VertexFormat::planar(&[]).with_alignment(1);
+            let mut vbos = vec![];
+            for (location, data) in &primitive.attributes {
+                format = format.with_attribute_at(*location, data.attribute());
+                vbos.push(mesh.add_raw_vertex_data(&data.bytes, data.count));
+            }
+            let ebo = primitive
+                .indices
+                .as_ref()
+                .map(|indices| {
+                    mesh.add_raw_element_data(&indices.bytes, indices.data_type, indices.count)
+                })
+                .transpose()?;
+            let vao = mesh.add_vertex_array(&format, &vbos, ebo);
+            let draw_call = primitive.indices.as_ref().map_or_else(
+                || {
+                    DrawCall::count(
+                        DrawCall::new(primitive.primitive),
+                        primitive.vertex_count() as i32,
+                    )
+                },
+                |indices| {
+                    DrawCall::count(
+                        DrawCall::index_type(DrawCall::new(primitive.primitive), indices.data_type),
+                        indices.count as i32,
+                    )
+                },
+            );
+            let material = primitive.material.unwrap_or(self.materials.len());
+            mesh.add_submesh(vao, draw_call, material);
+        }
+        Ok(mesh)
+    }
+}
+
+// Split a binary glTF in its JSON and BIN chunks
+fn parse_glb(bytes: &[u8]) -> Result<(&str, Option<&[u8]>), String> {
+    let read_u32 = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| "Truncated GLB".to_owned())
+    };
+    let version = read_u32(4)?;
+    if version != 2 {
+        return Err(format!("Unsupported GLB version {version}"));
+    }
+    let length = read_u32(8)?.min(bytes.len());
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(offset)?;
+        let chunk_type = read_u32(offset + 4)?;
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| "Truncated GLB chunk".to_owned())?;
+        match chunk_type {
+            GLB_CHUNK_JSON => json = Some(chunk),
+            GLB_CHUNK_BIN => binary = Some(chunk),
+            // Unknown chunks must be ignored
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+    let json = json.ok_or_else(|| "GLB without JSON chunk".to_owned())?;
+    let source = std::str::from_utf8(json).map_err(|_| "GLB JSON is not valid UTF-8".to_owned())?;
+    Ok((source, binary))
+}
+
+fn load_buffers(
+    json: &Json,
+    binary: Option<&[u8]>,
+    base_dir: &Path,
+) -> Result<Vec<Vec<u8>>, String> {
+    let mut buffers = vec![];
+    for (index, buffer) in json.get("buffers").elements().iter().enumerate() {
+        let data = match buffer.get("uri").as_str() {
+            Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)?.1,
+            Some(uri) => {
+                let path = base_dir.join(decode_percent(uri));
+                fs::read(&path)
+                    .map_err(|error| format!("Failed to read {}: {error}", path.display()))?
+            }
+            // Only the first buffer may refer to the GLB binary chunk
+            None if index == 0 => binary
+                .ok_or_else(|| "Buffer 0 has no URI and there is no GLB binary chunk".to_owned())?
+                .to_vec(),
+            None => return Err(format!("Buffer {index} has no URI")),
+        };
+        let length = buffer.get("byteLength").as_usize().unwrap_or(0);
+        if data.len() < length {
+            return Err(format!(
+                "Buffer {index} has {} bytes but byteLength is {length}",
+                data.len()
+            ));
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+// Returns the MIME type and the decoded data of a base64 data URI
+fn decode_data_uri(uri: &str) -> Result<(&str, Vec<u8>), String> {
+    let (header, payload) = uri
+        .strip_prefix("data:")
+        .and_then(|uri| uri.split_once(','))
+        .ok_or_else(|| "Invalid data URI".to_owned())?;
+    let Some(mime_type) = header.strip_suffix(";base64") else {
+        return Err("Only base64 data URIs are supported".to_owned());
+    };
+    Ok((mime_type, decode_base64(payload)?))
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(text.len() * 3 / 4);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    for byte in text.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err("Invalid base64 data".to_owned()),
+        };
+        accumulator = (accumulator << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+    Ok(output)
+}
+
+// URIs are percent-encoded, file names with spaces are written as %20
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            output.push(byte);
+            index += 3;
+        } else {
+            output.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+// Accessor component types are the matching GL enums
+const fn component_type(value: Option<usize>) -> Option<data::Type> {
+    match value {
+        Some(5120) => Some(data::Type::Byte),
+        Some(5121) => Some(data::Type::UByte),
+        Some(5122) => Some(data::Type::Short),
+        Some(5123) => Some(data::Type::UShort),
+        Some(5125) => Some(data::Type::UInt),
+        Some(5126) => Some(data::Type::Float),
+        _ => None,
+    }
+}
+
+fn element_components(element_type: &str) -> Option<i32> {
+    match element_type {
+        "SCALAR" => Some(1),
+        "VEC2" => Some(2),
+        "VEC3" => Some(3),
+        "VEC4" | "MAT2" => Some(4),
+        "MAT3" => Some(9),
+        "MAT4" => Some(16),
+        _ => None,
+    }
+}
+
+fn read_component(data_type: data::Type, bytes: &[u8], normalized: bool) -> f32 {
+    match data_type {
+        data::Type::Byte => {
+            let value = f32::from(bytes[0] as i8);
+            if normalized {
+                (value / 127.0).max(-1.0)
+            } else {
+                value
+            }
+        }
+        data::Type::UByte => {
+            let value = f32::from(bytes[0]);
+            if normalized {
+                value / 255.0
+            } else {
+                value
+            }
+        }
+        data::Type::Short => {
+            let value = f32::from(i16::from_le_bytes([bytes[0], bytes[1]]));
+            if normalized {
+                (value / 32767.0).max(-1.0)
+            } else {
+                value
+            }
+        }
+        data::Type::UShort => {
+            let value = f32::from(u16::from_le_bytes([bytes[0], bytes[1]]));
+            if normalized {
+                value / 65535.0
+            } else {
+                value
+            }
+        }
+        data::Type::UInt => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        data::Type::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        _ => 0.0,
+    }
+}
+
+fn read_unsigned(data_type: data::Type, bytes: &[u8]) -> u32 {
+    match data_type {
+        data::Type::Byte | data::Type::UByte => u32::from(bytes[0]),
+        data::Type::Short | data::Type::UShort => {
+            u32::from(u16::from_le_bytes([bytes[0], bytes[1]]))
+        }
+        data::Type::UInt => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        data::Type::Float => read_component(data_type, bytes, false) as u32,
+        _ => 0,
+    }
+}
+
+fn color(value: &Json, default: Color) -> Result<Color, String> {
+    if value.is_null() {
+        return Ok(default);
+    }
+    match value.as_f32_array().as_deref() {
+        Some(&[r, g, b]) => Ok(Color::new(r, g, b, 1.0)),
+        Some(&[r, g, b, a]) => Ok(Color::new(r, g, b, a)),
+        _ => Err("Invalid color".to_owned()),
+    }
+}
+
+fn name(value: &Json) -> String {
+    value.get("name").as_str().unwrap_or("").to_owned()
+}
+
+struct Loader<'a> {
+    json: &'a Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Loader<'_> {
+    fn load(&self, base_dir: &Path) -> Result<GltfDocument, String> {
+        let lights = self
+            .json
+            .get("extensions")
+            .get("KHR_lights_punctual")
+            .get("lights");
+        Ok(GltfDocument {
+            meshes: self.load_all("meshes", Self::load_mesh)?,
+            materials: self.load_all("materials", Self::load_material)?,
+            textures: self.load_all("textures", Self::load_texture)?,
+            images: self.load_all("images", |loader, image| loader.load_image(image, base_dir))?,
+            samplers: self.load_all("samplers", |_, sampler| Ok(load_sampler(sampler)))?,
+            nodes: self.load_nodes()?,
+            scenes: self.load_all("scenes", Self::load_scene)?,
+            scene: self.optional_index(self.json.get("scene"), "scenes")?,
+            cameras: self.load_all("cameras", |_, camera| load_camera(camera))?,
+            lights: lights
+                .elements()
+                .iter()
+                .enumerate()
+                .map(|(index, light)| load_light(light).map_err(|e| format!("Light {index}: {e}")))
+                .collect::<Result<_, _>>()?,
+            skins: self.load_all("skins", Self::load_skin)?,
+            animations: self.load_all("animations", Self::load_animation)?,
+        })
+    }
+
+    // Load every element of a top level array, errors are prefixed with the element
+    fn load_all<T>(
+        &self,
+        array: &str,
+        load: impl Fn(&Self, &Json) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.json
+            .get(array)
+            .elements()
+            .iter()
+            .enumerate()
+            .map(|(index, value)| load(self, value).map_err(|e| format!("{array}[{index}]: {e}")))
+            .collect()
+    }
+
+    fn index(&self, value: &Json, array: &str) -> Result<usize, String> {
+        self.optional_index(value, array)?
+            .ok_or_else(|| format!("Missing index into {array}"))
+    }
+
+    fn optional_index(&self, value: &Json, array: &str) -> Result<Option<usize>, String> {
+        if value.is_null() {
+            return Ok(None);
+        }
+        let count = self.json.get(array).elements().len();
+        match value.as_usize() {
+            Some(index) if index < count => Ok(Some(index)),
+            _ => Err(format!("Invalid index into {array}")),
+        }
+    }
+
+    fn indices(&self, value: &Json, array: &str) -> Result<Vec<usize>, String> {
+        value
+            .elements()
+            .iter()
+            .map(|index| self.index(index, array))
+            .collect()
+    }
+
+    fn accessor(&self, value: &Json) -> Result<AccessorData, String> {
+        let index = self.index(value, "accessors")?;
+        self.read_accessor(index)
+            .map_err(|error| format!("accessors[{index}]: {error}"))
+    }
+
+    fn read_accessor(&self, index: usize) -> Result<AccessorData, String> {
+        let accessor = &self.json.get("accessors").elements()[index];
+        let data_type = component_type(accessor.get("componentType").as_usize())
+            .ok_or_else(|| "Invalid componentType".to_owned())?;
+        let components = accessor
+            .get("type")
+            .as_str()
+            .and_then(element_components)
+            .ok_or_else(|| "Invalid type".to_owned())?;
+        let count = accessor
+            .get("count")
+            .as_usize()
+            .ok_or_else(|| "Invalid count".to_owned())?;
+        let element_size = data_type.get_size() as usize * components as usize;
+
+        // Without buffer view the accessor is all zeros, unless sparse values replace some
+        let mut bytes =
+            if let Some(view) = self.optional_index(accessor.get("bufferView"), "bufferViews")? {
+                let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+                self.read_elements(view, offset, element_size, count)?
+            } else {
+                let size = element_size
+                    .checked_mul(count)
+                    .ok_or_else(|| format!("{count} elements are too many"))?;
+                let mut bytes = vec![];
+                bytes
+                    .try_reserve_exact(size)
+                    .map_err(|_| format!("{count} elements are too many"))?;
+                bytes.resize(size, 0);
+                bytes
+            };
+
+        let sparse = accessor.get("sparse");
+        if !sparse.is_null() {
+            let sparse_count = sparse.get("count").as_usize().unwrap_or(0);
+            let indices = sparse.get("indices");
+            let index_type = component_type(indices.get("componentType").as_usize())
+                .ok_or_else(|| "Invalid sparse componentType".to_owned())?;
+            let index_size = index_type.get_size() as usize;
+            let index_bytes = self.read_elements(
+                self.index(indices.get("bufferView"), "bufferViews")?,
+                indices.get("byteOffset").as_usize().unwrap_or(0),
+                index_size,
+                sparse_count,
+            )?;
+            let values = sparse.get("values");
+            let value_bytes = self.read_elements(
+                self.index(values.get("bufferView"), "bufferViews")?,
+                values.get("byteOffset").as_usize().unwrap_or(0),
+                element_size,
+                sparse_count,
+            )?;
+            for (target, value) in index_bytes
+                .chunks_exact(index_size)
+                .zip(value_bytes.chunks_exact(element_size))
+            {
+                let target = read_unsigned(index_type, target) as usize;
+                if target >= count {
+                    return Err(format!("Sparse index {target} out of {count} elements"));
+                }
+                bytes[target * element_size..][..element_size].copy_from_slice(value);
+            }
+        }
+
+        Ok(AccessorData {
+            data_type,
+            components,
+            normalized: accessor.get("normalized").as_bool().unwrap_or(false),
+            count,
+            bytes,
+        })
+    }
+
+    // Copy `count` elements from a buffer view, removing the stride. Everything is checked
+    // against the view before allocating, so malformed counts and offsets are errors
+    fn read_elements(
+        &self,
+        view: usize,
+        offset: usize,
+        element_size: usize,
+        count: usize,
+    ) -> Result<Vec<u8>, String> {
+        let view_json = &self.json.get("bufferViews").elements()[view];
+        let buffer = self.index(view_json.get("buffer"), "buffers")?;
+        let view_offset = view_json.get("byteOffset").as_usize().unwrap_or(0);
+        let view_length = view_json
+            .get("byteLength")
+            .as_usize()
+            .ok_or_else(|| format!("Buffer view {view} has no byteLength"))?;
+        let stride = view_json
+            .get("byteStride")
+            .as_usize()
+            .unwrap_or(element_size);
+        if stride < element_size {
+            return Err(format!(
+                "Buffer view {view} has a stride of {stride} for elements of {element_size} bytes"
+            ));
+        }
+        let data = view_offset
+            .checked_add(view_length)
+            .and_then(|end| self.buffers[buffer].get(view_offset..end))
+            .ok_or_else(|| format!("Buffer view {view} is outside of buffer {buffer}"))?;
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let end = stride
+            .checked_mul(count - 1)
+            .and_then(|last| last.checked_add(offset))
+            .and_then(|last| last.checked_add(element_size));
+        if end.is_none_or(|end| end > data.len()) {
+            return Err(format!("Reads past the end of buffer view {view}"));
+        }
+        // The stride is at least the element size, so this fits in the view
+        let mut output = Vec::with_capacity(element_size * count);
+        for index in 0..count {
+            let start = offset + index * stride;
+            output.extend_from_slice(&data[start..start + element_size]);
+        }
+        Ok(output)
+    }
+
+    fn load_mesh(&self, mesh: &Json) -> Result<GltfMesh, String> {
+        let mut primitives = vec![];
+        for primitive in mesh.get("primitives").elements() {
+            let mut attributes = vec![];
+            for (name, accessor) in primitive.get("attributes").members() {
+                let Some((_, location)) = ATTRIBUTE_LOCATIONS.iter().find(|(n, _)| n == name)
+                else {
+                    continue;
+                };
+                if attributes.iter().any(|(used, _)| used == location) {
+                    return Err(format!("Attribute {name} is given twice"));
+                }
+                let data = self.accessor(accessor)?;
+                if attributes
+                    .first()
+                    .is_some_and(|(_, first): &(GLuint, AccessorData)| first.count != data.count)
+                {
+                    return Err(format!("Attribute {name} has a different vertex count"));
+                }
+                attributes.push((*location, data));
+            }
+            attributes.sort_by_key(|(location, _)| *location);
+
+            let indices = if primitive.get("indices").is_null() {
+                None
+            } else {
+                let indices = self.accessor(primitive.get("indices"))?;
+                if indices.components != 1
+                    || !ElementBufferObject::is_supported_type(indices.data_type)
+                {
+                    return Err("Indices must be unsigned integer scalars".to_owned());
+                }
+                Some(indices)
+            };
+
+            let primitive_type = match primitive.get("mode").as_usize().unwrap_or(4) {
+                0 => Primitive::Points,
+                1 => Primitive::Lines,
+                2 => Primitive::LineLoop,
+                3 => Primitive::LineStrip,
+                4 => Primitive::Triangles,
+                5 => Primitive::TriangleStrip,
+                6 => Primitive::TriangleFan,
+                mode => return Err(format!("Invalid primitive mode {mode}")),
+            };
+
+            primitives.push(GltfPrimitive {
+                attributes,
+                indices,
+                primitive: primitive_type,
+                material: self.optional_index(primitive.get("material"), "materials")?,
+            });
+        }
+        Ok(GltfMesh {
+            name: name(mesh),
+            primitives,
+            weights: mesh.get("weights").as_f32_array().unwrap_or_default(),
+        })
+    }
+
+    fn texture_ref(&self, value: &Json) -> Result<Option<TextureRef>, String> {
+        Ok(self
+            .optional_index(value.get("index"), "textures")?
+            .map(|texture| TextureRef {
+                texture,
+                tex_coord: value.get("texCoord").as_usize().unwrap_or(0) as u32,
+            }))
+    }
+
+    fn load_material(&self, material: &Json) -> Result<GltfMaterial, String> {
+        let defaults = GltfMaterial::default();
+        let pbr = material.get("pbrMetallicRoughness");
+        let normal = material.get("normalTexture");
+        let occlusion = material.get("occlusionTexture");
+        let alpha_mode = match material.get("alphaMode").as_str().unwrap_or("OPAQUE") {
+            "OPAQUE" => AlphaMode::Opaque,
+            "MASK" => AlphaMode::Mask(material.get("alphaCutoff").as_f32().unwrap_or(0.5)),
+            "BLEND" => AlphaMode::Blend,
+            mode => return Err(format!("Invalid alphaMode {mode}")),
+        };
+        Ok(GltfMaterial {
+            name: name(material),
+            base_color: color(pbr.get("baseColorFactor"), defaults.base_color)?,
+            base_color_texture: self.texture_ref(pbr.get("baseColorTexture"))?,
+            metallic: pbr
+                .get("metallicFactor")
+                .as_f32()
+                .unwrap_or(defaults.metallic),
+            roughness: pbr
+                .get("roughnessFactor")
+                .as_f32()
+                .unwrap_or(defaults.roughness),
+            metallic_roughness_texture: self.texture_ref(pbr.get("metallicRoughnessTexture"))?,
+            normal_texture: self.texture_ref(normal)?,
+            normal_scale: normal
+                .get("scale")
+                .as_f32()
+                .unwrap_or(defaults.normal_scale),
+            occlusion_texture: self.texture_ref(occlusion)?,
+            occlusion_strength: occlusion
+                .get("strength")
+                .as_f32()
+                .unwrap_or(defaults.occlusion_strength),
+            emissive: color(material.get("emissiveFactor"), defaults.emissive)?,
+            emissive_texture: self.texture_ref(material.get("emissiveTexture"))?,
+            alpha_mode,
+            double_sided: material.get("doubleSided").as_bool().unwrap_or(false),
+        })
+    }
+
+    fn load_texture(&self, texture: &Json) -> Result<GltfTexture, String> {
+        Ok(GltfTexture {
+            name: name(texture),
+            image: self.optional_index(texture.get("source"), "images")?,
+            sampler: self.optional_index(texture.get("sampler"), "samplers")?,
+        })
+    }
+
+    fn load_image(&self, image: &Json, base_dir: &Path) -> Result<GltfImage, String> {
+        let mime_type = image.get("mimeType").as_str().map(str::to_owned);
+        let source = match image.get("uri").as_str() {
+            Some(uri) if uri.starts_with("data:") => {
+                let (uri_mime_type, data) = decode_data_uri(uri)?;
+                ImageSource::Embedded {
+                    mime_type: mime_type.or_else(|| Some(uri_mime_type.to_owned())),
+                    data,
+                }
+            }
+            Some(uri) => ImageSource::Path(base_dir.join(decode_percent(uri))),
+            None => {
+                let view = self.index(image.get("bufferView"), "bufferViews")?;
+                let length = self.json.get("bufferViews").elements()[view]
+                    .get("byteLength")
+                    .as_usize()
+                    .unwrap_or(0);
+                let data = self.read_elements(view, 0, 1, length)?;
+                ImageSource::Embedded { mime_type, data }
+            }
+        };
+        Ok(GltfImage {
+            name: name(image),
+            source,
+        })
+    }
+
+    fn load_nodes(&self) -> Result<Vec<GltfNode>, String> {
+        let nodes = self.load_all("nodes", Self::load_node)?;
+        // The hierarchy must be a forest, so every node has at most one parent
+        let mut has_parent = vec![false; nodes.len()];
+        for (index, node) in nodes.iter().enumerate() {
+            for &child in &node.children {
+                if child == index || has_parent[child] {
+                    return Err(format!("nodes[{child}]: Node has more than one parent"));
+                }
+                has_parent[child] = true;
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn load_node(&self, node: &Json) -> Result<GltfNode, String> {
+        let (scale, rotation, translation) = match node.get("matrix").as_f32_array() {
+            Some(matrix) if matrix.len() == 16 => {
+                Mat4::from_cols_slice(&matrix).to_scale_rotation_translation()
+            }
+            Some(_) => return Err("Invalid matrix".to_owned()),
+            None => {
+                let vector = |key: &str, default: Vec3| match node.get(key).as_f32_array() {
+                    Some(values) if values.len() == 3 => Ok(Vec3::from_slice(&values)),
+                    Some(_) => Err(format!("Invalid {key}")),
+                    None => Ok(default),
+                };
+                let rotation = match node.get("rotation").as_f32_array() {
+                    Some(values) if values.len() == 4 => Quat::from_slice(&values).normalize(),
+                    Some(_) => return Err("Invalid rotation".to_owned()),
+                    None => Quat::IDENTITY,
+                };
+                (
+                    vector("scale", Vec3::ONE)?,
+                    rotation,
+                    vector("translation", Vec3::ZERO)?,
+                )
+            }
+        };
+        let light = node
+            .get("extensions")
+            .get("KHR_lights_punctual")
+            .get("light");
+        let light_count = self
+            .json
+            .get("extensions")
+            .get("KHR_lights_punctual")
+            .get("lights")
+            .elements()
+            .len();
+        let light = match light.as_usize() {
+            Some(light) if light < light_count => Some(light),
+            Some(_) => return Err("Invalid light index".to_owned()),
+            None => None,
+        };
+        Ok(GltfNode {
+            name: name(node),
+            children: self.indices(node.get("children"), "nodes")?,
+            mesh: self.optional_index(node.get("mesh"), "meshes")?,
+            camera: self.optional_index(node.get("camera"), "cameras")?,
+            skin: self.optional_index(node.get("skin"), "skins")?,
+            light,
+            translation,
+            rotation,
+            scale,
+            weights: node.get("weights").as_f32_array().unwrap_or_default(),
+        })
+    }
+
+    fn load_scene(&self, scene: &Json) -> Result<GltfScene, String> {
+        Ok(GltfScene {
+            name: name(scene),
+            nodes: self.indices(scene.get("nodes"), "nodes")?,
+        })
+    }
+
+    fn load_skin(&self, skin: &Json) -> Result<GltfSkin, String> {
+        let joints = self.indices(skin.get("joints"), "nodes")?;
+        let inverse_bind_matrices = if skin.get("inverseBindMatrices").is_null() {
+            vec![Mat4::IDENTITY; joints.len()]
+        } else {
+            let data = self.accessor(skin.get("inverseBindMatrices"))?;
+            if data.components != 16 || data.count < joints.len() {
+                return Err("inverseBindMatrices must hold a MAT4 per joint".to_owned());
+            }
+            data.to_f32()
+                .chunks_exact(16)
+                .map(Mat4::from_cols_slice)
+                .collect()
+        };
+        Ok(GltfSkin {
+            name: name(skin),
+            joints,
+            inverse_bind_matrices,
+            skeleton: self.optional_index(skin.get("skeleton"), "nodes")?,
+        })
+    }
+
+    fn load_animation(&self, animation: &Json) -> Result<GltfAnimation, String> {
+        let samplers = animation.get("samplers").elements();
+        let mut channels = vec![];
+        for channel in animation.get("channels").elements() {
+            let sampler = channel
+                .get("sampler")
+                .as_usize()
+                .and_then(|sampler| samplers.get(sampler))
+                .ok_or_else(|| "Invalid animation sampler index".to_owned())?;
+            let target = channel.get("target");
+            let path = match target.get("path").as_str().unwrap_or("") {
+                "translation" => AnimationPath::Translation,
+                "rotation" => AnimationPath::Rotation,
+                "scale" => AnimationPath::Scale,
+                "weights" => AnimationPath::Weights,
+                path => return Err(format!("Invalid animation path '{path}'")),
+            };
+            let interpolation = match sampler.get("interpolation").as_str().unwrap_or("LINEAR") {
+                "LINEAR" => Interpolation::Linear,
+                "STEP" => Interpolation::Step,
+                "CUBICSPLINE" => Interpolation::CubicSpline,
+                interpolation => {
+                    return Err(format!("Invalid interpolation '{interpolation}'"));
+                }
+            };
+            let input = self.accessor(sampler.get("input"))?;
+            if input.components != 1 {
+                return Err("Animation input must be SCALAR".to_owned());
+            }
+            let output = self.accessor(sampler.get("output"))?;
+            let expected_components = match path {
+                AnimationPath::Translation | AnimationPath::Scale => 3,
+                AnimationPath::Rotation => 4,
+                AnimationPath::Weights => 1,
+            };
+            if output.components != expected_components {
+                return Err(format!(
+                    "Animation output of {path:?} must have {expected_components} components"
+                ));
+            }
+            let channel = GltfChannel {
+                node: self.optional_index(target.get("node"), "nodes")?,
+                path,
+                interpolation,
+                times: input.to_f32(),
+                values: output.to_f32(),
+            };
+            channel.validate()?;
+            channels.push(channel);
+        }
+        Ok(GltfAnimation {
+            name: name(animation),
+            channels,
+        })
+    }
+}
+
+fn load_sampler(sampler: &Json) -> GltfSampler {
+    let defaults = GltfSampler::default();
+    let enum_value = |key: &str| sampler.get(key).as_usize().map(|value| value as GLenum);
+    GltfSampler {
+        mag_filter: enum_value("magFilter"),
+        min_filter: enum_value("minFilter"),
+        wrap_s: enum_value("wrapS").unwrap_or(defaults.wrap_s),
+        wrap_t: enum_value("wrapT").unwrap_or(defaults.wrap_t),
+    }
+}
+
+fn load_camera(camera: &Json) -> Result<GltfCamera, String> {
+    let projection = match camera.get("type").as_str().unwrap_or("") {
+        "perspective" => {
+            let perspective = camera.get("perspective");
+            GltfProjection::Perspective {
+                yfov: perspective
+                    .get("yfov")
+                    .as_f32()
+                    .ok_or_else(|| "Missing yfov".to_owned())?,
+                aspect_ratio: perspective.get("aspectRatio").as_f32(),
+                znear: perspective
+                    .get("znear")
+                    .as_f32()
+                    .ok_or_else(|| "Missing znear".to_owned())?,
+                zfar: perspective.get("zfar").as_f32(),
+            }
+        }
+        "orthographic" => {
+            let orthographic = camera.get("orthographic");
+            let value = |key: &str| {
+                orthographic
+                    .get(key)
+                    .as_f32()
+                    .ok_or_else(|| format!("Missing {key}"))
+            };
+            GltfProjection::Orthographic {
+                xmag: value("xmag")?,
+                ymag: value("ymag")?,
+                znear: value("znear")?,
+                zfar: value("zfar")?,
+            }
+        }
+        camera_type => return Err(format!("Invalid camera type '{camera_type}'")),
+    };
+    Ok(GltfCamera {
+        name: name(camera),
+        projection,
+    })
+}
+
+fn load_light(light: &Json) -> Result<GltfLight, String> {
+    let kind = match light.get("type").as_str().unwrap_or("") {
+        "directional" => LightKind::Directional,
+        "point" => LightKind::Point,
+        "spot" => {
+            let spot = light.get("spot");
+            LightKind::Spot {
+                inner_cone_angle: spot.get("innerConeAngle").as_f32().unwrap_or(0.0),
+                outer_cone_angle: spot
+                    .get("outerConeAngle")
+                    .as_f32()
+                    .unwrap_or(std::f32::consts::FRAC_PI_4),
+            }
+        }
+        light_type => return Err(format!("Invalid light type '{light_type}'")),
+    };
+    Ok(GltfLight {
+        name: name(light),
+        kind,
+        color: color(light.get("color"), Color::new(1.0, 1.0, 1.0, 1.0))?,
+        intensity: light.get("intensity").as_f32().unwrap_or(1.0),
+        range: light.get("range").as_f32(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Binary glTF with `json` and the floats of `data` as the buffer
+    fn glb(json: &str, data: &[f32]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let binary: Vec<u8> = data.iter().flat_map(|value| value.to_le_bytes()).collect();
+        let length = 12 + 8 + json.len() + 8 + binary.len();
+        let mut bytes = GLB_MAGIC.to_vec();
+        for value in [2, length] {
+            bytes.extend((value as u32).to_le_bytes());
+        }
+        for (chunk_type, chunk) in [(GLB_CHUNK_JSON, &json), (GLB_CHUNK_BIN, &binary)] {
+            bytes.extend((chunk.len() as u32).to_le_bytes());
+            bytes.extend((chunk_type as u32).to_le_bytes());
+            bytes.extend(chunk);
+        }
+        bytes
+    }
+
+    // Rotation animation of node 0 with the times and quaternions in the buffer
+    fn animation(times: &[f32], output_count: usize, output_type: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"byteLength": {length}}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": {times_length}}},
+                    {{"buffer": 0, "byteOffset": {times_length}, "byteLength": {values_length}}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": {count}, "type": "SCALAR"}},
+                    {{"bufferView": 1, "componentType": 5126, "count": {output_count},
+                      "type": "{output_type}"}}
+                ],
+                "nodes": [{{}}],
+                "animations": [{{
+                    "samplers": [{{"input": 0, "output": 1}}],
+                    "channels": [{{"sampler": 0, "target": {{"node": 0, "path": "rotation"}}}}]
+                }}]
+            }}"#,
+            length = (times.len() + output_count * 4) * 4,
+            times_length = times.len() * 4,
+            values_length = output_count * 4 * 4,
+            count = times.len(),
+        )
+    }
+
+    fn load(json: &str, data: &[f32]) -> Result<GltfDocument, String> {
+        GltfDocument::from_slice(&glb(json, data), Path::new(""))
+    }
+
+    #[test]
+    fn sample_rotation() {
+        let quarter = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let mut data = vec![0.0, 2.0];
+        data.extend(Quat::IDENTITY.to_array());
+        data.extend(quarter.to_array());
+        let document = load(&animation(&[0.0, 2.0], 2, "VEC4"), &data).unwrap();
+        let channel = &document.animations[0].channels[0];
+        assert_eq!(channel.value_size(), 4);
+        assert_eq!(document.animations[0].duration(), 2.0);
+        let halfway = Quat::from_slice(&channel.sample(1.0));
+        assert!(halfway.abs_diff_eq(Quat::IDENTITY.slerp(quarter, 0.5), 1e-5));
+        assert_eq!(channel.sample(-1.0), Quat::IDENTITY.to_array());
+        assert_eq!(channel.sample(5.0), quarter.to_array());
+    }
+
+    #[test]
+    fn reject_malformed_animations() {
+        let identity = Quat::IDENTITY.to_array();
+        let error = |json: &str, data: &[f32]| load(json, data).unwrap_err();
+        let data = [[1.0, 1.0].as_slice(), &identity, &identity].concat();
+        assert!(error(&animation(&[1.0, 1.0], 2, "VEC4"), &data).contains("increasing"));
+        let data = [[0.0, 1.0].as_slice(), &identity].concat();
+        assert!(error(&animation(&[0.0, 1.0], 1, "VEC4"), &data).contains("animation values"));
+        // Translations for a rotation channel, the buffer is sized for 2 rotations
+        let data = [[0.0, 1.0].as_slice(), &identity, &identity].concat();
+        assert!(error(&animation(&[0.0, 1.0], 2, "VEC3"), &data).contains("4 components"));
+    }
+
+    #[test]
+    fn invalid_channels_sample_nothing() {
+        let channel = GltfChannel {
+            node: None,
+            path: AnimationPath::Rotation,
+            interpolation: Interpolation::CubicSpline,
+            times: vec![0.0, 1.0],
+            values: vec![0.0; 8],
+        };
+        assert!(channel.validate().is_err());
+        assert!(channel.sample(0.5).is_empty());
+        let empty = GltfChannel {
+            times: vec![],
+            values: vec![],
+            ..channel
+        };
+        assert!(empty.validate().is_err());
+        assert!(empty.sample(0.5).is_empty());
+    }
+
+    #[test]
+    fn reject_malformed_documents() {
+        for source in [
+            "",
+            "{",
+            r#"{"asset": {"version": "1.0"}}"#,
+            r#"{"asset": {"version": "2.0"}, "extensionsRequired": ["EXT_unknown"]}"#,
+            r#"{"asset": {"version": "2.0"}, "scenes": [{"nodes": [3]}]}"#,
+            r#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 4}]}"#,
+            r#"{"asset": {"version": "2.0"}, "buffers": [{"uri": "data:;base64,@@"}]}"#,
+        ] {
+            assert!(
+                GltfDocument::from_slice(source.as_bytes(), Path::new("")).is_err(),
+                "{source}"
+            );
+        }
+        let mut truncated = glb(r#"{"asset": {"version": "2.0"}}"#, &[]);
+        truncated.truncate(24);
+        assert!(GltfDocument::from_slice(&truncated, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn reject_oversized_accessors() {
+        let mesh = |count: &str, view: &str| {
+            format!(
+                r#"{{
+                    "asset": {{"version": "2.0"}},
+                    "buffers": [{{"byteLength": 12}}],
+                    "bufferViews": [{{"buffer": 0, "byteLength": 12 {view}}}],
+                    "accessors": [{{{count}, "componentType": 5126, "type": "VEC3"}}],
+                    "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}}}]}}]
+                }}"#
+            )
+        };
+        let error = |count: &str, view: &str| load(&mesh(count, view), &[0.0; 3]).unwrap_err();
+        let document = load(&mesh(r#""bufferView": 0, "count": 1"#, ""), &[0.0; 3]).unwrap();
+        assert_eq!(document.meshes[0].primitives[0].attributes[0].1.count, 1);
+        assert!(error(r#""bufferView": 0, "count": 2"#, "").contains("past the end"));
+        assert!(error(r#""bufferView": 0, "count": 1e300"#, "").contains("past the end"));
+        assert!(error(r#""count": 1e300"#, "").contains("too many"));
+        let stride = r#", "byteStride": 4"#;
+        assert!(error(r#""bufferView": 0, "count": 1"#, stride).contains("stride"));
+        let offset = r#", "byteOffset": 1e300"#;
+        assert!(error(r#""bufferView": 0, "count": 1"#, offset).contains("outside"));
+        assert!(document.to_mesh(1).is_err());
+    }
+
+    #[test]
+    fn infinite_projection_uses_gl_depth() {
+        let camera = |zfar| GltfCamera {
+            name: String::new(),
+            projection: GltfProjection::Perspective {
+                yfov: 1.0,
+                aspect_ratio: None,
+                znear: 0.1,
+                zfar,
+            },
+        };
+        let infinite = camera(None).projection_matrix(1.5);
+        let far = camera(Some(1.0e7)).projection_matrix(1.5);
+        assert!(infinite.abs_diff_eq(far, 1e-4));
+        // The near plane maps to -1 and infinity to 1
+        let near = infinite.project_point3(Vec3::new(0.0, 0.0, -0.1));
+        assert!((near.z + 1.0).abs() < 1e-5);
+        let distant = infinite.project_point3(Vec3::new(0.0, 0.0, -1.0e9));
+        assert!((distant.z - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,345 @@
+// Minimal JSON reader, enough for the importers. Numbers are kept as f64
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Self>),
+    Object(Vec<(String, Self)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            bytes: source.as_bytes(),
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    // Member of an object, Null when missing or when this is not an object
+    pub fn get(&self, key: &str) -> &Self {
+        match self {
+            Self::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    pub const fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    pub const fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub const fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        self.as_f64().map(|value| value as f32)
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|value| *value >= 0.0 && value.fract() == 0.0)
+            .map(|value| value as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Elements of an array, empty when missing or when this is not an array
+    pub fn elements(&self) -> &[Self] {
+        match self {
+            Self::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+
+    pub fn members(&self) -> &[(String, Self)] {
+        match self {
+            Self::Object(members) => members,
+            _ => &[],
+        }
+    }
+
+    pub fn as_f32_array(&self) -> Option<Vec<f32>> {
+        match self {
+            Self::Array(elements) => elements.iter().map(Self::as_f32).collect(),
+            _ => None,
+        }
+    }
+}
+
+// Arrays and objects nested deeper than this are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // Arrays and objects currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("JSON error at byte {}: {message}", self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.parse_literal("null", Json::Null),
+            Some(b't') => self.parse_literal("true", Json::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b'[') => self.nested(Self::parse_array),
+            Some(b'{') => self.nested(Self::parse_object),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.position += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => bytes.push(escape),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let character = self.parse_unicode_escape()?;
+                            let mut buffer = [0; 4];
+                            bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            // Surrogate pair, the low half must follow as another \u escape
+            if !self.bytes[self.position..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.position += 2;
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let json =
+            Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d\n\u00e9\ud83d\ude00"}} "#)
+                .unwrap();
+        assert_eq!(json.get("a").elements().len(), 4);
+        assert_eq!(json.get("a").elements()[1].as_f64(), Some(-25.0));
+        assert_eq!(json.get("a").elements()[2].as_bool(), Some(true));
+        assert!(json.get("a").elements()[3].is_null());
+        assert_eq!(json.get("b").get("c").as_str(), Some("d\né😀"));
+        assert!(json.get("missing").get("deeper").is_null());
+    }
+
+    #[test]
+    fn reject_malformed_input() {
+        for source in [
+            "",
+            "{",
+            "[1, 2",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{a: 1}",
+            "[1,]",
+            "tru",
+            "\"unterminated",
+            "\"bad \\x escape\"",
+            "\"\\u12\"",
+            "1.2.3",
+            "[] []",
+        ] {
+            assert!(Json::parse(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn reject_unpaired_surrogates() {
+        assert!(Json::parse(r#""\ud83d""#).is_err());
+        assert!(Json::parse(r#""\ud83d\u0041""#).is_err());
+        assert!(Json::parse(r#""\ud83d\ud83d""#).is_err());
+    }
+
+    #[test]
+    fn limit_nesting_depth() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Json::parse(&"{\"a\":".repeat(100_000)).is_err());
+    }
+}
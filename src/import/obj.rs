@@ -0,0 +1,623 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::{
+    core::color::Color,
+    geometry::{
+        drawcall::{DrawCall, Primitive},
+        mesh::Mesh,
+        primitives::{MeshData, PrimitiveVertex},
+        vertex_layout::Vertex,
+    },
+};
+
+// Material parsed from a .mtl library, texture paths are resolved relative to the library
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Color,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub emissive: Color,
+    pub shininess: f32,
+    pub opacity: f32,
+    pub optical_density: f32,
+    pub illumination_model: u32,
+    pub ambient_texture: Option<PathBuf>,
+    pub diffuse_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    pub emissive_texture: Option<PathBuf>,
+    pub alpha_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ambient: Color::new(0.0, 0.0, 0.0, 1.0),
+            diffuse: Color::new(0.8, 0.8, 0.8, 1.0),
+            specular: Color::new(0.0, 0.0, 0.0, 1.0),
+            emissive: Color::new(0.0, 0.0, 0.0, 1.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            optical_density: 1.0,
+            illumination_model: 2,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            emissive_texture: None,
+            alpha_texture: None,
+            normal_texture: None,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Self>, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+        Self::from_source(&source, path.parent().unwrap_or_else(|| Path::new("")))
+            .map_err(|error| format!("{}:{error}", path.display()))
+    }
+
+    // Parse all the materials in a .mtl library, errors are prefixed with the line number
+    pub fn from_source(source: &str, base_dir: &Path) -> Result<Vec<Self>, String> {
+        let mut materials: Vec<Self> = vec![];
+        for (number, line) in source.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            if keyword.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("{}: {message} in '{}'", number + 1, line.trim());
+            if keyword == "newmtl" {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if name.is_empty() {
+                    return Err(error("missing material name"));
+                }
+                materials.push(Self::new(&name));
+                continue;
+            }
+            let Some(material) = materials.last_mut() else {
+                return Err(error("statement before newmtl"));
+            };
+            let arguments: Vec<&str> = tokens.collect();
+            match keyword {
+                "Ka" => material.ambient = parse_color(&arguments).map_err(|e| error(&e))?,
+                "Kd" => material.diffuse = parse_color(&arguments).map_err(|e| error(&e))?,
+                "Ks" => material.specular = parse_color(&arguments).map_err(|e| error(&e))?,
+                "Ke" => material.emissive = parse_color(&arguments).map_err(|e| error(&e))?,
+                "Ns" => material.shininess = parse_scalar(&arguments).map_err(|e| error(&e))?,
+                "Ni" => {
+                    material.optical_density = parse_scalar(&arguments).map_err(|e| error(&e))?;
+                }
+                "d" => material.opacity = parse_scalar(&arguments).map_err(|e| error(&e))?,
+                "Tr" => {
+                    material.opacity =
+                        1.0 - parse_scalar::<f32>(&arguments).map_err(|e| error(&e))?;
+                }
+                "illum" => {
+                    material.illumination_model =
+                        parse_scalar::<u32>(&arguments).map_err(|e| error(&e))?;
+                }
+                "map_Ka" | "map_Kd" | "map_Ks" | "map_Ke" | "map_d" | "map_Bump" | "map_bump"
+                | "bump" | "norm" => {
+                    let file = texture_path(&arguments);
+                    if file.is_empty() {
+                        return Err(error("missing texture path"));
+                    }
+                    let path = Some(base_dir.join(file));
+                    match keyword {
+                        "map_Ka" => material.ambient_texture = path,
+                        "map_Kd" => material.diffuse_texture = path,
+                        "map_Ks" => material.specular_texture = path,
+                        "map_Ke" => material.emissive_texture = path,
+                        "map_d" => material.alpha_texture = path,
+                        _ => material.normal_texture = path,
+                    }
+                }
+                // Other statements do not map to anything we can render
+                _ => {}
+            }
+        }
+        Ok(materials)
+    }
+}
+
+// Faces using the same material, drawn as one submesh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjGroup {
+    pub material: Option<usize>,
+    pub first_index: usize,
+    pub index_count: usize,
+}
+
+// Triangulated model with de-duplicated interleaved vertices
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    pub data: MeshData,
+    // Colors given after the positions as in "v x y z r g b", one per vertex of `data` and
+    // white where missing. Empty when the file has none
+    pub colors: Vec<Color>,
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    // Load an .obj file and the .mtl libraries it references
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::from_source(&source, |library| {
+            ObjMaterial::from_file(base_dir.join(library))
+        })
+        .map_err(|error| format!("{}:{error}", path.display()))
+    }
+
+    // Parse an .obj source, `load_library` is called with each mtllib name
+    pub fn from_source(
+        source: &str,
+        mut load_library: impl FnMut(&str) -> Result<Vec<ObjMaterial>, String>,
+    ) -> Result<Self, String> {
+        let mut parser = Parser::default();
+        for (number, line) in source.lines().enumerate() {
+            parser
+                .parse_line(line, &mut load_library)
+                .map_err(|message| format!("{}: {message} in '{}'", number + 1, line.trim()))?;
+        }
+        Ok(parser.finish())
+    }
+
+    // Mesh with a single VAO and one submesh per group, using the material index as slot.
+    // Groups without material use the slot after the last material. Vertex colors, if any,
+    // go to location 4 after the `PrimitiveVertex` attributes, like COLOR_0 in glTF
+    #[must_use]
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new();
+        let (vbo, layout) = if self.colors.is_empty() {
+            let vbo = mesh.add_vertex_data(&self.data.vertices);
+            (vbo, PrimitiveVertex::layout())
+        } else {
+            let vbo = mesh.add_vertex_data(&self.colored_vertices());
+            (vbo, ColoredVertex::layout())
+        };
+        let ebo = mesh.add_element_data(&self.data.indices);
+        let vao = mesh.add_vertex_array_from_layout(&layout, vbo, Some(ebo));
+        for group in &self.groups {
+            let draw_call = DrawCall::count(
+                DrawCall::first(
+                    DrawCall::index_type(DrawCall::new(Primitive::Triangles), mesh.index_type(ebo)),
+                    (group.first_index * size_of::<u32>()) as i32,
+                ),
+                group.index_count as i32,
+            );
+            let material = group.material.unwrap_or(self.materials.len());
+            mesh.add_submesh(vao, draw_call, material);
+        }
+        mesh
+    }
+
+    fn colored_vertices(&self) -> Vec<ColoredVertex> {
+        self.data
+            .vertices
+            .iter()
+            .zip(&self.colors)
+            .map(|(vertex, &color)| ColoredVertex {
+                position: vertex.position,
+                normal: vertex.normal,
+                uv: vertex.uv,
+                tangent: vertex.tangent,
+                color,
+            })
+            .collect()
+    }
+}
+
+// `PrimitiveVertex` followed by the vertex color
+#[derive(Clone, Copy, Debug, Default, PartialEq, Vertex)]
+#[repr(C)]
+struct ColoredVertex {
+    position: Vec3,
+    normal: Vec3,
+    uv: Vec2,
+    tangent: Vec4,
+    color: Color,
+}
+
+#[derive(Default)]
+struct Parser {
+    positions: Vec<Vec3>,
+    // One per position once any position has a color
+    colors: Vec<Option<Color>>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    materials: Vec<ObjMaterial>,
+    current_material: Option<usize>,
+    // Vertex index for each (position, uv, normal) combination already emitted
+    vertex_map: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    vertex_positions: Vec<usize>,
+    missing_normals: bool,
+    data: MeshData,
+    groups: Vec<ObjGroup>,
+}
+
+impl Parser {
+    fn parse_line(
+        &mut self,
+        line: &str,
+        load_library: &mut impl FnMut(&str) -> Result<Vec<ObjMaterial>, String>,
+    ) -> Result<(), String> {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+        let arguments: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => {
+                // x y z, with w or with r g b
+                let values = parse_floats(&arguments, 3, 6)?;
+                if values.len() == 5 {
+                    return Err("expected 3, 4 or 6 numbers, found 5".to_owned());
+                }
+                let color =
+                    (values.len() == 6).then(|| Color::rgb(values[3], values[4], values[5]));
+                if color.is_some() || !self.colors.is_empty() {
+                    self.colors.resize(self.positions.len(), None);
+                    self.colors.push(color);
+                }
+                self.positions
+                    .push(Vec3::new(values[0], values[1], values[2]));
+            }
+            "vt" => {
+                let values = parse_floats(&arguments, 1, 3)?;
+                self.uvs
+                    .push(Vec2::new(values[0], values.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let values = parse_floats(&arguments, 3, 3)?;
+                self.normals
+                    .push(Vec3::new(values[0], values[1], values[2]).normalize_or_zero());
+            }
+            "f" => self.parse_face(&arguments)?,
+            "usemtl" => {
+                let name = arguments.join(" ");
+                let material = self
+                    .materials
+                    .iter()
+                    .position(|material| material.name == name)
+                    .unwrap_or_else(|| {
+                        // Keep the faces apart with a default material, later uses find it
+                        log::warn!("Unknown material '{name}', using a default one");
+                        self.materials.push(ObjMaterial::new(&name));
+                        self.materials.len() - 1
+                    });
+                self.current_material = Some(material);
+            }
+            "mtllib" => {
+                // A missing library only loses the materials, unknown names are handled above
+                for library in arguments {
+                    match load_library(library) {
+                        Ok(materials) => self.materials.extend(materials),
+                        Err(error) => log::warn!("Skipping material library {library}: {error}"),
+                    }
+                }
+            }
+            // Objects, groups, smoothing groups, lines and points do not change the result
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, arguments: &[&str]) -> Result<(), String> {
+        if arguments.len() < 3 {
+            return Err("face with less than 3 vertices".to_owned());
+        }
+        let mut corners = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            corners.push(self.parse_corner(argument)?);
+        }
+
+        // Continue the group if the material did not change since the last face
+        let index_count = self.data.indices.len();
+        match self.groups.last_mut() {
+            Some(group) if group.material == self.current_material => {}
+            _ => self.groups.push(ObjGroup {
+                material: self.current_material,
+                first_index: index_count,
+                index_count: 0,
+            }),
+        }
+
+        // Triangulate convex polygons as a fan
+        for i in 1..corners.len() - 1 {
+            self.data
+                .indices
+                .extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+        }
+        if let Some(group) = self.groups.last_mut() {
+            group.index_count += 3 * (corners.len() - 2);
+        }
+        Ok(())
+    }
+
+    // Parse v, v/vt, v//vn or v/vt/vn and return the index of the matching vertex
+    fn parse_corner(&mut self, corner: &str) -> Result<u32, String> {
+        let mut parts = corner.split('/');
+        let position = resolve_index(parts.next(), self.positions.len(), "position")?
+            .ok_or_else(|| format!("missing position index in '{corner}'"))?;
+        let uv = resolve_index(parts.next(), self.uvs.len(), "texture coordinate")?;
+        let normal = resolve_index(parts.next(), self.normals.len(), "normal")?;
+        if parts.next().is_some() {
+            return Err(format!("too many indices in '{corner}'"));
+        }
+
+        let key = (position, uv, normal);
+        if let Some(&index) = self.vertex_map.get(&key) {
+            return Ok(index);
+        }
+        self.missing_normals |= normal.is_none();
+        self.data.vertices.push(PrimitiveVertex {
+            position: self.positions[position],
+            normal: normal.map_or(Vec3::ZERO, |normal| self.normals[normal]),
+            uv: uv.map_or(Vec2::ZERO, |uv| self.uvs[uv]),
+            tangent: Vec4::ZERO,
+        });
+        self.vertex_positions.push(position);
+        let index = (self.data.vertices.len() - 1) as u32;
+        self.vertex_map.insert(key, index);
+        Ok(index)
+    }
+
+    fn finish(mut self) -> ObjModel {
+        if self.missing_normals {
+            self.generate_normals();
+        }
+        let colors = if self.colors.is_empty() {
+            vec![]
+        } else {
+            self.vertex_positions
+                .iter()
+                .map(|&position| {
+                    self.colors
+                        .get(position)
+                        .copied()
+                        .flatten()
+                        .unwrap_or(Color::WHITE)
+                })
+                .collect()
+        };
+        ObjModel {
+            data: self.data,
+            colors,
+            groups: self.groups,
+            materials: self.materials,
+        }
+    }
+
+    // Smooth normals for the vertices that had none, area weighted and shared by position
+    fn generate_normals(&mut self) {
+        let mut accumulated = vec![Vec3::ZERO; self.positions.len()];
+        for triangle in self.data.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+                .map(|index| self.data.vertices[index as usize].position);
+            let normal = (b - a).cross(c - a);
+            for &index in triangle {
+                accumulated[self.vertex_positions[index as usize]] += normal;
+            }
+        }
+        for (vertex, position) in self.data.vertices.iter_mut().zip(&self.vertex_positions) {
+            if vertex.normal == Vec3::ZERO {
+                vertex.normal = accumulated[*position].normalize_or_zero();
+            }
+        }
+    }
+}
+
+// OBJ indices are 1-based, negative values count back from the last element
+fn resolve_index(token: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        return Ok(None);
+    };
+    let value: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid {kind} index '{token}'"))?;
+    let index = match value {
+        0 => None,
+        value if value > 0 => Some(value as usize - 1),
+        value => (count as i64 + value).try_into().ok(),
+    };
+    match index {
+        Some(index) if index < count => Ok(Some(index)),
+        _ => Err(format!(
+            "{kind} index {value} out of range, only {count} defined"
+        )),
+    }
+}
+
+fn parse_floats(arguments: &[&str], min: usize, max: usize) -> Result<Vec<f32>, String> {
+    if arguments.len() < min || arguments.len() > max {
+        return Err(format!(
+            "expected {min} to {max} numbers, found {}",
+            arguments.len()
+        ));
+    }
+    arguments
+        .iter()
+        .map(|argument| {
+            argument
+                .parse()
+                .map_err(|_| format!("invalid number '{argument}'"))
+        })
+        .collect()
+}
+
+// File name of a map statement, after options such as -bm 1.0 or -o 0 0 0. Names may contain
+// spaces
+fn texture_path(arguments: &[&str]) -> String {
+    let mut remaining = arguments;
+    while let Some((option, rest)) = remaining.split_first() {
+        let values = match *option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan"
+            | "-texres" | "-type" => 1,
+            "-mm" => 2,
+            // Up to three numbers
+            "-o" | "-s" | "-t" => rest
+                .iter()
+                .take(3)
+                .take_while(|value| value.parse::<f32>().is_ok())
+                .count(),
+            _ => break,
+        };
+        remaining = rest.get(values..).unwrap_or_default();
+    }
+    remaining.join(" ")
+}
+
+fn parse_scalar<T: std::str::FromStr>(arguments: &[&str]) -> Result<T, String> {
+    let [argument] = arguments else {
+        return Err(format!("expected 1 value, found {}", arguments.len()));
+    };
+    argument
+        .parse()
+        .map_err(|_| format!("invalid value '{argument}'"))
+}
+
+fn parse_color(arguments: &[&str]) -> Result<Color, String> {
+    let values = parse_floats(arguments, 1, 3)?;
+    // A single value is used for all channels
+    let value = |i: usize| values.get(i).copied().unwrap_or(values[0]);
+    Ok(Color::new(value(0), value(1), value(2), 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ObjModel, String> {
+        ObjModel::from_source(source, |_| Ok(vec![]))
+    }
+
+    #[test]
+    fn parse_quad() {
+        let model = parse(
+            "# quad\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1 -1/1/1\n",
+        )
+        .unwrap();
+        assert_eq!(model.data.vertices.len(), 4);
+        assert_eq!(model.data.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].index_count, 6);
+    }
+
+    #[test]
+    fn generate_missing_normals() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert!(model
+            .data
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == Vec3::Z));
+    }
+
+    #[test]
+    fn reject_malformed_obj() {
+        for (source, line) in [
+            ("v 1 2\n", 1),
+            ("v 1 2 x\n", 1),
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3),
+            ("v 0 0 0\nf 1 2 3\n", 2),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n", 4),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n", 4),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2 3\n", 4),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/// 2 3\n", 4),
+        ] {
+            let error = parse(source).unwrap_err();
+            assert!(
+                error.starts_with(&format!("{line}:")),
+                "{source:?}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_vertex_colors() {
+        let model = parse("v 0 0 0 1 0 0\nv 1 0 0\nv 0 1 0 0 0 1\nf 1 2 3\n").unwrap();
+        assert_eq!(model.colors, [Color::RED, Color::WHITE, Color::BLUE]);
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n")
+            .unwrap()
+            .colors
+            .is_empty());
+        assert!(parse("v 0 0 0 1 0\n").is_err());
+
+        // Colors follow the other attributes of each vertex
+        let vertices = model.colored_vertices();
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(vertices[2].color, Color::BLUE);
+        assert_eq!(vertices[2].position, model.data.vertices[2].position);
+        let layout = ColoredVertex::layout();
+        let (location, color) = layout.attributes()[4];
+        assert_eq!(location, 4);
+        assert_eq!(
+            color.offset() as usize,
+            std::mem::offset_of!(ColoredVertex, color)
+        );
+    }
+
+    #[test]
+    fn unknown_materials_get_a_default() {
+        let source = "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n\
+                      usemtl blue\nf 3 2 1\nusemtl red\nf 1 3 2\n";
+        let model = ObjModel::from_source(source, |name| Err(format!("{name} not found"))).unwrap();
+        let names: Vec<&str> = model.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["red", "blue"]);
+        let materials: Vec<_> = model.groups.iter().map(|group| group.material).collect();
+        assert_eq!(materials, [Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn texture_paths_keep_spaces() {
+        let source =
+            "newmtl a\nmap_Kd -o 0.5 0.5 -bm 2 my texture.png\nbump -mm 0 1 normal map.png\n\
+                      map_Ks -o 1 spec.png\n";
+        let material = &ObjMaterial::from_source(source, Path::new("dir")).unwrap()[0];
+        assert_eq!(
+            material.diffuse_texture,
+            Some(PathBuf::from("dir/my texture.png"))
+        );
+        assert_eq!(
+            material.normal_texture,
+            Some(PathBuf::from("dir/normal map.png"))
+        );
+        assert_eq!(
+            material.specular_texture,
+            Some(PathBuf::from("dir/spec.png"))
+        );
+        assert!(ObjMaterial::from_source("newmtl a\nmap_Kd -bm 2\n", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn reject_malformed_mtl() {
+        let base_dir = Path::new("");
+        for source in [
+            "Kd 1 1 1\n",
+            "newmtl\n",
+            "newmtl a\nKd 1 x 1\n",
+            "newmtl a\nmap_Kd\n",
+        ] {
+            assert!(
+                ObjMaterial::from_source(source, base_dir).is_err(),
+                "{source:?}"
+            );
+        }
+    }
+}
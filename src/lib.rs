@@ -1,5 +1,14 @@
+// Lets #[derive(Vertex)] refer to ::itugl from inside this crate
+extern crate self as itugl;
+
 pub mod application;
+pub mod camera;
 pub mod core;
 pub mod error;
 pub mod geometry;
+pub mod import;
+pub mod material;
+pub mod random;
+pub mod renderer;
+pub mod scene;
 pub mod shader;
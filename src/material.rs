@@ -0,0 +1,269 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use gl::types::{GLenum, GLuint};
+use glam::{Mat2, Mat3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::{
+    core::{color::Color, texture::Texture},
+    shader::{Program, Uniform},
+};
+
+// Value of a material property, uploaded to the uniform with the same name
+#[derive(Clone, Debug)]
+pub enum Property {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Bool(bool),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat2(Mat2),
+    Mat3(Mat3),
+    Mat4(Mat4),
+    // Fits both vec3 and vec4 uniforms, alpha is dropped for vec3
+    Color(Color),
+    // Bound to a texture unit chosen when the material is applied
+    Texture(Rc<Texture>),
+}
+
+impl Property {
+    // Whether this value can be uploaded to a uniform of GL type `kind`
+    #[must_use]
+    pub fn matches(&self, kind: GLenum) -> bool {
+        match self {
+            Self::Float(_) => kind == gl::FLOAT,
+            Self::Int(_) => matches!(kind, gl::INT | gl::BOOL),
+            Self::UInt(_) => matches!(kind, gl::UNSIGNED_INT | gl::BOOL),
+            Self::Bool(_) => kind == gl::BOOL,
+            Self::Vec2(_) => kind == gl::FLOAT_VEC2,
+            Self::Vec3(_) => kind == gl::FLOAT_VEC3,
+            Self::Vec4(_) => kind == gl::FLOAT_VEC4,
+            Self::Mat2(_) => kind == gl::FLOAT_MAT2,
+            Self::Mat3(_) => kind == gl::FLOAT_MAT3,
+            Self::Mat4(_) => kind == gl::FLOAT_MAT4,
+            Self::Color(_) => matches!(kind, gl::FLOAT_VEC3 | gl::FLOAT_VEC4),
+            Self::Texture(texture) => texture.target().matches_sampler(kind),
+        }
+    }
+
+    // The program must be in use, textures take `unit` which is then advanced
+    pub(crate) fn upload(&self, program: &Program, uniform: &Uniform, unit: &mut GLuint) {
+        let location = uniform.location;
+        match self {
+            Self::Float(value) => program.set_uniform1f(location, *value),
+            Self::Int(value) => program.set_uniform1i(location, *value),
+            Self::UInt(value) => program.set_uniform1ui(location, *value),
+            Self::Bool(value) => program.set_uniform1i(location, i32::from(*value)),
+            Self::Vec2(value) => program.set_uniform2f(location, value.x, value.y),
+            Self::Vec3(value) => program.set_uniform3f(location, value.x, value.y, value.z),
+            Self::Vec4(value) => {
+                program.set_uniform4f(location, value.x, value.y, value.z, value.w);
+            }
+            Self::Mat2(value) => program.set_uniform_matrix2f(location, &value.to_cols_array()),
+            Self::Mat3(value) => program.set_uniform_matrix3f(location, &value.to_cols_array()),
+            Self::Mat4(value) => program.set_uniform_matrix4f(location, &value.to_cols_array()),
+            Self::Color(color) => {
+                if uniform.kind == gl::FLOAT_VEC3 {
+                    program.set_uniform3f(location, color.r, color.g, color.b);
+                } else {
+                    program.set_uniform4f(location, color.r, color.g, color.b, color.a);
+                }
+            }
+            Self::Texture(texture) => {
+                texture.bind_to_unit(*unit);
+                program.set_uniform1i(location, *unit as i32);
+                *unit += 1;
+            }
+        }
+    }
+}
+
+impl From<f32> for Property {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+impl From<i32> for Property {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<u32> for Property {
+    fn from(value: u32) -> Self {
+        Self::UInt(value)
+    }
+}
+impl From<bool> for Property {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<Vec2> for Property {
+    fn from(value: Vec2) -> Self {
+        Self::Vec2(value)
+    }
+}
+impl From<Vec3> for Property {
+    fn from(value: Vec3) -> Self {
+        Self::Vec3(value)
+    }
+}
+impl From<Vec4> for Property {
+    fn from(value: Vec4) -> Self {
+        Self::Vec4(value)
+    }
+}
+impl From<Mat2> for Property {
+    fn from(value: Mat2) -> Self {
+        Self::Mat2(value)
+    }
+}
+impl From<Mat3> for Property {
+    fn from(value: Mat3) -> Self {
+        Self::Mat3(value)
+    }
+}
+impl From<Mat4> for Property {
+    fn from(value: Mat4) -> Self {
+        Self::Mat4(value)
+    }
+}
+impl From<Color> for Property {
+    fn from(value: Color) -> Self {
+        Self::Color(value)
+    }
+}
+impl From<Rc<Texture>> for Property {
+    fn from(value: Rc<Texture>) -> Self {
+        Self::Texture(value)
+    }
+}
+
+// A program with the uniform values to draw with. Instances share the program of their parent
+// and only store the properties they override, changes to the parent show through the rest
+#[derive(Debug)]
+pub struct Material {
+    program: Rc<Program>,
+    parent: Option<Rc<Self>>,
+    properties: BTreeMap<String, Property>,
+    // None inherits from the parent, opaque by default
+    transparent: Option<bool>,
+}
+
+impl Material {
+    #[must_use]
+    pub const fn new(program: Rc<Program>) -> Self {
+        Self {
+            program,
+            parent: None,
+            properties: BTreeMap::new(),
+            transparent: None,
+        }
+    }
+
+    #[must_use]
+    pub fn instance(parent: &Rc<Self>) -> Self {
+        Self {
+            program: Rc::clone(&parent.program),
+            parent: Some(Rc::clone(parent)),
+            properties: BTreeMap::new(),
+            transparent: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn program(&self) -> &Rc<Program> {
+        &self.program
+    }
+
+    #[must_use]
+    pub const fn parent(&self) -> Option<&Rc<Self>> {
+        self.parent.as_ref()
+    }
+
+    // Transparent materials are blended and drawn after the opaque ones, back to front
+    #[must_use]
+    pub const fn with_transparency(mut self, transparent: bool) -> Self {
+        self.transparent = Some(transparent);
+        self
+    }
+
+    pub const fn set_transparency(&mut self, transparent: bool) {
+        self.transparent = Some(transparent);
+    }
+
+    #[must_use]
+    pub fn is_transparent(&self) -> bool {
+        self.transparent.unwrap_or_else(|| {
+            self.parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_transparent())
+        })
+    }
+
+    // Fails if the program has no active uniform `name` or its type does not match the value
+    pub fn set(&mut self, name: &str, value: impl Into<Property>) -> Result<(), String> {
+        let value = value.into();
+        let uniform = self
+            .program
+            .uniform(name)
+            .ok_or_else(|| format!("Program has no active uniform {name}"))?;
+        if !value.matches(uniform.kind) {
+            return Err(format!(
+                "Uniform {name} of type {:#06x} cannot be set to {value:?}",
+                uniform.kind
+            ));
+        }
+        self.properties.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    pub fn with(mut self, name: &str, value: impl Into<Property>) -> Result<Self, String> {
+        self.set(name, value)?;
+        Ok(self)
+    }
+
+    // Drop the value set on this material, instances fall back to their parent again
+    pub fn reset(&mut self, name: &str) -> Option<Property> {
+        self.properties.remove(name)
+    }
+
+    // Value set on this material or inherited from its parents
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .get(name)
+            .or_else(|| self.parent.as_ref()?.get(name))
+    }
+
+    #[must_use]
+    pub fn overrides(&self, name: &str) -> bool {
+        self.properties.contains_key(name)
+    }
+
+    // All values this material draws with, sorted by name
+    #[must_use]
+    pub fn properties(&self) -> BTreeMap<&str, &Property> {
+        let mut properties = self
+            .parent
+            .as_ref()
+            .map_or_else(BTreeMap::new, |parent| parent.properties());
+        for (name, value) in &self.properties {
+            properties.insert(name, value);
+        }
+        properties
+    }
+
+    // Use the program and upload every property, textures take units from 0 in name order
+    pub fn apply(&self) {
+        self.program.set_used();
+        let mut unit = 0;
+        for (name, value) in self.properties() {
+            // Validated in `set` against the same program
+            if let Some(uniform) = self.program.uniform(name) {
+                value.upload(&self.program, uniform, &mut unit);
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{cell::RefCell, f32::consts::TAU};
+
+use glam::{Vec2, Vec3};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+use crate::core::color::Color;
+
+// Seed used until an application or test picks another, so runs are reproducible by default
+pub const DEFAULT_SEED: u64 = 0x1707_61e5_eed5_eed5;
+
+thread_local! {
+    // Like the GL state, randomness is per thread so each test can seed its own
+    static SOURCE: RefCell<Box<dyn RngCore>> =
+        RefCell::new(Box::new(StdRng::seed_from_u64(DEFAULT_SEED)));
+}
+
+// Run `f` with the random source of this thread. Do not nest calls
+pub fn with<R>(f: impl FnOnce(&mut dyn RngCore) -> R) -> R {
+    SOURCE.with_borrow_mut(|source| f(source.as_mut()))
+}
+
+// Restart the sequence, the same seed gives the same values on every run
+pub fn seed(seed: u64) {
+    set_source(StdRng::seed_from_u64(seed));
+}
+
+// Different values on every run
+pub fn seed_from_entropy() {
+    set_source(StdRng::from_rng(&mut rand::rng()));
+}
+
+// Replace the source, such as with a mock generator in tests
+pub fn set_source(source: impl RngCore + 'static) {
+    SOURCE.set(Box::new(source));
+}
+
+// Shapes and colors sampled uniformly, for any generator
+pub trait RandomExt: Rng {
+    // Opaque color with every channel in [0, 1]
+    fn random_color(&mut self) -> Color {
+        Color::rgb(
+            self.random_range(0.0..=1.0),
+            self.random_range(0.0..=1.0),
+            self.random_range(0.0..=1.0),
+        )
+    }
+
+    // Opaque color of any hue with the given saturation and value
+    fn random_hue(&mut self, saturation: f32, value: f32) -> Color {
+        Color::from_hsv(self.random_range(0.0..360.0), saturation, value, 1.0)
+    }
+
+    // Unit vector, uniform over the sphere
+    fn random_direction(&mut self) -> Vec3 {
+        let z = self.random_range(-1.0..=1.0_f32);
+        let angle = self.random_range(0.0..TAU);
+        let radius = (1.0 - z * z).sqrt();
+        Vec3::new(radius * angle.cos(), radius * angle.sin(), z)
+    }
+
+    // Point in the disc of `radius` around the origin
+    fn random_in_disc(&mut self, radius: f32) -> Vec2 {
+        // The square root spreads the points evenly instead of bunching them at the center
+        let distance = radius * self.random_range(0.0..=1.0_f32).sqrt();
+        let angle = self.random_range(0.0..TAU);
+        Vec2::from_angle(angle) * distance
+    }
+
+    // Point in the ball of `radius` around the origin
+    fn random_in_sphere(&mut self, radius: f32) -> Vec3 {
+        let distance = radius * self.random_range(0.0..=1.0_f32).cbrt();
+        self.random_direction() * distance
+    }
+
+    // Unit vector at most `half_angle` radians away from `axis`, uniform over that cap
+    fn random_in_cone(&mut self, axis: Vec3, half_angle: f32) -> Vec3 {
+        let axis = axis.normalize();
+        let cos_theta = self.random_range(half_angle.cos()..=1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let angle = self.random_range(0.0..TAU);
+        let (tangent, bitangent) = axis.any_orthonormal_pair();
+        axis * cos_theta + (tangent * angle.cos() + bitangent * angle.sin()) * sin_theta
+    }
+}
+
+impl<R: Rng + ?Sized> RandomExt for R {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts up from its start value, so tests know exactly what the source returns
+    struct Counter(u64);
+
+    impl RngCore for Counter {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 += 1;
+            self.0 - 1
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            for chunk in dst.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_values() {
+        seed(7);
+        let first: Vec<u32> = with(|rng| (0..8).map(|_| rng.next_u32()).collect());
+        seed(7);
+        let second: Vec<u32> = with(|rng| (0..8).map(|_| rng.next_u32()).collect());
+        seed(8);
+        let other: Vec<u32> = with(|rng| (0..8).map(|_| rng.next_u32()).collect());
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn set_source_overrides_seed() {
+        set_source(Counter(5));
+        assert_eq!(with(|rng| rng.next_u64()), 5);
+        assert_eq!(with(|rng| rng.next_u64()), 6);
+        seed(DEFAULT_SEED);
+    }
+
+    #[test]
+    fn shapes_stay_in_bounds() {
+        seed(1);
+        with(|rng| {
+            for _ in 0..1000 {
+                assert!((rng.random_direction().length() - 1.0).abs() < 1e-5);
+                assert!(rng.random_in_disc(2.0).length() <= 2.0 + 1e-5);
+                assert!(rng.random_in_sphere(3.0).length() <= 3.0 + 1e-5);
+                let direction = rng.random_in_cone(Vec3::Y * 2.0, 0.3);
+                assert!(direction.angle_between(Vec3::Y) <= 0.3 + 1e-4);
+            }
+        });
+    }
+}
@@ -0,0 +1,288 @@
+use std::rc::Rc;
+
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::{
+    camera::camera::Camera,
+    core::{
+        object::{Handle, Object},
+        render_state::BlendState,
+        state,
+    },
+    error::check_gl_error,
+    geometry::mesh::Mesh,
+    material::{Material, Property},
+    scene::scene::{NodeId, Scene},
+    shader::Program,
+};
+
+// Uniforms set once per program and frame, when the program declares them
+pub const VIEW_MATRIX: &str = "ViewMatrix";
+pub const PROJECTION_MATRIX: &str = "ProjectionMatrix";
+pub const VIEW_PROJECTION_MATRIX: &str = "ViewProjectionMatrix";
+pub const CAMERA_POSITION: &str = "CameraPosition";
+pub const TIME: &str = "Time";
+
+// Uniforms set for every draw item, when the program declares them
+pub const WORLD_MATRIX: &str = "WorldMatrix";
+pub const WORLD_VIEW_PROJECTION_MATRIX: &str = "WorldViewProjectionMatrix";
+// Inverse transpose of the world matrix, for normals
+pub const NORMAL_MATRIX: &str = "NormalMatrix";
+
+// One submesh drawn with a material at a world transform
+#[derive(Clone, Debug)]
+pub struct DrawItem {
+    pub mesh: Rc<Mesh>,
+    pub submesh: usize,
+    pub material: Rc<Material>,
+    pub transform: Mat4,
+}
+
+impl DrawItem {
+    #[must_use]
+    pub const fn new(
+        mesh: Rc<Mesh>,
+        submesh: usize,
+        material: Rc<Material>,
+        transform: Mat4,
+    ) -> Self {
+        Self {
+            mesh,
+            submesh,
+            material,
+            transform,
+        }
+    }
+
+    fn vao(&self) -> Handle {
+        let submesh = &self.mesh.submeshes()[self.submesh];
+        self.mesh.vertex_array(submesh.vao()).handle()
+    }
+}
+
+// State changes done by the last `Renderer::render`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    pub program_changes: usize,
+    pub material_changes: usize,
+    pub vao_changes: usize,
+}
+
+// Queued draw item with the keys it is sorted by
+#[derive(Debug)]
+struct QueueEntry {
+    item: DrawItem,
+    program: Handle,
+    material: usize,
+    vao: Handle,
+    // View space distance in front of the camera
+    depth: f32,
+}
+
+// Forward renderer: queue draw items every frame and `render` them in an order that keeps state
+// changes low. Opaque items are grouped by program, material and VAO and drawn front to back
+// inside each group, transparent items are blended back to front after them
+#[derive(Debug, Default)]
+pub struct Renderer {
+    items: Vec<DrawItem>,
+    stats: RenderStats,
+}
+
+impl Renderer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn submit(&mut self, item: DrawItem) {
+        assert!(
+            item.submesh < item.mesh.submesh_count(),
+            "Invalid submesh {}",
+            item.submesh
+        );
+        self.items.push(item);
+    }
+
+    // Queue every submesh, picking its material by the submesh material slot. Slots past the end
+    // of `materials` use the first material
+    pub fn submit_mesh(&mut self, mesh: &Rc<Mesh>, materials: &[Rc<Material>], transform: Mat4) {
+        for (index, submesh) in mesh.submeshes().iter().enumerate() {
+            let Some(material) = materials
+                .get(submesh.material())
+                .or_else(|| materials.first())
+            else {
+                return;
+            };
+            self.submit(DrawItem::new(
+                Rc::clone(mesh),
+                index,
+                Rc::clone(material),
+                transform,
+            ));
+        }
+    }
+
+    // Queue the visible meshes of a scene, `materials` gives the materials of each node
+    pub fn submit_scene<'a>(
+        &mut self,
+        scene: &Scene,
+        materials: impl Fn(NodeId) -> &'a [Rc<Material>],
+    ) {
+        for (id, mesh, transform) in scene.meshes() {
+            self.submit_mesh(mesh, materials(id), transform);
+        }
+    }
+
+    #[must_use]
+    pub fn queued(&self) -> &[DrawItem] {
+        &self.items
+    }
+
+    #[must_use]
+    pub const fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    // Draw everything queued from `camera` and empty the queue
+    pub fn render(&mut self, camera: &Camera, time: f32) -> RenderStats {
+        let view = camera.view_matrix();
+        let (mut opaque, mut transparent): (Vec<_>, Vec<_>) = self
+            .items
+            .drain(..)
+            .map(|item| QueueEntry {
+                program: item.material.program().id(),
+                material: Rc::as_ptr(&item.material) as usize,
+                vao: item.vao(),
+                depth: -view.transform_point3(item.transform.w_axis.truncate()).z,
+                item,
+            })
+            .partition(|entry| !entry.item.material.is_transparent());
+
+        opaque.sort_by(|a, b| {
+            (a.program, a.material, a.vao)
+                .cmp(&(b.program, b.material, b.vao))
+                .then_with(|| a.depth.total_cmp(&b.depth))
+        });
+        transparent.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+
+        let frame = FrameUniforms {
+            view,
+            projection: camera.projection_matrix(),
+            camera_position: camera.position(),
+            time,
+        };
+        let mut pass = Pass::new(&frame);
+        pass.draw(&opaque);
+        if !transparent.is_empty() {
+            // Blend without writing depth, then leave blending as the caller had it
+            let saved = state::with(|state| {
+                let saved = state.blending();
+                BlendState::ALPHA.apply(state);
+                state.set_depth_mask(false);
+                saved
+            });
+            pass.draw(&transparent);
+            state::with(|state| state.restore_blending(saved));
+        }
+        self.stats = pass.stats;
+        self.stats
+    }
+}
+
+#[derive(Debug)]
+struct FrameUniforms {
+    view: Mat4,
+    projection: Mat4,
+    camera_position: Vec3,
+    time: f32,
+}
+
+// Tracks what is bound while drawing the sorted queues
+#[derive(Debug)]
+struct Pass<'a> {
+    frame: &'a FrameUniforms,
+    program: Option<Handle>,
+    material: Option<usize>,
+    vao: Option<Handle>,
+    stats: RenderStats,
+}
+
+impl<'a> Pass<'a> {
+    const fn new(frame: &'a FrameUniforms) -> Self {
+        Self {
+            frame,
+            program: None,
+            material: None,
+            vao: None,
+            stats: RenderStats {
+                draw_calls: 0,
+                program_changes: 0,
+                material_changes: 0,
+                vao_changes: 0,
+            },
+        }
+    }
+
+    fn draw(&mut self, entries: &[QueueEntry]) {
+        for entry in entries {
+            let item = &entry.item;
+            let program = item.material.program();
+            if self.material != Some(entry.material) {
+                // Also makes the program current
+                item.material.apply();
+                self.material = Some(entry.material);
+                self.stats.material_changes += 1;
+            }
+            if self.program != Some(entry.program) {
+                self.set_frame_uniforms(program);
+                self.program = Some(entry.program);
+                self.stats.program_changes += 1;
+            }
+            self.set_object_uniforms(program, &item.transform);
+
+            let submesh = &item.mesh.submeshes()[item.submesh];
+            if self.vao != Some(entry.vao) {
+                item.mesh.vertex_array(submesh.vao()).bind();
+                self.vao = Some(entry.vao);
+                self.stats.vao_changes += 1;
+            }
+            submesh.draw_call().draw();
+            self.stats.draw_calls += 1;
+        }
+    }
+
+    fn set_frame_uniforms(&self, program: &Program) {
+        let frame = self.frame;
+        set_if_declared(program, VIEW_MATRIX, &frame.view.into());
+        set_if_declared(program, PROJECTION_MATRIX, &frame.projection.into());
+        let view_projection = frame.projection * frame.view;
+        set_if_declared(program, VIEW_PROJECTION_MATRIX, &view_projection.into());
+        set_if_declared(program, CAMERA_POSITION, &frame.camera_position.into());
+        set_if_declared(program, TIME, &frame.time.into());
+    }
+
+    fn set_object_uniforms(&self, program: &Program, world: &Mat4) {
+        set_if_declared(program, WORLD_MATRIX, &(*world).into());
+        let world_view_projection = self.frame.projection * self.frame.view * *world;
+        set_if_declared(
+            program,
+            WORLD_VIEW_PROJECTION_MATRIX,
+            &world_view_projection.into(),
+        );
+        if program.uniform(NORMAL_MATRIX).is_some() {
+            let normal = Mat3::from_mat4(*world).inverse().transpose();
+            set_if_declared(program, NORMAL_MATRIX, &normal.into());
+        }
+    }
+}
+
+// The program must be in use, values of the wrong type are skipped
+fn set_if_declared(program: &Program, name: &str, value: &Property) {
+    if let Some(uniform) = program.uniform(name) {
+        if value.matches(uniform.kind) {
+            value.upload(program, uniform, &mut 0);
+        }
+    }
+    check_gl_error();
+}
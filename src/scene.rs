@@ -0,0 +1,4 @@
+pub mod light;
+#[allow(clippy::module_inception)]
+pub mod scene;
+pub mod transform;
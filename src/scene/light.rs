@@ -0,0 +1,50 @@
+use glam::{Mat4, Vec3};
+
+use crate::core::color::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // Cone angles in radians from the light direction
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// Lights shine down the -Z axis of the node they are attached to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Color,
+    pub intensity: f32,
+    // None for infinite range
+    pub range: Option<f32>,
+}
+
+impl Light {
+    #[must_use]
+    pub const fn new(kind: LightKind, color: Color, intensity: f32) -> Self {
+        Self {
+            kind,
+            color,
+            intensity,
+            range: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    // World direction the light points to when placed with `world`
+    #[must_use]
+    pub fn direction(world: &Mat4) -> Vec3 {
+        world
+            .transform_vector3(Vec3::NEG_Z)
+            .normalize_or(Vec3::NEG_Z)
+    }
+}
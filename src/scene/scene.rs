@@ -0,0 +1,472 @@
+use std::{cell::Cell, rc::Rc};
+
+use glam::{Mat4, Vec3};
+
+use crate::{
+    camera::camera::Camera,
+    geometry::{mesh::Mesh, processing::BoundingBox},
+};
+
+use super::{light::Light, transform::Transform};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug)]
+pub struct Node {
+    name: String,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    mesh: Option<Rc<Mesh>>,
+    light: Option<Light>,
+    camera: Option<Camera>,
+    bounds: Option<BoundingBox>,
+    visible: bool,
+    // Cached parent world * local, a dirty node always has all its descendants dirty too
+    world: Cell<Mat4>,
+    dirty: Cell<bool>,
+}
+
+impl Node {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            transform: Transform::IDENTITY,
+            parent: None,
+            children: vec![],
+            mesh: None,
+            light: None,
+            camera: None,
+            bounds: None,
+            visible: true,
+            world: Cell::new(Mat4::IDENTITY),
+            dirty: Cell::new(true),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    #[must_use]
+    pub fn with_mesh(mut self, mesh: Rc<Mesh>) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_light(mut self, light: Light) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    // Local space bounds, used for picking and culling
+    #[must_use]
+    pub const fn with_bounds(mut self, bounds: BoundingBox) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        name.clone_into(&mut self.name);
+    }
+
+    #[must_use]
+    pub const fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    #[must_use]
+    pub const fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    #[must_use]
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    #[must_use]
+    pub const fn mesh(&self) -> Option<&Rc<Mesh>> {
+        self.mesh.as_ref()
+    }
+
+    pub fn set_mesh(&mut self, mesh: Option<Rc<Mesh>>) {
+        self.mesh = mesh;
+    }
+
+    #[must_use]
+    pub const fn light(&self) -> Option<&Light> {
+        self.light.as_ref()
+    }
+
+    pub const fn set_light(&mut self, light: Option<Light>) {
+        self.light = light;
+    }
+
+    // Only the projection of the attached camera is used, it is placed by the node
+    #[must_use]
+    pub const fn camera(&self) -> Option<&Camera> {
+        self.camera.as_ref()
+    }
+
+    pub const fn set_camera(&mut self, camera: Option<Camera>) {
+        self.camera = camera;
+    }
+
+    #[must_use]
+    pub const fn bounds(&self) -> Option<&BoundingBox> {
+        self.bounds.as_ref()
+    }
+
+    pub const fn set_bounds(&mut self, bounds: Option<BoundingBox>) {
+        self.bounds = bounds;
+    }
+
+    // Hidden nodes and their whole subtree are skipped when rendering and picking
+    #[must_use]
+    pub const fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub const fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+}
+
+// Node hierarchy, world matrices are computed lazily and cached until a transform above changes
+#[derive(Debug, Default)]
+pub struct Scene {
+    // Removed nodes leave an empty slot so the other ids stay valid
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add a node under `parent`, or as a root, returns its id
+    pub fn add_node(&mut self, parent: Option<NodeId>, mut node: Node) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        node.dirty.set(true);
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(Some(node));
+        id
+    }
+
+    // Remove a node with all its descendants
+    pub fn remove_node(&mut self, id: NodeId) {
+        self.detach(id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    // Move a node under another parent, or make it a root, keeping its local transform
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        if let Some(parent) = parent {
+            assert!(
+                !self.iter_from(id).any(|node| node == parent),
+                "Cannot parent node {id:?} under its own descendant {parent:?}"
+            );
+        }
+        self.detach(id);
+        self.node_mut(id).parent = parent;
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.mark_dirty(id);
+    }
+
+    fn detach(&mut self, id: NodeId) {
+        match self.node(id).parent {
+            Some(parent) => self.node_mut(parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes.get(id.0).is_some_and(Option::is_some)
+    }
+
+    #[must_use]
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.nodes
+            .get(id.0)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| panic!("Invalid node {id:?}"))
+    }
+
+    // Transforms can only be changed through the scene, so the cached matrices stay valid
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .unwrap_or_else(|| panic!("Invalid node {id:?}"))
+    }
+
+    #[must_use]
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|&id| self.node(id).name == name)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.node_mut(id).transform = transform;
+        self.mark_dirty(id);
+    }
+
+    // Mark the node and its descendants, stopping at subtrees that are already dirty
+    fn mark_dirty(&self, id: NodeId) {
+        let node = self.node(id);
+        node.dirty.set(true);
+        let mut stack = node.children.clone();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if !node.dirty.replace(true) {
+                stack.extend_from_slice(&node.children);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn world_matrix(&self, id: NodeId) -> Mat4 {
+        let node = self.node(id);
+        if node.dirty.get() {
+            let parent = node
+                .parent
+                .map_or(Mat4::IDENTITY, |parent| self.world_matrix(parent));
+            node.world.set(parent * node.transform.matrix());
+            node.dirty.set(false);
+        }
+        node.world.get()
+    }
+
+    // World space bounds of a node with bounds
+    #[must_use]
+    pub fn world_bounds(&self, id: NodeId) -> Option<BoundingBox> {
+        let bounds = self.node(id).bounds?;
+        Some(bounds.transformed(&self.world_matrix(id)))
+    }
+
+    // Attached camera placed at the world position and orientation of its node
+    #[must_use]
+    pub fn world_camera(&self, id: NodeId) -> Option<Camera> {
+        let mut camera = *self.node(id).camera.as_ref()?;
+        let (_, rotation, translation) = self.world_matrix(id).to_scale_rotation_translation();
+        camera.set_position(translation);
+        camera.set_orientation(rotation);
+        Some(camera)
+    }
+
+    // All nodes depth first, parents before their children
+    #[must_use]
+    pub fn iter(&self) -> DepthFirst<'_> {
+        DepthFirst::new(self, self.roots.clone(), false)
+    }
+
+    // A node and its descendants, depth first
+    #[must_use]
+    pub fn iter_from(&self, id: NodeId) -> DepthFirst<'_> {
+        DepthFirst::new(self, vec![id], false)
+    }
+
+    // Nodes that are not hidden themselves or by an ancestor
+    #[must_use]
+    pub fn iter_visible(&self) -> DepthFirst<'_> {
+        DepthFirst::new(self, self.roots.clone(), true)
+    }
+
+    // Visible meshes with their world matrix, for rendering
+    pub fn meshes(&self) -> impl Iterator<Item = (NodeId, &Rc<Mesh>, Mat4)> {
+        self.iter_visible().filter_map(|id| {
+            let mesh = self.node(id).mesh.as_ref()?;
+            Some((id, mesh, self.world_matrix(id)))
+        })
+    }
+
+    // Visible lights with their world matrix
+    pub fn lights(&self) -> impl Iterator<Item = (NodeId, &Light, Mat4)> {
+        self.iter_visible().filter_map(|id| {
+            let light = self.node(id).light.as_ref()?;
+            Some((id, light, self.world_matrix(id)))
+        })
+    }
+
+    // Visible nodes whose world bounds are hit by the ray, with the distance along it, unsorted
+    pub fn ray_hits(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+    ) -> impl Iterator<Item = (NodeId, f32)> + '_ {
+        self.iter_visible().filter_map(move |id| {
+            let distance = self.world_bounds(id)?.intersect_ray(origin, direction)?;
+            Some((id, distance))
+        })
+    }
+
+    // Closest visible node hit by the ray, see `Camera::screen_ray` to pick with the cursor
+    #[must_use]
+    pub fn pick(&self, origin: Vec3, direction: Vec3) -> Option<(NodeId, f32)> {
+        self.ray_hits(origin, direction)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+// Pre-order traversal, `visible_only` skips hidden subtrees
+#[derive(Debug)]
+pub struct DepthFirst<'a> {
+    scene: &'a Scene,
+    stack: Vec<NodeId>,
+    visible_only: bool,
+}
+
+impl<'a> DepthFirst<'a> {
+    fn new(scene: &'a Scene, mut start: Vec<NodeId>, visible_only: bool) -> Self {
+        start.reverse();
+        Self {
+            scene,
+            stack: start,
+            visible_only,
+        }
+    }
+}
+
+impl Iterator for DepthFirst<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        loop {
+            let id = self.stack.pop()?;
+            let node = self.scene.node(id);
+            if self.visible_only && !node.visible {
+                continue;
+            }
+            self.stack.extend(node.children.iter().rev());
+            return Some(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // root -> child -> grandchild, each moved one unit along its own axis
+    fn chain() -> (Scene, [NodeId; 3]) {
+        let mut scene = Scene::new();
+        let root = scene.add_node(
+            None,
+            Node::new("root").with_transform(Transform::from_translation(Vec3::X)),
+        );
+        let child = scene.add_node(
+            Some(root),
+            Node::new("child").with_transform(Transform::from_translation(Vec3::Y)),
+        );
+        let grandchild = scene.add_node(
+            Some(child),
+            Node::new("grandchild").with_transform(Transform::from_translation(Vec3::Z)),
+        );
+        (scene, [root, child, grandchild])
+    }
+
+    fn position(scene: &Scene, id: NodeId) -> Vec3 {
+        scene.world_matrix(id).transform_point3(Vec3::ZERO)
+    }
+
+    #[test]
+    fn ancestor_changes_reach_cached_matrices() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        assert_eq!(position(&scene, grandchild), Vec3::ONE);
+        // Every matrix is cached now, moving the root must still reach the grandchild
+        scene.set_transform(root, Transform::from_translation(Vec3::NEG_X));
+        assert_eq!(position(&scene, grandchild), Vec3::new(-1.0, 1.0, 1.0));
+        // Only the child was computed again, its dirty descendants must not be skipped later
+        scene.set_transform(root, Transform::from_scale(Vec3::splat(2.0)));
+        assert_eq!(position(&scene, child), Vec3::new(0.0, 2.0, 0.0));
+        scene.set_transform(child, Transform::IDENTITY);
+        assert_eq!(position(&scene, grandchild), Vec3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn reparenting_keeps_local_transforms() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        assert_eq!(position(&scene, grandchild), Vec3::ONE);
+        scene.set_parent(grandchild, Some(root));
+        assert_eq!(position(&scene, grandchild), Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(scene.node(child).children(), []);
+        scene.set_parent(grandchild, None);
+        assert_eq!(position(&scene, grandchild), Vec3::Z);
+        assert_eq!(scene.roots(), [root, grandchild]);
+
+        scene.set_parent(grandchild, Some(child));
+        scene.remove_node(child);
+        assert!(!scene.contains(child) && !scene.contains(grandchild));
+        assert_eq!(scene.node(root).children(), []);
+        assert_eq!(scene.find("grandchild"), None);
+        assert_eq!(scene.iter().collect::<Vec<_>>(), [root]);
+    }
+
+    #[test]
+    #[should_panic = "under its own descendant"]
+    fn reject_parenting_cycles() {
+        let (mut scene, [root, _, grandchild]) = chain();
+        scene.set_parent(root, Some(grandchild));
+    }
+
+    #[test]
+    fn hidden_subtrees_are_skipped() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        let sibling = scene.add_node(Some(root), Node::new("sibling"));
+        assert_eq!(
+            scene.iter().collect::<Vec<_>>(),
+            [root, child, grandchild, sibling]
+        );
+        scene.node_mut(child).set_visible(false);
+        assert_eq!(scene.iter_visible().collect::<Vec<_>>(), [root, sibling]);
+        assert_eq!(scene.iter_from(child).count(), 2);
+
+        // Picking ignores the hidden grandchild even though it is closer
+        let bounds = BoundingBox::new(Vec3::splat(-0.5), Vec3::splat(0.5));
+        scene.node_mut(grandchild).set_bounds(Some(bounds));
+        scene.node_mut(sibling).set_bounds(Some(bounds));
+        let origin = Vec3::new(1.0, 0.0, -10.0);
+        assert_eq!(scene.pick(origin, Vec3::Z), Some((sibling, 9.5)));
+        scene.node_mut(child).set_visible(true);
+        assert_eq!(scene.ray_hits(origin, Vec3::Z).count(), 1);
+        let origin = Vec3::new(1.0, 1.0, -10.0);
+        assert_eq!(scene.pick(origin, Vec3::Z), Some((grandchild, 10.5)));
+    }
+}
@@ -2,7 +2,7 @@ use std::ffi::{CStr, CString};
 
 use gl::types;
 
-use crate::error::check_gl_error;
+use crate::{core::state, error::check_gl_error};
 
 pub type Location = gl::types::GLint;
 
@@ -76,9 +76,22 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
     unsafe { CString::from_vec_unchecked(buffer) }
 }
 
+// Active uniform found by reflection after linking
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Uniform {
+    // Arrays are named without the trailing [0]
+    pub name: String,
+    pub location: Location,
+    // GL type such as gl::FLOAT_VEC3 or gl::SAMPLER_2D
+    pub kind: types::GLenum,
+    // Number of elements, 1 unless it is an array
+    pub size: types::GLint,
+}
+
 #[derive(Debug)]
 pub struct Program {
     id: gl::types::GLuint,
+    uniforms: Vec<Uniform>,
 }
 impl Drop for Program {
     fn drop(&mut self) {
@@ -86,6 +99,7 @@ impl Drop for Program {
             gl::DeleteProgram(self.id);
             check_gl_error();
         }
+        state::with(|state| state.forget_program(self.id));
     }
 }
 
@@ -140,13 +154,14 @@ impl Program {
             check_gl_error();
         }
 
-        Ok(Self { id: program_id })
+        let uniforms = active_uniforms(program_id);
+        Ok(Self {
+            id: program_id,
+            uniforms,
+        })
     }
     pub fn set_used(&self) {
-        unsafe {
-            gl::UseProgram(self.id);
-        }
-        check_gl_error();
+        state::with(|state| state.use_program(self.id));
     }
 
     #[must_use]
@@ -158,6 +173,17 @@ impl Program {
         unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) }
     }
 
+    // Uniforms in the default block, uniforms inside blocks have no location and are skipped
+    #[must_use]
+    pub fn uniforms(&self) -> &[Uniform] {
+        &self.uniforms
+    }
+
+    #[must_use]
+    pub fn uniform(&self, name: &str) -> Option<&Uniform> {
+        self.uniforms.iter().find(|uniform| uniform.name == name)
+    }
+
     pub fn get_attribute_location(&self, name: &CStr) -> Location {
         unsafe { gl::GetAttribLocation(self.id, name.as_ptr()) }
     }
@@ -187,4 +213,65 @@ impl Program {
     ) {
         unsafe { gl::Uniform4f(loc, v0, v1, v3, v4) }
     }
+    pub fn set_uniform1i(&self, loc: Location, value: types::GLint) {
+        unsafe { gl::Uniform1i(loc, value) }
+    }
+    pub fn set_uniform1ui(&self, loc: Location, value: types::GLuint) {
+        unsafe { gl::Uniform1ui(loc, value) }
+    }
+    // Matrices are column major, like glam stores them
+    pub fn set_uniform_matrix2f(&self, loc: Location, value: &[types::GLfloat; 4]) {
+        unsafe { gl::UniformMatrix2fv(loc, 1, gl::FALSE, value.as_ptr()) }
+    }
+    pub fn set_uniform_matrix3f(&self, loc: Location, value: &[types::GLfloat; 9]) {
+        unsafe { gl::UniformMatrix3fv(loc, 1, gl::FALSE, value.as_ptr()) }
+    }
+    pub fn set_uniform_matrix4f(&self, loc: Location, value: &[types::GLfloat; 16]) {
+        unsafe { gl::UniformMatrix4fv(loc, 1, gl::FALSE, value.as_ptr()) }
+    }
+}
+
+fn active_uniforms(program_id: gl::types::GLuint) -> Vec<Uniform> {
+    let mut count: gl::types::GLint = 0;
+    let mut max_length: gl::types::GLint = 0;
+    unsafe {
+        gl::GetProgramiv(program_id, gl::ACTIVE_UNIFORMS, &mut count);
+        gl::GetProgramiv(program_id, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+    }
+    check_gl_error();
+
+    let mut uniforms = vec![];
+    let mut buffer = vec![0u8; max_length.max(1) as usize];
+    for index in 0..count as gl::types::GLuint {
+        let mut length: gl::types::GLsizei = 0;
+        let mut size: gl::types::GLint = 0;
+        let mut kind: gl::types::GLenum = 0;
+        unsafe {
+            gl::GetActiveUniform(
+                program_id,
+                index,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut kind,
+                buffer.as_mut_ptr().cast(),
+            );
+        }
+        check_gl_error();
+        let name = String::from_utf8_lossy(&buffer[..length as usize]);
+        let name = name.strip_suffix("[0]").unwrap_or(&name).to_owned();
+        let Ok(c_name) = CString::new(name.as_str()) else {
+            continue;
+        };
+        let location = unsafe { gl::GetUniformLocation(program_id, c_name.as_ptr()) };
+        if location >= 0 {
+            uniforms.push(Uniform {
+                name,
+                location,
+                kind,
+                size,
+            });
+        }
+    }
+    uniforms
 }
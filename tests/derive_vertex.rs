@@ -0,0 +1,100 @@
+use std::mem::{offset_of, size_of};
+
+use glam::{Vec2, Vec3};
+use itugl::{
+    core::{color::Color, data::Type},
+    geometry::vertex_layout::{Vertex, VertexLayout},
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct Particle {
+    position: Vec3,
+    #[skip]
+    _padding: f32,
+    #[normalized]
+    weights: [u8; 4],
+    #[location = 4]
+    uv: Vec2,
+    color: Color,
+    #[divisor = 1]
+    #[location = 2]
+    index: u32,
+}
+
+// Location, offset, type, components, normalized and divisor of each attribute
+fn attributes(layout: &VertexLayout) -> Vec<(u32, i32, Type, i32, bool, u32)> {
+    layout
+        .attributes()
+        .iter()
+        .map(|(location, layout)| {
+            let attribute = layout.attribute();
+            assert_eq!(layout.stride(), size_of::<Particle>() as i32);
+            (
+                *location,
+                layout.offset(),
+                attribute.data_type(),
+                attribute.components(),
+                attribute.is_normalized(),
+                attribute.divisor(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn layout_matches_the_struct() {
+    let layout = Particle::layout();
+    assert_eq!(layout.stride(), size_of::<Particle>() as i32);
+    let offset = |offset: usize| offset as i32;
+    assert_eq!(
+        attributes(&layout),
+        [
+            (
+                0,
+                offset(offset_of!(Particle, position)),
+                Type::Float,
+                3,
+                false,
+                0
+            ),
+            (
+                1,
+                offset(offset_of!(Particle, weights)),
+                Type::UByte,
+                4,
+                true,
+                0
+            ),
+            (
+                4,
+                offset(offset_of!(Particle, uv)),
+                Type::Float,
+                2,
+                false,
+                0
+            ),
+            (
+                5,
+                offset(offset_of!(Particle, color)),
+                Type::Float,
+                4,
+                false,
+                0
+            ),
+            (
+                2,
+                offset(offset_of!(Particle, index)),
+                Type::UInt,
+                1,
+                false,
+                1
+            ),
+        ]
+    );
+}
+
+#[test]
+fn invalid_derives_do_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,13 @@
+use glam::{Vec2, Vec3};
+use itugl::geometry::vertex_layout::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct Point {
+    #[location = 1]
+    position: Vec3,
+    #[location = 1]
+    uv: Vec2,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use glam::Vec3;
+use itugl::geometry::vertex_layout::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+struct Point {
+    position: Vec3,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use glam::Vec3;
+use itugl::geometry::vertex_layout::{Vertex, VertexLayout};
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct Point {
+    #[normalized]
+    position: Vec3,
+}
+
+fn main() {
+    let _: VertexLayout = Point::layout();
+}
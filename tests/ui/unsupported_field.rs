@@ -0,0 +1,11 @@
+use glam::Vec3;
+use itugl::geometry::vertex_layout::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct Point {
+    position: Vec3,
+    visible: bool,
+}
+
+fn main() {}